@@ -143,6 +143,8 @@ pub struct Context {
     pub session_id: Option<String>,
     /// Dense embedding vector.
     pub vector: Option<Vec<f32>>,
+    /// Sparse embedding: term -> weight.
+    pub sparse_vector: Option<HashMap<String, f32>>,
     /// Vectorization payload (not persisted to JSON).
     #[serde(skip)]
     pub vectorize: Vectorize,
@@ -171,6 +173,7 @@ impl Context {
             meta: HashMap::new(),
             session_id: None,
             vector: None,
+            sparse_vector: None,
             vectorize: Vectorize { text: abs },
         }
     }
@@ -368,6 +371,7 @@ impl ContextBuilder {
             meta: self.meta,
             session_id: self.session_id,
             vector: None,
+            sparse_vector: None,
             vectorize: Vectorize {
                 text: self.abstract_text,
             },
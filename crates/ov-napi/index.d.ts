@@ -59,6 +59,13 @@ export interface VectorSearchResult {
   score: number
 }
 export declare function vectorSearch(query: Array<number>, vectorsJson: string, topK?: number | undefined | null): Array<VectorSearchResult>
+/**
+ * Check a filter expression against a collection schema before searching:
+ * `schema_json` is a collection config (`{"name", "fields"}`), the context
+ * collection schema if omitted. The error names the JSON path of the first
+ * problem, e.g. `$.conds[1].op`.
+ */
+export declare function validateFilter(filterJson: string, schemaJson?: string | undefined | null): void
 export interface ExtractedMemory {
   category: string
   content: string
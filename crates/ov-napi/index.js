@@ -310,7 +310,7 @@ if (!nativeBinding) {
   throw new Error(`Failed to load native binding`)
 }
 
-const { ping, addMemory, searchMemory, createSession, getSession, listSessions, addSessionMessage, closeSession, compress, compressDetailed, route, vectorSearch, extractMemories } = nativeBinding
+const { ping, addMemory, searchMemory, createSession, getSession, listSessions, addSessionMessage, closeSession, compress, compressDetailed, route, vectorSearch, validateFilter, extractMemories } = nativeBinding
 
 module.exports.ping = ping
 module.exports.addMemory = addMemory
@@ -324,4 +324,5 @@ module.exports.compress = compress
 module.exports.compressDetailed = compressDetailed
 module.exports.route = route
 module.exports.vectorSearch = vectorSearch
+module.exports.validateFilter = validateFilter
 module.exports.extractMemories = extractMemories
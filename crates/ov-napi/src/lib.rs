@@ -22,6 +22,19 @@ fn ov_err_to_napi(e: ov_core::OvError) -> napi::Error {
     napi::Error::new(Status::GenericFailure, format!("[{}] {}", code, msg))
 }
 
+fn vdb_err_to_napi(e: ov_vectordb::error::VectorDbError) -> napi::Error {
+    use ov_vectordb::error::VectorDbError;
+    let (status, code) = match &e {
+        VectorDbError::InvalidFilter { .. } => (Status::InvalidArg, "ERR_INVALID_FILTER"),
+        VectorDbError::DimensionMismatch { .. } | VectorDbError::InvalidConfig(_) => (Status::InvalidArg, "ERR_INVALID_ARG"),
+        VectorDbError::CollectionNotFound(_) | VectorDbError::IndexNotFound(_) | VectorDbError::ProjectNotFound(_) => {
+            (Status::GenericFailure, "ERR_NOT_FOUND")
+        }
+        _ => (Status::GenericFailure, "ERR_STORAGE"),
+    };
+    napi::Error::new(status, format!("[{}] {}", code, e))
+}
+
 // ========== Global Session Manager ==========
 
 fn global_session_manager() -> &'static Arc<RwLock<ov_session::SessionManager>> {
@@ -462,6 +475,24 @@ pub fn vector_search(query: Vec<f64>, vectors_json: String, top_k: Option<u32>)
     Ok(scores.into_iter().map(|(id, score)| VectorSearchResult { id, score }).collect())
 }
 
+/// Check a filter expression against a collection schema before searching:
+/// `schema_json` is a collection config (`{"name", "fields"}`), the context
+/// collection schema if omitted. The error names the JSON path of the first
+/// problem, e.g. `$.conds[1].op`.
+#[napi]
+pub fn validate_filter(filter_json: String, schema_json: Option<String>) -> Result<()> {
+    let filter: serde_json::Value = serde_json::from_str(&filter_json)
+        .map_err(|e| napi::Error::new(Status::InvalidArg, format!("Invalid JSON: {}", e)))?;
+    let config: ov_vectordb::CollectionConfig = match schema_json {
+        Some(json) => serde_json::from_str(&json)
+            .map_err(|e| napi::Error::new(Status::InvalidArg, format!("Invalid schema: {}", e)))?,
+        // Vector fields cannot be filtered, so their dimension does not matter.
+        None => ov_storage::context_collection_schema("context", 0).to_collection_config(),
+    };
+    ov_vectordb::filter::Filter::from_json_with_schema(&filter, &config).map_err(vdb_err_to_napi)?;
+    Ok(())
+}
+
 // ========== Memory Extraction ==========
 
 #[napi(object)]
@@ -711,4 +742,27 @@ mod tests {
         let msg = format!("{}", ne);
         assert!(msg.contains("ERR_INVALID_ARG"));
     }
+
+    #[test]
+    fn test_validate_filter() {
+        assert!(validate_filter(r#"{"op": "must", "field": "tags", "conds": ["a"]}"#.into(), None).is_ok());
+        let e = validate_filter(r#"{"op": "and", "conds": [{"op": "rnage", "field": "n"}]}"#.into(), None).unwrap_err();
+        assert_eq!(e.status, Status::InvalidArg);
+        let msg = format!("{}", e);
+        assert!(msg.contains("ERR_INVALID_FILTER"));
+        assert!(msg.contains("$.conds[0].op"));
+        assert!(validate_filter("not json".into(), None).is_err());
+
+        let unknown = r#"{"op": "must", "field": "tag", "conds": ["a"]}"#;
+        let msg = format!("{}", validate_filter(unknown.into(), None).unwrap_err());
+        assert!(msg.contains("ERR_INVALID_FILTER") && msg.contains("$.field"));
+        let schema = r#"{"name": "notes", "fields": [
+            {"name": "id", "field_type": "int64", "is_primary_key": true},
+            {"name": "tag", "field_type": "string"}
+        ]}"#;
+        assert!(validate_filter(unknown.into(), Some(schema.into())).is_ok());
+        let wrong_type = r#"{"op": "range", "field": "tag", "gt": 1}"#;
+        assert!(validate_filter(wrong_type.into(), Some(schema.into())).is_err());
+        assert!(validate_filter(unknown.into(), Some("{}".into())).is_err());
+    }
 }
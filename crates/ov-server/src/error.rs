@@ -5,6 +5,7 @@ use axum::{
     response::{IntoResponse, Response},
     Json,
 };
+use ov_vectordb::error::VectorDbError;
 use serde_json::json;
 
 /// API error with status code and message.
@@ -51,3 +52,25 @@ impl From<ov_core::error::OvError> for ApiError {
         }
     }
 }
+
+impl From<VectorDbError> for ApiError {
+    fn from(err: VectorDbError) -> Self {
+        match &err {
+            VectorDbError::InvalidFilter { .. } => Self {
+                status: StatusCode::BAD_REQUEST,
+                code: "invalid_filter",
+                message: err.to_string(),
+            },
+            VectorDbError::DimensionMismatch { .. } | VectorDbError::InvalidConfig(_) => {
+                ApiError::bad_request(err.to_string())
+            }
+            VectorDbError::CollectionNotFound(_)
+            | VectorDbError::IndexNotFound(_)
+            | VectorDbError::ProjectNotFound(_) => ApiError::not_found(err.to_string()),
+            VectorDbError::CollectionAlreadyExists(_)
+            | VectorDbError::IndexAlreadyExists(_)
+            | VectorDbError::ProjectAlreadyExists(_) => ApiError::conflict(err.to_string()),
+            _ => ApiError::internal(err.to_string()),
+        }
+    }
+}
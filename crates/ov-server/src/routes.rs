@@ -8,6 +8,9 @@ use axum::{
 };
 use ov_core::context::{Context, ContextType};
 use ov_session::session::{Part, Role};
+use ov_storage::agfs::context_to_fields;
+use ov_storage::context_collection_schema;
+use ov_vectordb::filter::Filter;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
@@ -87,6 +90,8 @@ pub struct SearchQuery {
     #[serde(rename = "type")]
     context_type: Option<String>,
     limit: Option<usize>,
+    /// JSON filter expression over the context collection fields.
+    filter: Option<String>,
 }
 
 async fn search_contexts(
@@ -97,10 +102,14 @@ async fn search_contexts(
     if query.is_empty() {
         return Err(ApiError::bad_request("query parameter \"q\" is required"));
     }
+    let filter = q.filter.as_deref().map(parse_context_filter).transpose()?;
     let mut results = state.context_store.search(&query);
     if let Some(ref ct) = q.context_type {
         results.retain(|c| c.context_type.as_str() == ct.as_str());
     }
+    if let Some(ref filter) = filter {
+        results.retain(|c| filter.matches(&context_to_fields(c)));
+    }
     let limit = q.limit.unwrap_or(20).min(200);
     results.truncate(limit);
     Ok(Json(json!({
@@ -110,6 +119,15 @@ async fn search_contexts(
     })))
 }
 
+/// Parse a filter against the fields of the context collection schema.
+fn parse_context_filter(json: &str) -> Result<Filter> {
+    let value: Value = serde_json::from_str(json)
+        .map_err(|e| ApiError::bad_request(format!("filter is not valid JSON: {e}")))?;
+    // Vector fields cannot be filtered, so their dimension does not matter.
+    let config = context_collection_schema("context", 0).to_collection_config();
+    Ok(Filter::from_json_with_schema(&value, &config)?)
+}
+
 #[derive(Deserialize)]
 pub struct CreateContextBody {
     pub uri: String,
@@ -1,32 +1,419 @@
 //! Agent FileSystem — virtual filesystem backed by vector store
+//!
+//! Every context is stored twice: as a VikingFS directory holding its
+//! L0/L1/L2 files plus a `.context.json` record, and as a row in a vector
+//! [`Collection`] built from [`context_collection_schema`]. `AgFs` keeps
+//! both sides in step so callers never wire them up by hand.
 
-use ov_core::context::Context;
+use crate::schema::{context_collection_schema, CollectionSchema};
+use crate::viking_fs::VikingFS;
+use ov_core::context::{Context, Vectorize};
+use ov_vectordb::Collection;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use ov_vectordb::VectorIndex;
 
-#[allow(dead_code)]
+/// Name of the vector index AgFs searches.
+pub const DEFAULT_INDEX: &str = "default";
+/// Per-directory file holding the serialized context record.
+pub const CONTEXT_FILE: &str = ".context.json";
+/// File name used for L2 content.
+pub const CONTENT_FILE: &str = "content.md";
+
+/// Agent filesystem over a [`VikingFS`] tree and a context [`Collection`].
 pub struct AgFs {
-    index: Arc<dyn VectorIndex>,
+    fs: VikingFS,
+    collection: Arc<Collection>,
 }
 
 impl AgFs {
-    pub fn new(index: Arc<dyn VectorIndex>) -> Self {
-        Self { index }
+    /// Create an AgFs over an existing VikingFS and a context collection
+    /// built from `schema`.
+    ///
+    /// A flat [`DEFAULT_INDEX`] with the schema's scalar indexes is created
+    /// if missing.
+    pub fn new(fs: VikingFS, collection: Arc<Collection>, schema: &CollectionSchema) -> anyhow::Result<Self> {
+        if !collection.has_index(DEFAULT_INDEX) {
+            collection.create_index(DEFAULT_INDEX, schema.to_index_config())?;
+        }
+        Ok(Self { fs, collection })
+    }
+
+    /// Open an AgFs rooted at `root`, persisting the collection at `collection_path`.
+    pub fn open(
+        root: impl Into<PathBuf>,
+        collection_path: impl Into<PathBuf>,
+        vector_dim: usize,
+    ) -> anyhow::Result<Self> {
+        let schema = context_collection_schema("context", vector_dim);
+        let collection = Collection::with_path(schema.to_collection_config(), collection_path.into())?;
+        Self::new(VikingFS::new(root), Arc::new(collection), &schema)
+    }
+
+    /// The underlying filesystem.
+    pub fn fs(&self) -> &VikingFS {
+        &self.fs
+    }
+
+    /// The underlying context collection.
+    pub fn collection(&self) -> &Arc<Collection> {
+        &self.collection
+    }
+
+    /// Read the context stored at `uri`, with its vector from the collection.
+    pub async fn read(&self, uri: &str) -> anyhow::Result<Option<Context>> {
+        let Some(mut ctx) = self.read_record(uri).await? else {
+            return Ok(None);
+        };
+        let pk = Value::from(ctx.id.to_string());
+        if let Some(Some(fields)) = self.collection.fetch_data(&[pk]).into_iter().next() {
+            ctx.vector = fields.get("vector").and_then(value_to_vector);
+            ctx.sparse_vector = fields.get("sparse_vector").and_then(value_to_sparse_vector);
+        }
+        ctx.vectorize = Vectorize::new(ctx.abstract_text.clone());
+        Ok(Some(ctx))
+    }
+
+    /// Write a context (L0 abstract only) and index it.
+    pub async fn write(&self, ctx: &Context) -> anyhow::Result<()> {
+        self.write_with_content(ctx, "", None).await
+    }
+
+    /// Write a context with its L1 overview and optional L2 content, and index it.
+    pub async fn write_with_content(
+        &self,
+        ctx: &Context,
+        overview: &str,
+        content: Option<&str>,
+    ) -> anyhow::Result<()> {
+        // What this write replaces: the record at the URI, and the row of
+        // this id, which a failed file write restores.
+        let replaced = self.read_record(&ctx.uri).await?;
+        let pk = Value::from(ctx.id.to_string());
+        let previous = self.collection.fetch_data(std::slice::from_ref(&pk)).pop().flatten();
+
+        // Upsert first: it validates the vector before anything touches disk.
+        self.collection.upsert_data(&[context_to_fields(ctx)])?;
+
+        if let Err(e) = self.write_files(ctx, overview, content).await {
+            // Best effort: the file write error is the one worth reporting.
+            let _ = match previous {
+                Some(row) => self.collection.upsert_data(&[row]).map(drop),
+                None => self.collection.delete_data(&[pk]),
+            };
+            return Err(e);
+        }
+
+        // A different context previously stored at this URI loses its row
+        // once the new one is in place.
+        if let Some(old) = replaced {
+            if old.id != ctx.id {
+                self.collection.delete_data(&[Value::from(old.id.to_string())])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// List the direct child contexts of `parent_uri`, ordered by URI.
+    pub async fn list(&self, parent_uri: &str) -> anyhow::Result<Vec<Context>> {
+        if !self.fs.is_dir(parent_uri).await {
+            return Ok(Vec::new());
+        }
+        let parent_path = self.fs.uri_to_path(parent_uri);
+        let mut contexts = Vec::new();
+        for entry in self.fs.ls(parent_uri).await? {
+            if !entry.is_dir {
+                continue;
+            }
+            let child_uri = self.fs.path_to_uri(&parent_path.join(&entry.name));
+            if let Some(ctx) = self.read(&child_uri).await? {
+                contexts.push(ctx);
+            }
+        }
+        contexts.sort_by(|a, b| a.uri.cmp(&b.uri));
+        Ok(contexts)
+    }
+
+    /// Delete the context at `uri` and every context beneath it.
+    pub async fn delete(&self, uri: &str) -> anyhow::Result<()> {
+        if !self.fs.exists(uri).await {
+            return Ok(());
+        }
+        let mut ids = Vec::new();
+        if let Some(ctx) = self.read_record(uri).await? {
+            ids.push(Value::from(ctx.id.to_string()));
+        }
+        if self.fs.is_dir(uri).await {
+            for entry in self.fs.tree(uri).await? {
+                if entry.is_dir || entry.name != CONTEXT_FILE {
+                    continue;
+                }
+                let bytes = self.fs.read(&entry.uri).await?;
+                if let Ok(ctx) = serde_json::from_slice::<Context>(&bytes) {
+                    ids.push(Value::from(ctx.id.to_string()));
+                }
+            }
+        }
+        self.collection.delete_data(&ids)?;
+        self.fs.rm(uri, true).await?;
+        Ok(())
+    }
+
+    /// Vector search over stored contexts, returning each hit with its score.
+    pub async fn search(
+        &self,
+        vector: &[f32],
+        limit: usize,
+        filters: Option<&Value>,
+    ) -> anyhow::Result<Vec<(Context, f32)>> {
+        let result = self.collection.search_by_vector(DEFAULT_INDEX, vector, limit, 0, filters)?;
+        let mut hits = Vec::with_capacity(result.data.len());
+        for item in result.data {
+            let Some(uri) = item.fields.get("uri").and_then(Value::as_str) else {
+                continue;
+            };
+            if let Some(ctx) = self.read(uri).await? {
+                hits.push((ctx, item.score));
+            }
+        }
+        Ok(hits)
+    }
+
+    async fn read_record(&self, uri: &str) -> anyhow::Result<Option<Context>> {
+        let file_uri = context_file_uri(&self.fs, uri);
+        if !self.fs.exists(&file_uri).await {
+            return Ok(None);
+        }
+        let bytes = self.fs.read(&file_uri).await?;
+        Ok(Some(serde_json::from_slice(&bytes)?))
+    }
+
+    async fn write_files(
+        &self,
+        ctx: &Context,
+        overview: &str,
+        content: Option<&str>,
+    ) -> anyhow::Result<()> {
+        self.fs
+            .write_context(&ctx.uri, &ctx.abstract_text, overview, content, CONTENT_FILE)
+            .await?;
+        // The vectors live in the collection only.
+        let mut record = ctx.clone();
+        record.vector = None;
+        record.sparse_vector = None;
+        let json = serde_json::to_vec_pretty(&record)?;
+        self.fs.write(&context_file_uri(&self.fs, &ctx.uri), &json).await?;
+        Ok(())
+    }
+}
+
+/// Build the collection row for a context, following [`context_collection_schema`].
+pub fn context_to_fields(ctx: &Context) -> HashMap<String, Value> {
+    let mut fields = HashMap::new();
+    fields.insert("id".into(), Value::from(ctx.id.to_string()));
+    fields.insert("uri".into(), Value::from(ctx.uri.clone()));
+    fields.insert(
+        "type".into(),
+        Value::from(if ctx.is_leaf { "file" } else { "directory" }),
+    );
+    fields.insert("context_type".into(), Value::from(ctx.context_type.as_str()));
+    if let Some(ref vector) = ctx.vector {
+        fields.insert("vector".into(), Value::from(vector.clone()));
+    }
+    if let Some(ref sparse) = ctx.sparse_vector {
+        let terms = sparse.iter().map(|(term, &weight)| (term.clone(), Value::from(weight))).collect();
+        fields.insert("sparse_vector".into(), Value::Object(terms));
+    }
+    fields.insert("created_at".into(), Value::from(ctx.created_at.to_rfc3339()));
+    fields.insert("updated_at".into(), Value::from(ctx.updated_at.to_rfc3339()));
+    fields.insert("active_count".into(), Value::from(ctx.active_count));
+    if let Some(ref parent) = ctx.parent_uri {
+        fields.insert("parent_uri".into(), Value::from(parent.clone()));
+    }
+    fields.insert("is_leaf".into(), Value::from(ctx.is_leaf));
+    let name = ctx.uri.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+    fields.insert("name".into(), Value::from(name));
+    let description = ctx.meta.get("description").and_then(Value::as_str).unwrap_or("");
+    fields.insert("description".into(), Value::from(description));
+    let tags = match ctx.meta.get("tags") {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(arr)) => arr.iter().filter_map(Value::as_str).collect::<Vec<_>>().join(","),
+        _ => String::new(),
+    };
+    fields.insert("tags".into(), Value::from(tags));
+    fields.insert("abstract".into(), Value::from(ctx.abstract_text.clone()));
+    fields
+}
+
+fn context_file_uri(fs: &VikingFS, uri: &str) -> String {
+    fs.path_to_uri(&fs.uri_to_path(uri).join(CONTEXT_FILE))
+}
+
+fn value_to_vector(v: &Value) -> Option<Vec<f32>> {
+    let arr = v.as_array()?;
+    if arr.is_empty() {
+        return None;
     }
+    Some(arr.iter().filter_map(|x| x.as_f64().map(|f| f as f32)).collect())
+}
 
-    pub async fn read(&self, _uri: &str) -> anyhow::Result<Option<Context>> {
-        todo!("agfs read")
+fn value_to_sparse_vector(v: &Value) -> Option<HashMap<String, f32>> {
+    let terms = v.as_object()?;
+    Some(terms.iter().filter_map(|(term, w)| Some((term.clone(), w.as_f64()? as f32))).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_agfs() -> (TempDir, AgFs) {
+        let tmp = TempDir::new().unwrap();
+        let agfs = AgFs::open(tmp.path().join("fs"), tmp.path().join("db"), 3).unwrap();
+        (tmp, agfs)
     }
 
-    pub async fn write(&self, _ctx: &Context) -> anyhow::Result<()> {
-        todo!("agfs write")
+    fn ctx(uri: &str, parent: &str, vector: Vec<f32>) -> Context {
+        let mut c = Context::builder(uri)
+            .parent_uri(parent)
+            .abstract_text(format!("abstract of {uri}"))
+            .build();
+        c.vector = Some(vector);
+        c
     }
 
-    pub async fn list(&self, _parent_uri: &str) -> anyhow::Result<Vec<Context>> {
-        todo!("agfs list")
+    #[tokio::test]
+    async fn test_write_read_roundtrip() {
+        let (_tmp, agfs) = make_agfs();
+        let c = ctx("viking://resources/doc", "viking://resources", vec![1.0, 0.0, 0.0]);
+        agfs.write_with_content(&c, "overview", Some("# body")).await.unwrap();
+
+        let back = agfs.read("viking://resources/doc").await.unwrap().unwrap();
+        assert_eq!(back.id, c.id);
+        assert_eq!(back.abstract_text, c.abstract_text);
+        assert_eq!(back.vector, Some(vec![1.0, 0.0, 0.0]));
+        assert_eq!(agfs.fs().overview("viking://resources/doc").await.unwrap(), "overview");
+        assert_eq!(agfs.collection().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_new_indexes_schema_scalar_fields() {
+        let tmp = TempDir::new().unwrap();
+        let mut schema = context_collection_schema("custom", 3);
+        schema.scalar_index = vec!["uri".into()];
+        let collection = Arc::new(Collection::new(schema.to_collection_config()));
+        let agfs = AgFs::new(VikingFS::new(tmp.path()), collection, &schema).unwrap();
+        agfs.write(&ctx("viking://resources/a", "viking://resources", vec![1.0, 0.0, 0.0])).await.unwrap();
+        agfs.write(&ctx("viking://resources/b", "viking://resources", vec![0.9, 0.1, 0.0])).await.unwrap();
+
+        let filter = serde_json::json!({"op": "must", "field": "uri", "conds": ["viking://resources/b"]});
+        let hits = agfs.search(&[1.0, 0.0, 0.0], 2, Some(&filter)).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0.uri, "viking://resources/b");
+    }
+
+    #[tokio::test]
+    async fn test_read_missing() {
+        let (_tmp, agfs) = make_agfs();
+        assert!(agfs.read("viking://resources/none").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_list_children() {
+        let (_tmp, agfs) = make_agfs();
+        agfs.write(&ctx("viking://resources/b", "viking://resources", vec![0.0, 1.0, 0.0])).await.unwrap();
+        agfs.write(&ctx("viking://resources/a", "viking://resources", vec![1.0, 0.0, 0.0])).await.unwrap();
+        agfs.write(&ctx("viking://resources/a/x", "viking://resources/a", vec![0.0, 0.0, 1.0])).await.unwrap();
+
+        let children = agfs.list("viking://resources").await.unwrap();
+        let uris: Vec<_> = children.iter().map(|c| c.uri.as_str()).collect();
+        assert_eq!(uris, vec!["viking://resources/a", "viking://resources/b"]);
+        assert!(agfs.list("viking://missing").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_delete_removes_subtree_rows() {
+        let (_tmp, agfs) = make_agfs();
+        agfs.write(&ctx("viking://resources/a", "viking://resources", vec![1.0, 0.0, 0.0])).await.unwrap();
+        agfs.write(&ctx("viking://resources/a/x", "viking://resources/a", vec![0.0, 1.0, 0.0])).await.unwrap();
+        agfs.write(&ctx("viking://resources/b", "viking://resources", vec![0.0, 0.0, 1.0])).await.unwrap();
+        assert_eq!(agfs.collection().count(), 3);
+
+        agfs.delete("viking://resources/a").await.unwrap();
+        assert_eq!(agfs.collection().count(), 1);
+        assert!(!agfs.fs().exists("viking://resources/a").await);
+        assert!(agfs.read("viking://resources/a/x").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rewrite_same_uri_replaces_row() {
+        let (_tmp, agfs) = make_agfs();
+        agfs.write(&ctx("viking://resources/a", "viking://resources", vec![1.0, 0.0, 0.0])).await.unwrap();
+        agfs.write(&ctx("viking://resources/a", "viking://resources", vec![0.0, 1.0, 0.0])).await.unwrap();
+        assert_eq!(agfs.collection().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_file_write_restores_row() {
+        let (_tmp, agfs) = make_agfs();
+        let mut c = ctx("viking://resources/a", "viking://resources", vec![1.0, 0.0, 0.0]);
+        agfs.write(&c).await.unwrap();
+        let other = ctx("viking://resources/a", "viking://resources", vec![0.0, 0.0, 1.0]);
+
+        // A directory in place of the record file makes the file write fail.
+        let record = agfs.fs().uri_to_path("viking://resources/a").join(CONTEXT_FILE);
+        std::fs::remove_file(&record).unwrap();
+        std::fs::create_dir(&record).unwrap();
+        c.vector = Some(vec![0.0, 1.0, 0.0]);
+        assert!(agfs.write(&c).await.is_err());
+        assert!(agfs.write(&other).await.is_err());
+
+        let rows = agfs.collection().fetch_data(&[Value::from(c.id.to_string()), Value::from(other.id.to_string())]);
+        assert_eq!(rows[0].as_ref().unwrap()["vector"], serde_json::json!([1.0, 0.0, 0.0]));
+        assert!(rows[1].is_none());
+        assert_eq!(agfs.collection().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_sparse_vector_roundtrip() {
+        let (_tmp, agfs) = make_agfs();
+        let mut c = ctx("viking://resources/a", "viking://resources", vec![1.0, 0.0, 0.0]);
+        c.sparse_vector = Some(HashMap::from([("tea".to_string(), 0.5)]));
+        agfs.write(&c).await.unwrap();
+
+        let row = agfs.collection().fetch_data(&[Value::from(c.id.to_string())]).remove(0).unwrap();
+        assert_eq!(row["sparse_vector"], serde_json::json!({"tea": 0.5}));
+        let back = agfs.read("viking://resources/a").await.unwrap().unwrap();
+        assert_eq!(back.sparse_vector, c.sparse_vector);
+    }
+
+    #[tokio::test]
+    async fn test_dimension_mismatch_writes_nothing() {
+        let (_tmp, agfs) = make_agfs();
+        let c = ctx("viking://resources/bad", "viking://resources", vec![1.0]);
+        assert!(agfs.write(&c).await.is_err());
+        assert!(!agfs.fs().exists("viking://resources/bad").await);
+        assert_eq!(agfs.collection().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_search_returns_contexts() {
+        let (_tmp, agfs) = make_agfs();
+        agfs.write(&ctx("viking://resources/a", "viking://resources", vec![1.0, 0.0, 0.0])).await.unwrap();
+        agfs.write(&ctx("viking://resources/b", "viking://resources", vec![0.0, 1.0, 0.0])).await.unwrap();
+
+        let hits = agfs.search(&[0.9, 0.1, 0.0], 1, None).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].0.uri, "viking://resources/a");
     }
 
-    pub async fn delete(&self, _uri: &str) -> anyhow::Result<()> {
-        todo!("agfs delete")
+    #[test]
+    fn test_context_to_fields() {
+        let c = ctx("viking://user/memories/preferences/tea", "viking://user/memories/preferences", vec![0.0; 3]);
+        let fields = context_to_fields(&c);
+        assert_eq!(fields["name"], "tea");
+        assert_eq!(fields["context_type"], "memory");
+        assert_eq!(fields["parent_uri"], "viking://user/memories/preferences");
     }
 }
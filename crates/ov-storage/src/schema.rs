@@ -37,6 +37,19 @@ impl FieldType {
             Self::DateTime => "date_time",
         }
     }
+
+    /// Map to the equivalent vector database field type.
+    pub fn to_vectordb(&self) -> ov_vectordb::FieldType {
+        match self {
+            Self::String => ov_vectordb::FieldType::String,
+            Self::Vector => ov_vectordb::FieldType::Vector,
+            Self::SparseVector => ov_vectordb::FieldType::SparseVector,
+            Self::Path => ov_vectordb::FieldType::Path,
+            Self::Bool => ov_vectordb::FieldType::Bool,
+            Self::Int64 => ov_vectordb::FieldType::Int64,
+            Self::DateTime => ov_vectordb::FieldType::DateTime,
+        }
+    }
 }
 
 /// A field definition in a collection schema.
@@ -67,6 +80,31 @@ pub struct CollectionSchema {
     pub scalar_index: Vec<String>,
 }
 
+impl CollectionSchema {
+    /// Convert to a vector database collection configuration.
+    pub fn to_collection_config(&self) -> ov_vectordb::CollectionConfig {
+        ov_vectordb::CollectionConfig {
+            name: self.name.clone(),
+            fields: self.fields.iter().map(|f| ov_vectordb::FieldDef {
+                name: f.name.clone(),
+                field_type: f.field_type.to_vectordb(),
+                is_primary_key: f.is_primary_key,
+                dim: f.dimension,
+            }).collect(),
+            description: self.description.clone(),
+        }
+    }
+
+    /// Configuration for the collection's default vector index, with scalar
+    /// indexes on the `scalar_index` fields.
+    pub fn to_index_config(&self) -> ov_vectordb::collection::IndexConfig {
+        ov_vectordb::collection::IndexConfig {
+            scalar_index_fields: self.scalar_index.clone(),
+            ..Default::default()
+        }
+    }
+}
+
 /// Build the default context collection schema.
 pub fn context_collection_schema(name: &str, vector_dim: usize) -> CollectionSchema {
     CollectionSchema {
@@ -121,6 +159,14 @@ mod tests {
         assert_eq!(vec_field.dimension, Some(768));
     }
 
+    #[test]
+    fn test_to_index_config() {
+        let schema = context_collection_schema("ctx", 8);
+        let cfg = schema.to_index_config();
+        assert_eq!(cfg.scalar_index_fields, schema.scalar_index);
+        assert_eq!(cfg.index_type, "flat");
+    }
+
     #[test]
     fn test_schema_scalar_index() {
         let schema = context_collection_schema("ctx", 1024);
@@ -224,4 +270,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_to_collection_config() {
+        let schema = context_collection_schema("ctx", 64);
+        let config = schema.to_collection_config();
+        assert_eq!(config.name, "ctx");
+        assert_eq!(config.primary_key(), Some("id"));
+        assert_eq!(config.dimension(), 64);
+        assert_eq!(config.fields.len(), schema.fields.len());
+    }
+
 }
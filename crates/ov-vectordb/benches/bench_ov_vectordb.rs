@@ -1,6 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use ov_vectordb::index::{FlatIndex, HnswIndex, VectorIndex};
-use ov_vectordb::distance::DistanceMetric;
+use ov_vectordb::index::{FlatIndex, HnswIndex, IvfIndex, Quantization, VectorIndex};
+use ov_vectordb::distance::{DistanceMetric, SimdLevel};
 use ov_vectordb::store::{MemoryKvStore, KvStore};
 use rand::Rng;
 
@@ -58,6 +58,16 @@ fn bench_hnsw_insert(c: &mut Criterion) {
             black_box(&idx);
         })
     });
+
+    let labels: Vec<u64> = (0..1000).collect();
+    let vectors: Vec<Vec<f32>> = (0..1000).map(|_| random_vector(dim)).collect();
+    c.bench_function("hnsw_insert_batch_1k_128d", |b| {
+        b.iter(|| {
+            let idx = HnswIndex::new(dim, DistanceMetric::Cosine);
+            idx.insert_batch(&labels, &vectors).unwrap();
+            black_box(&idx);
+        })
+    });
 }
 
 fn bench_hnsw_search(c: &mut Criterion) {
@@ -75,6 +85,117 @@ fn bench_hnsw_search(c: &mut Criterion) {
     });
 }
 
+fn bench_hnsw_open(c: &mut Criterion) {
+    let dim = 1024;
+    let idx = HnswIndex::with_params(dim, DistanceMetric::Cosine, 16, 64, 50);
+    for i in 0..5000u64 {
+        idx.insert(i, &random_vector(dim)).unwrap();
+    }
+    let dir = tempfile::TempDir::new().unwrap();
+    idx.save(dir.path()).unwrap();
+
+    for (name, mmap) in [("read", false), ("mmap", true)] {
+        c.bench_function(&format!("hnsw_open_{name}_5k_1024d"), |b| {
+            b.iter(|| {
+                let mut loaded = HnswIndex::new(dim, DistanceMetric::Cosine).with_mmap(mmap);
+                loaded.load(dir.path()).unwrap();
+                black_box(loaded);
+            })
+        });
+    }
+}
+
+fn bench_quantized_search(c: &mut Criterion) {
+    let dim = 128;
+    let vectors: Vec<Vec<f32>> = (0..10000).map(|_| random_vector(dim)).collect();
+    let queries: Vec<Vec<f32>> = (0..50).map(|_| random_vector(dim)).collect();
+    let exact = FlatIndex::with_capacity(dim, DistanceMetric::L2, vectors.len());
+    for (i, v) in vectors.iter().enumerate() {
+        exact.insert(i as u64, v).unwrap();
+    }
+    let truth: Vec<Vec<u64>> = queries.iter().map(|q| exact.search(q, 10).unwrap().ids).collect();
+
+    for (name, quantization) in [
+        ("none", Quantization::None),
+        ("int8", Quantization::Int8),
+        ("pq16", Quantization::Pq { m: 16 }),
+    ] {
+        let idx = HnswIndex::new(dim, DistanceMetric::L2).with_quantization(quantization);
+        for (i, v) in vectors.iter().enumerate() {
+            idx.insert(i as u64, v).unwrap();
+        }
+        // Recall@10 against exact search, alongside the vector memory.
+        let found: usize = queries.iter().zip(&truth)
+            .map(|(q, t)| idx.search(q, 10).unwrap().ids.iter().filter(|l| t.contains(l)).count())
+            .sum();
+        println!(
+            "hnsw_{name}: recall@10 {:.3}, vector bytes {}",
+            found as f64 / (10 * queries.len()) as f64,
+            idx.vector_bytes()
+        );
+
+        c.bench_function(&format!("hnsw_{name}_search_top10_from_10k"), |b| {
+            let query = random_vector(dim);
+            b.iter(|| {
+                black_box(idx.search(&query, 10).unwrap());
+            })
+        });
+    }
+}
+
+fn bench_ivf_search(c: &mut Criterion) {
+    let dim = 128;
+    let vectors: Vec<Vec<f32>> = (0..10000).map(|_| random_vector(dim)).collect();
+    let queries: Vec<Vec<f32>> = (0..50).map(|_| random_vector(dim)).collect();
+    let exact = FlatIndex::with_capacity(dim, DistanceMetric::L2, vectors.len());
+    for (i, v) in vectors.iter().enumerate() {
+        exact.insert(i as u64, v).unwrap();
+    }
+    let truth: Vec<Vec<u64>> = queries.iter().map(|q| exact.search(q, 10).unwrap().ids).collect();
+
+    for (name, quantization) in [("none", Quantization::None), ("pq16", Quantization::Pq { m: 16 })] {
+        let idx = IvfIndex::with_params(dim, DistanceMetric::L2, 100, 10).with_quantization(quantization);
+        for (i, v) in vectors.iter().enumerate() {
+            idx.insert(i as u64, v).unwrap();
+        }
+        let found: usize = queries.iter().zip(&truth)
+            .map(|(q, t)| idx.search(q, 10).unwrap().ids.iter().filter(|l| t.contains(l)).count())
+            .sum();
+        println!(
+            "ivf_{name}: recall@10 {:.3}, vector bytes {}",
+            found as f64 / (10 * queries.len()) as f64,
+            idx.vector_bytes()
+        );
+
+        c.bench_function(&format!("ivf_{name}_search_top10_from_10k"), |b| {
+            let query = random_vector(dim);
+            b.iter(|| {
+                black_box(idx.search(&query, 10).unwrap());
+            })
+        });
+    }
+}
+
+fn bench_distance_kernels(c: &mut Criterion) {
+    let dim = 1024;
+    let query = random_vector(dim);
+    let rows: Vec<Vec<f32>> = (0..1000).map(|_| random_vector(dim)).collect();
+    let rows: Vec<&[f32]> = rows.iter().map(|r| r.as_slice()).collect();
+    let mut out = vec![0.0; rows.len()];
+
+    for level in SimdLevel::available() {
+        c.bench_function(&format!("ip_1024d_{level:?}"), |b| {
+            b.iter(|| black_box(level.inner_product(&query, rows[0])))
+        });
+        c.bench_function(&format!("ip_batch_1k_1024d_{level:?}"), |b| {
+            b.iter(|| {
+                level.inner_product_batch(&query, &rows, &mut out);
+                black_box(&out);
+            })
+        });
+    }
+}
+
 fn bench_kv_store(c: &mut Criterion) {
     c.bench_function("kv_put_get_1000", |b| {
         b.iter(|| {
@@ -102,5 +223,16 @@ fn bench_kv_store(c: &mut Criterion) {
     });
 }
 
-criterion_group!(benches, bench_flat_insert, bench_flat_search, bench_hnsw_insert, bench_hnsw_search, bench_kv_store);
+criterion_group!(
+    benches,
+    bench_flat_insert,
+    bench_flat_search,
+    bench_hnsw_insert,
+    bench_hnsw_search,
+    bench_hnsw_open,
+    bench_quantized_search,
+    bench_ivf_search,
+    bench_distance_kernels,
+    bench_kv_store
+);
 criterion_main!(benches);
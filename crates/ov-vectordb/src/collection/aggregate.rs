@@ -0,0 +1,199 @@
+//! Facet counts and numeric and date aggregations over scalar fields.
+
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+use serde_json::Value;
+
+use crate::error::{Result, VectorDbError};
+use crate::index::ScalarKey;
+
+/// An aggregation for [`Collection::aggregate`](super::Collection::aggregate).
+/// List fields contribute every element.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Aggregation {
+    /// Records per distinct value, most frequent first, keeping the
+    /// `size` largest buckets. `sub` runs within each bucket.
+    Terms { field: String, size: usize, sub: Option<Box<Aggregation>> },
+    /// Count, min, max, sum and average of a numeric field.
+    Stats { field: String },
+    /// Records per fixed-width range of a numeric field.
+    Histogram { field: String, interval: f64 },
+    /// Records per fixed-length period of a datetime field, aligned to
+    /// the Unix epoch (so daily buckets start at UTC midnight).
+    DateHistogram { field: String, interval: Duration },
+}
+
+impl Aggregation {
+    pub fn terms(field: &str, size: usize) -> Self {
+        Self::Terms { field: field.to_string(), size, sub: None }
+    }
+
+    pub fn stats(field: &str) -> Self {
+        Self::Stats { field: field.to_string() }
+    }
+
+    pub fn histogram(field: &str, interval: f64) -> Self {
+        Self::Histogram { field: field.to_string(), interval }
+    }
+
+    pub fn date_histogram(field: &str, interval: Duration) -> Self {
+        Self::DateHistogram { field: field.to_string(), interval }
+    }
+
+    /// Run `sub` within each bucket of a terms aggregation.
+    pub fn with_sub(mut self, aggregation: Aggregation) -> Self {
+        if let Self::Terms { sub, .. } = &mut self {
+            *sub = Some(Box::new(aggregation));
+        }
+        self
+    }
+
+    /// Fields this aggregation and its sub-aggregations read.
+    pub(super) fn fields(&self) -> Vec<&str> {
+        match self {
+            Self::Terms { field, sub, .. } => {
+                let mut fields = vec![field.as_str()];
+                fields.extend(sub.iter().flat_map(|s| s.fields()));
+                fields
+            }
+            Self::Stats { field } | Self::Histogram { field, .. } | Self::DateHistogram { field, .. } => vec![field],
+        }
+    }
+
+    pub(super) fn validate(&self) -> Result<()> {
+        match self {
+            Self::Terms { sub: Some(sub), .. } => sub.validate(),
+            Self::Histogram { interval, .. } if !(interval.is_finite() && *interval > 0.0) => {
+                Err(VectorDbError::InvalidConfig(format!("histogram interval must be positive, got {interval}")))
+            }
+            Self::DateHistogram { interval, .. } if interval.as_micros() == 0 => {
+                Err(VectorDbError::InvalidConfig("date histogram interval must be at least a microsecond".into()))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Result of an [`Aggregation`], of the matching variant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AggregationResult {
+    Terms(Vec<Bucket>),
+    Stats(FieldStats),
+    /// Non-empty buckets in key order, keyed by the start of their range.
+    Histogram(Vec<Bucket>),
+    /// Non-empty buckets in time order, keyed by the RFC 3339 start of
+    /// their period.
+    DateHistogram(Vec<Bucket>),
+}
+
+/// Records sharing a value or range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bucket {
+    pub key: Value,
+    pub count: usize,
+    /// The sub-aggregation over the records of this bucket.
+    pub sub: Option<AggregationResult>,
+}
+
+/// Summary of a numeric field; min, max and avg are `None` if no record
+/// has a value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FieldStats {
+    pub count: usize,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+    pub sum: f64,
+    pub avg: Option<f64>,
+}
+
+/// Labels per key of a field among the allowed labels (all if `None`).
+pub(super) type Groups<'a> = dyn Fn(&str, Option<&HashSet<u64>>) -> Vec<(ScalarKey, Vec<u64>)> + 'a;
+
+pub(super) fn run(aggregation: &Aggregation, allowed: Option<&HashSet<u64>>, groups: &Groups<'_>) -> AggregationResult {
+    match aggregation {
+        Aggregation::Terms { field, size, sub } => {
+            let mut terms: Vec<(Value, Vec<u64>)> = groups(field, allowed).into_iter()
+                .filter_map(|(key, labels)| Some((term(key)?, labels)))
+                .collect();
+            // Stable, so equal counts stay in value order.
+            terms.sort_by_key(|(_, labels)| std::cmp::Reverse(labels.len()));
+            terms.truncate(*size);
+            AggregationResult::Terms(terms.into_iter()
+                .map(|(key, labels)| Bucket {
+                    key,
+                    count: labels.len(),
+                    sub: sub.as_ref().map(|sub| run(sub, Some(&labels.into_iter().collect()), groups)),
+                })
+                .collect())
+        }
+        Aggregation::Stats { field } => {
+            let mut stats = FieldStats::default();
+            for (key, labels) in groups(field, allowed) {
+                let ScalarKey::Num(n) = key else { continue };
+                let n = n.into_inner();
+                stats.count += labels.len();
+                stats.sum += n * labels.len() as f64;
+                stats.min = Some(stats.min.map_or(n, |m| m.min(n)));
+                stats.max = Some(stats.max.map_or(n, |m| m.max(n)));
+            }
+            stats.avg = (stats.count > 0).then(|| stats.sum / stats.count as f64);
+            AggregationResult::Stats(stats)
+        }
+        Aggregation::Histogram { field, interval } => {
+            let buckets = bucket(groups(field, allowed), |key| match key {
+                ScalarKey::Num(n) => Some((n.into_inner() / interval).floor() as i64),
+                _ => None,
+            });
+            AggregationResult::Histogram(buckets.into_iter()
+                .map(|(i, count)| Bucket { key: number(i as f64 * interval), count, sub: None })
+                .collect())
+        }
+        Aggregation::DateHistogram { field, interval } => {
+            let step = interval.as_micros().min(i64::MAX as u128) as i64;
+            let buckets = bucket(groups(field, allowed), |key| match key {
+                ScalarKey::Time(t) => Some(t.div_euclid(step)),
+                _ => None,
+            });
+            AggregationResult::DateHistogram(buckets.into_iter()
+                .map(|(i, count)| {
+                    let start = chrono::DateTime::from_timestamp_micros(i.saturating_mul(step))
+                        .map(|t| Value::from(t.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true)))
+                        .unwrap_or(Value::Null);
+                    Bucket { key: start, count, sub: None }
+                })
+                .collect())
+        }
+    }
+}
+
+/// Distinct labels per bucket index, for the keys `index` places.
+fn bucket(groups: Vec<(ScalarKey, Vec<u64>)>, index: impl Fn(&ScalarKey) -> Option<i64>) -> Vec<(i64, usize)> {
+    let mut buckets: BTreeMap<i64, HashSet<u64>> = BTreeMap::new();
+    for (key, labels) in groups {
+        if let Some(i) = index(&key) {
+            buckets.entry(i).or_default().extend(labels);
+        }
+    }
+    buckets.into_iter().map(|(i, labels)| (i, labels.len())).collect()
+}
+
+/// The field value a terms bucket stands for. Datetimes are also indexed
+/// as strings, so their time keys are skipped.
+fn term(key: ScalarKey) -> Option<Value> {
+    match key {
+        ScalarKey::Bool(b) => Some(Value::Bool(b)),
+        ScalarKey::Num(n) => Some(number(n.into_inner())),
+        ScalarKey::Str(s) => Some(Value::String(s)),
+        ScalarKey::Time(_) => None,
+    }
+}
+
+/// Integral values as JSON integers, so they compare equal to the values
+/// that were written.
+fn number(n: f64) -> Value {
+    if n.fract() == 0.0 && n.abs() < 9.0e15 {
+        Value::from(n as i64)
+    } else {
+        serde_json::Number::from_f64(n).map(Value::Number).unwrap_or(Value::Null)
+    }
+}
@@ -0,0 +1,138 @@
+//! Query-time rescoring of search hits by field values.
+
+use std::collections::HashMap;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+
+use crate::error::{Result, VectorDbError};
+use crate::filter::parse_datetime;
+
+/// Scoring expression for [`Collection::search_boosted`](super::Collection::search_boosted):
+/// `similarity_weight * similarity + Σ weight * function(field)`.
+#[derive(Debug, Clone)]
+pub struct ScoreBoost {
+    pub similarity_weight: f32,
+    pub terms: Vec<BoostTerm>,
+    /// Hits fetched by similarity per requested hit, then re-ranked.
+    pub candidates: usize,
+    /// Reference time of decays; `None` for the time of the search.
+    pub now: Option<DateTime<Utc>>,
+}
+
+/// One field-based term of a [`ScoreBoost`].
+#[derive(Debug, Clone)]
+pub struct BoostTerm {
+    pub field: String,
+    pub function: BoostFunction,
+    pub weight: f32,
+}
+
+/// Function of a field value. A missing or unreadable value scores 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoostFunction {
+    /// `0.5^(age / half_life)` of a datetime field: 1 at the reference
+    /// time, halving every `half_life`. Later times count as age 0.
+    Decay { half_life: Duration },
+    /// `ln(1 + value)` of a numeric field, with negatives counted as 0.
+    Log,
+}
+
+/// Breakdown of a boosted score into its terms.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScoreExplanation {
+    /// Similarity from the index.
+    pub similarity: f32,
+    /// `similarity_weight * similarity`.
+    pub similarity_score: f32,
+    pub terms: Vec<TermScore>,
+}
+
+/// Contribution of one [`BoostTerm`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermScore {
+    pub field: String,
+    /// The function of the field value.
+    pub value: f32,
+    /// `weight * value`.
+    pub score: f32,
+}
+
+impl Default for ScoreBoost {
+    fn default() -> Self {
+        Self { similarity_weight: 1.0, terms: Vec::new(), candidates: 4, now: None }
+    }
+}
+
+impl ScoreBoost {
+    /// Similarity alone, weighted 1.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_similarity_weight(mut self, weight: f32) -> Self {
+        self.similarity_weight = weight;
+        self
+    }
+
+    pub fn with_candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates;
+        self
+    }
+
+    pub fn with_now(mut self, now: DateTime<Utc>) -> Self {
+        self.now = Some(now);
+        self
+    }
+
+    pub fn decay(self, field: &str, half_life: Duration, weight: f32) -> Self {
+        self.term(field, BoostFunction::Decay { half_life }, weight)
+    }
+
+    pub fn log(self, field: &str, weight: f32) -> Self {
+        self.term(field, BoostFunction::Log, weight)
+    }
+
+    fn term(mut self, field: &str, function: BoostFunction, weight: f32) -> Self {
+        self.terms.push(BoostTerm { field: field.to_string(), function, weight });
+        self
+    }
+
+    pub(super) fn validate(&self) -> Result<()> {
+        if self.candidates == 0 {
+            return Err(VectorDbError::InvalidConfig("score boost needs at least one candidate per hit".into()));
+        }
+        for term in &self.terms {
+            if matches!(term.function, BoostFunction::Decay { half_life } if half_life.is_zero()) {
+                return Err(VectorDbError::InvalidConfig(format!("decay on {} needs a positive half-life", term.field)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Boosted score of a hit with this similarity and these fields;
+    /// `now` is in Unix microseconds.
+    pub(super) fn score(&self, similarity: f32, fields: &HashMap<String, Value>, now: i64) -> (f32, ScoreExplanation) {
+        let terms: Vec<TermScore> = self.terms.iter()
+            .map(|term| {
+                let value = fields.get(&term.field).and_then(|v| term.function.apply(v, now)).unwrap_or(0.0);
+                TermScore { field: term.field.clone(), value, score: term.weight * value }
+            })
+            .collect();
+        let similarity_score = self.similarity_weight * similarity;
+        let total = similarity_score + terms.iter().map(|t| t.score).sum::<f32>();
+        (total, ScoreExplanation { similarity, similarity_score, terms })
+    }
+}
+
+impl BoostFunction {
+    fn apply(&self, value: &Value, now: i64) -> Option<f32> {
+        match self {
+            Self::Decay { half_life } => {
+                let age = now.saturating_sub(parse_datetime(value.as_str()?)?).max(0);
+                Some(0.5f64.powf(age as f64 / half_life.as_micros() as f64) as f32)
+            }
+            Self::Log => Some(value.as_f64()?.max(0.0).ln_1p() as f32),
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Maximal marginal relevance re-ranking of search hits.
+
+use crate::distance::{self, DistanceMetric};
+use crate::error::{Result, VectorDbError};
+
+/// Settings of [`Collection::search_by_vector_mmr`](super::Collection::search_by_vector_mmr).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mmr {
+    /// Trade-off between relevance (1) and diversity (0).
+    pub lambda: f32,
+    /// Hits fetched by similarity per requested hit, then re-ranked.
+    pub candidates: usize,
+}
+
+impl Default for Mmr {
+    fn default() -> Self {
+        Self { lambda: 0.5, candidates: 4 }
+    }
+}
+
+impl Mmr {
+    pub fn new(lambda: f32) -> Self {
+        Self { lambda, ..Self::default() }
+    }
+
+    pub fn with_candidates(mut self, candidates: usize) -> Self {
+        self.candidates = candidates;
+        self
+    }
+
+    pub(super) fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.lambda) {
+            return Err(VectorDbError::InvalidConfig(format!("MMR lambda must be within [0, 1], got {}", self.lambda)));
+        }
+        if self.candidates == 0 {
+            return Err(VectorDbError::InvalidConfig("MMR needs at least one candidate per hit".into()));
+        }
+        Ok(())
+    }
+
+    /// Greedily pick up to `k` of `vectors`, each maximising
+    /// `lambda * relevance - (1 - lambda) * similarity to the closest pick`.
+    /// Returns their positions in pick order.
+    pub(super) fn select(&self, metric: DistanceMetric, relevance: &[f32], vectors: &[&[f32]], k: usize) -> Vec<usize> {
+        let mut picked = Vec::with_capacity(k.min(vectors.len()));
+        // Similarity of each candidate to its closest pick, floored at 0;
+        // `None` once picked.
+        let mut closest: Vec<Option<f32>> = vec![Some(0.0); vectors.len()];
+        while picked.len() < k {
+            let best = closest.iter().enumerate()
+                .filter_map(|(i, c)| c.map(|c| (i, self.lambda * relevance[i] - (1.0 - self.lambda) * c)))
+                // Strictly greater, so ties keep search order.
+                .fold(None, |best: Option<(usize, f32)>, (i, score)| match best {
+                    Some((_, b)) if b >= score => best,
+                    _ => Some((i, score)),
+                });
+            let Some((next, _)) = best else { break };
+            closest[next] = None;
+            for (i, c) in closest.iter_mut().enumerate() {
+                if let Some(c) = c {
+                    *c = c.max(distance::compute_score(metric, vectors[next], vectors[i]));
+                }
+            }
+            picked.push(next);
+        }
+        picked
+    }
+}
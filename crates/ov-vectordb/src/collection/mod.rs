@@ -1,15 +1,39 @@
 //! Collection management: CRUD for vectors with filtering and search.
 
-use std::collections::HashMap;
+mod aggregate;
+mod boost;
+mod mmr;
+mod pk_map;
+mod schema;
+mod wal;
+
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-use parking_lot::RwLock;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use parking_lot::{Mutex, RwLock, RwLockUpgradableReadGuard};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::distance::DistanceMetric;
+use crate::distance::{self, DistanceMetric};
 use crate::error::{Result, VectorDbError};
 use crate::filter::Filter;
-use crate::index::{FlatIndex, HnswIndex, VectorIndex};
+use crate::index::{
+    fuse, group_values, plan_filter, tokenize, FlatIndex, FullTextIndex, Fusion, HnswIndex, IvfIndex, Quantization, ScalarIndex, SearchResult,
+    SparseIndex, SparseVector, VectorIndex,
+};
+use crate::meta::IndexMeta;
+use crate::store::FileStore;
+use pk_map::PkMap;
+use wal::{Wal, WalEntry, WAL_FILE};
+
+pub use aggregate::{Aggregation, AggregationResult, Bucket, FieldStats};
+pub use boost::{BoostFunction, BoostTerm, ScoreBoost, ScoreExplanation, TermScore};
+pub use mmr::Mmr;
+pub use schema::{SchemaIssue, SchemaMode};
+pub(crate) use schema::format_issues;
+pub use wal::Durability;
 
 /// Field type for collection schema.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -50,6 +74,24 @@ impl FieldType {
             _ => Self::String,
         }
     }
+
+    /// Schema name of the type, as it is serialized.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Int64 => "int64",
+            Self::Float32 => "float32",
+            Self::String => "string",
+            Self::Bool => "bool",
+            Self::Vector => "vector",
+            Self::ListString => "list<string>",
+            Self::ListInt64 => "list<int64>",
+            Self::ListFloat32 => "list<float32>",
+            Self::Path => "path",
+            Self::DateTime => "datetime",
+            Self::GeoPoint => "geopoint",
+            Self::SparseVector => "sparsevector",
+        }
+    }
 }
 
 /// Field definition in collection schema.
@@ -79,13 +121,29 @@ impl CollectionConfig {
             .map(|f| f.name.as_str())
     }
 
+    /// The default vector field: the first one in the schema.
     pub fn vector_field(&self) -> Option<&FieldDef> {
-        self.fields.iter().find(|f| f.field_type == FieldType::Vector)
+        self.vector_fields().next()
+    }
+
+    /// All vector fields, in schema order.
+    pub fn vector_fields(&self) -> impl Iterator<Item = &FieldDef> {
+        self.fields.iter().filter(|f| f.field_type == FieldType::Vector)
     }
 
+    /// Dimension of the default vector field.
     pub fn dimension(&self) -> usize {
         self.vector_field().and_then(|f| f.dim).unwrap_or(0)
     }
+
+    /// Dimension of the named vector field, or 0 if there is no such field.
+    pub fn vector_dimension(&self, name: &str) -> usize {
+        self.vector_fields().find(|f| f.name == name).and_then(|f| f.dim).unwrap_or(0)
+    }
+
+    pub fn sparse_vector_field(&self) -> Option<&FieldDef> {
+        self.fields.iter().find(|f| f.field_type == FieldType::SparseVector)
+    }
 }
 
 /// Search result item.
@@ -94,6 +152,8 @@ pub struct SearchItem {
     pub id: Value,
     pub score: f32,
     pub fields: HashMap<String, Value>,
+    /// How `score` was made up, for boosted searches.
+    pub explanation: Option<ScoreExplanation>,
 }
 
 /// Collection search result.
@@ -102,18 +162,138 @@ pub struct CollectionSearchResult {
     pub data: Vec<SearchItem>,
 }
 
+/// A record returned by [`Collection::scroll`].
+#[derive(Debug, Clone)]
+pub struct ScrollItem {
+    pub id: Value,
+    /// Fields and vectors, as returned by [`Collection::fetch_data`].
+    pub fields: HashMap<String, Value>,
+}
+
+/// One page of [`Collection::scroll`].
+#[derive(Debug, Clone, Default)]
+pub struct ScrollResult {
+    pub data: Vec<ScrollItem>,
+    /// Cursor for the next page; `None` once every match was returned.
+    pub next_cursor: Option<u64>,
+}
+
+/// How [`Collection::search_grouped`] collapses hits.
+#[derive(Debug, Clone)]
+pub struct GroupBy {
+    /// Field whose value keys the groups.
+    pub field: String,
+    /// Number of groups to return.
+    pub groups: usize,
+    /// Hits kept per group.
+    pub group_size: usize,
+}
+
+impl GroupBy {
+    pub fn new(field: &str, groups: usize, group_size: usize) -> Self {
+        Self { field: field.to_string(), groups, group_size }
+    }
+}
+
+/// Hits sharing a value of the grouping field.
+#[derive(Debug, Clone)]
+pub struct SearchGroup {
+    pub key: Value,
+    /// Score of the best hit.
+    pub score: f32,
+    /// Best hits first.
+    pub hits: Vec<SearchItem>,
+}
+
+/// Result of [`Collection::search_grouped`], best group first.
+#[derive(Debug, Clone, Default)]
+pub struct GroupedSearchResult {
+    pub groups: Vec<SearchGroup>,
+}
+
+/// A query against one index; the variant must match the index kind.
+#[derive(Debug, Clone, Copy)]
+pub enum IndexQuery<'a> {
+    Dense(&'a [f32]),
+    Sparse(&'a SparseVector),
+    Text(&'a str),
+}
+
+/// One weighted sub-query of a [`HybridQuery`].
+#[derive(Debug, Clone)]
+pub struct HybridPart<'a> {
+    pub index: &'a str,
+    pub query: IndexQuery<'a>,
+    pub weight: f32,
+}
+
+/// Query for [`Collection::search_hybrid`]: searches on several indexes
+/// whose ranked results are fused into one list.
+#[derive(Debug, Clone, Default)]
+pub struct HybridQuery<'a> {
+    pub parts: Vec<HybridPart<'a>>,
+    pub fusion: Fusion,
+}
+
+impl<'a> HybridQuery<'a> {
+    /// Empty query, fused with reciprocal rank fusion.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn dense(self, index: &'a str, vector: &'a [f32], weight: f32) -> Self {
+        self.part(index, IndexQuery::Dense(vector), weight)
+    }
+
+    pub fn sparse(self, index: &'a str, vector: &'a SparseVector, weight: f32) -> Self {
+        self.part(index, IndexQuery::Sparse(vector), weight)
+    }
+
+    pub fn text(self, index: &'a str, text: &'a str, weight: f32) -> Self {
+        self.part(index, IndexQuery::Text(text), weight)
+    }
+
+    pub fn with_fusion(mut self, fusion: Fusion) -> Self {
+        self.fusion = fusion;
+        self
+    }
+
+    fn part(mut self, index: &'a str, query: IndexQuery<'a>, weight: f32) -> Self {
+        self.parts.push(HybridPart { index, query, weight });
+        self
+    }
+}
+
 /// Upsert result.
 #[derive(Debug, Clone, Default)]
 pub struct UpsertResult {
     pub ids: Vec<Value>,
+    /// Values dropped or fields kept outside the schema in
+    /// [`SchemaMode::Lenient`].
+    pub warnings: Vec<SchemaIssue>,
 }
 
 /// Index configuration.
 #[derive(Debug, Clone)]
 pub struct IndexConfig {
-    pub index_type: String,  // "flat" or "hnsw"
+    pub index_type: String,  // "flat", "hnsw", "ivf", "sparse" or "fulltext"
     pub distance: DistanceMetric,
     pub scalar_index_fields: Vec<String>,
+    pub text_fields: Vec<String>,  // fields searched by a "fulltext" index
+    /// Vector field a "flat", "hnsw" or "ivf" index is built over; `None` means
+    /// the collection's first vector field.
+    pub vector_field: Option<String>,
+    /// Encoding of the vectors held by a "flat", "hnsw" or "ivf" index.
+    pub quantization: Quantization,
+    /// With quantization, fetch this many candidates per requested hit and
+    /// re-rank them by the full-precision vectors of the records.
+    pub rescore: Option<usize>,
+    /// Memory-map the vectors of an "hnsw" index when it is reopened.
+    pub mmap: bool,
+    /// Lists an "ivf" index partitions its vectors into; `None` for 256.
+    pub nlist: Option<usize>,
+    /// Lists an "ivf" index scans per query; `None` for 16.
+    pub nprobe: Option<usize>,
 }
 
 impl Default for IndexConfig {
@@ -122,21 +302,375 @@ impl Default for IndexConfig {
             index_type: "flat".to_string(),
             distance: DistanceMetric::Cosine,
             scalar_index_fields: Vec::new(),
+            text_fields: Vec::new(),
+            vector_field: None,
+            quantization: Quantization::None,
+            rescore: None,
+            mmap: false,
+            nlist: None,
+            nprobe: None,
+        }
+    }
+}
+
+impl IndexConfig {
+    /// Describe this index for persistence.
+    pub fn to_meta(&self, index_name: &str) -> IndexMeta {
+        IndexMeta {
+            index_name: index_name.to_string(),
+            index_type: self.index_type.clone(),
+            distance: self.distance.to_string(),
+            scalar_index_fields: self.scalar_index_fields.clone(),
+            text_fields: self.text_fields.clone(),
+            vector_field: self.vector_field.clone(),
+            quantization: self.quantization,
+            rescore: self.rescore,
+            mmap: self.mmap,
+            nlist: self.nlist,
+            nprobe: self.nprobe,
+            description: String::new(),
+        }
+    }
+
+    /// Rebuild an index configuration from persisted metadata.
+    pub fn from_meta(meta: &IndexMeta) -> Self {
+        Self {
+            index_type: meta.index_type.clone(),
+            distance: DistanceMetric::from_str_loose(&meta.distance),
+            scalar_index_fields: meta.scalar_index_fields.clone(),
+            text_fields: meta.text_fields.clone(),
+            vector_field: meta.vector_field.clone(),
+            quantization: meta.quantization,
+            rescore: meta.rescore,
+            mmap: meta.mmap,
+            nlist: meta.nlist,
+            nprobe: meta.nprobe,
         }
     }
 }
 
+/// Build state of a named index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexStatus {
+    /// Being filled from the records in the background; not searchable yet.
+    Building,
+    /// Registered and searchable.
+    Ready,
+    /// The background build failed; the index can be dropped or created again.
+    Failed(String),
+}
+
+/// Writes seen while an index is built in the background, replayed
+/// before the index is registered.
+#[derive(Default)]
+struct IndexBuild {
+    error: Option<String>,
+    /// Labels upserted or deleted since the build took its snapshot.
+    touched: HashSet<u64>,
+    /// All data was deleted since the snapshot.
+    cleared: bool,
+}
+
+/// Records a background build copies out per read of the records.
+const BUILD_CHUNK: usize = 1024;
+
 /// Internal record stored in the collection.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Record {
     label: u64,
-    vector: Vec<f32>,
+    /// Dense vectors by vector field name.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    vectors: HashMap<String, Vec<f32>>,
+    /// The single vector of records written before named vector fields;
+    /// moved into `vectors` by [`Record::upgrade`].
+    #[serde(default, rename = "vector", skip_serializing)]
+    legacy_vector: Vec<f32>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    sparse: SparseVector,
     fields: HashMap<String, Value>,
 }
 
+impl Record {
+    /// File the legacy unnamed vector under the collection's first vector field.
+    fn upgrade(&mut self, config: &CollectionConfig) {
+        if self.legacy_vector.is_empty() {
+            return;
+        }
+        let vector = std::mem::take(&mut self.legacy_vector);
+        if let Some(field) = config.vector_field() {
+            self.vectors.entry(field.name.clone()).or_insert(vector);
+        }
+    }
+
+    fn vector(&self, field: &str) -> Option<&[f32]> {
+        self.vectors.get(field).map(Vec::as_slice).filter(|v| !v.is_empty())
+    }
+}
+
+/// Storage behind a named index: dense ANN, sparse inverted or BM25.
+enum IndexBackend {
+    /// ANN index over one vector field.
+    Dense { index: Box<dyn VectorIndex>, field: String },
+    Sparse(SparseIndex),
+    FullText { index: FullTextIndex, fields: Vec<String> },
+}
+
+impl IndexBackend {
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Dense { .. } => "dense",
+            Self::Sparse(_) => "sparse",
+            Self::FullText { .. } => "fulltext",
+        }
+    }
+
+    /// Whether this index holds an entry for `record`.
+    fn covers(&self, record: &Record) -> bool {
+        match self {
+            Self::Dense { field, .. } => record.vector(field).is_some(),
+            Self::Sparse(_) => !record.sparse.is_empty(),
+            Self::FullText { fields, .. } => !tokenize(&record_text(fields, record)).is_empty(),
+        }
+    }
+
+    fn insert(&self, record: &Record) -> Result<()> {
+        match self {
+            Self::Dense { index, field } => match record.vector(field) {
+                Some(v) => index.insert(record.label, v),
+                None => index.delete(record.label),
+            },
+            // Empty sparse vectors and texts unlink any previous entry.
+            Self::Sparse(index) => index.insert(record.label, &record.sparse),
+            Self::FullText { index, fields } => index.insert(record.label, &record_text(fields, record)),
+        }
+    }
+
+    /// Insert many records. A dense index takes them as one batch, which
+    /// it may link in parallel; vectors of the wrong dimension are skipped
+    /// as single inserts would reject them.
+    fn insert_batch(&self, records: &[&Record]) -> Result<()> {
+        let Self::Dense { index, field } = self else {
+            for record in records {
+                self.insert(record)?;
+            }
+            return Ok(());
+        };
+        // A label repeated in the batch ends up as its last record.
+        let last: HashMap<u64, &Record> = records.iter().map(|r| (r.label, *r)).collect();
+        let mut labels = Vec::with_capacity(last.len());
+        let mut vectors = Vec::with_capacity(last.len());
+        for record in last.values() {
+            match record.vector(field) {
+                Some(v) if v.len() == index.dimension() => {
+                    labels.push(record.label);
+                    vectors.push(v.to_vec());
+                }
+                Some(_) => {}
+                None => index.delete(record.label)?,
+            }
+        }
+        index.insert_batch(&labels, &vectors)
+    }
+
+    fn delete(&self, label: u64) -> Result<()> {
+        match self {
+            Self::Dense { index, .. } => index.delete(label),
+            Self::Sparse(index) => index.delete(label),
+            Self::FullText { index, .. } => index.delete(label),
+        }
+    }
+
+    /// Drop deleted entries from a dense index that has built up enough
+    /// of them; the sparse and full-text indexes remove entries on delete.
+    fn vacuum(&self) -> Result<usize> {
+        match self {
+            Self::Dense { index, .. } if index.needs_rebuild() => index.vacuum(),
+            _ => Ok(0),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::Dense { index, .. } => index.len(),
+            Self::Sparse(index) => index.len(),
+            Self::FullText { index, .. } => index.len(),
+        }
+    }
+
+    fn metric(&self) -> DistanceMetric {
+        match self {
+            Self::Dense { index, .. } => index.metric(),
+            // Both score higher-is-better, like inner product.
+            Self::Sparse(_) | Self::FullText { .. } => DistanceMetric::Ip,
+        }
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        match self {
+            Self::Dense { index, .. } => index.save(path),
+            Self::Sparse(index) => index.save(path),
+            Self::FullText { index, .. } => index.save(path),
+        }
+    }
+
+    fn load(&mut self, path: &Path) -> Result<()> {
+        match self {
+            Self::Dense { index, .. } => index.load(path),
+            Self::Sparse(index) => index.load(path),
+            Self::FullText { index, .. } => index.load(path),
+        }
+    }
+
+    /// Top-`k` hits for `query` among labels passing `allow`.
+    fn search_filtered(
+        &self,
+        name: &str,
+        query: IndexQuery<'_>,
+        k: usize,
+        allow: &dyn Fn(u64) -> bool,
+    ) -> Result<SearchResult> {
+        match (self, query) {
+            (Self::Dense { index, .. }, IndexQuery::Dense(v)) => index.search_filtered(v, k, allow),
+            // Sparse and text searches only visit postings of the query
+            // terms, so filtering an exhaustive search is cheap.
+            _ => {
+                let mut hits = self.search(name, query, self.len())?;
+                hits.retain(allow);
+                hits.truncate(k);
+                Ok(hits)
+            }
+        }
+    }
+
+    fn search(&self, name: &str, query: IndexQuery<'_>, k: usize) -> Result<SearchResult> {
+        match (self, query) {
+            (Self::Dense { index, .. }, IndexQuery::Dense(v)) => index.search(v, k),
+            (Self::Sparse(index), IndexQuery::Sparse(v)) => index.search(v, k),
+            (Self::FullText { index, .. }, IndexQuery::Text(text)) => index.search(text, k),
+            _ => Err(VectorDbError::InvalidConfig(format!(
+                "index {name} is {}; it cannot serve a {} query",
+                self.kind(),
+                match query {
+                    IndexQuery::Dense(_) => "dense",
+                    IndexQuery::Sparse(_) => "sparse",
+                    IndexQuery::Text(_) => "text",
+                }
+            ))),
+        }
+    }
+}
+
 struct CollectionIndex {
     config: IndexConfig,
-    index: Box<dyn VectorIndex>,
+    index: IndexBackend,
+    /// Filter indexes for `config.scalar_index_fields`, rebuilt from
+    /// records on open.
+    scalars: HashMap<String, ScalarIndex>,
+}
+
+impl CollectionIndex {
+    fn insert_batch(&self, records: &[&Record]) -> Result<()> {
+        self.index.insert_batch(records)?;
+        for (field, scalar) in &self.scalars {
+            for record in records {
+                scalar.insert(record.label, record.fields.get(field));
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&self, label: u64) {
+        let _ = self.index.delete(label);
+        for scalar in self.scalars.values() {
+            scalar.delete(label);
+        }
+    }
+
+    /// Top-`k` hits for `query`. A quantized dense index with `rescore`
+    /// set over-fetches candidates and ranks them exactly.
+    fn search(
+        &self,
+        name: &str,
+        query: IndexQuery<'_>,
+        k: usize,
+        filter: Option<&Filter>,
+        records: &HashMap<u64, Record>,
+    ) -> Result<SearchResult> {
+        let rescore = match (&self.index, query) {
+            (IndexBackend::Dense { .. }, IndexQuery::Dense(_)) if self.config.quantization != Quantization::None => {
+                self.config.rescore
+            }
+            _ => None,
+        };
+        let Some(factor) = rescore else {
+            return self.candidates(name, query, k, filter, records);
+        };
+        let candidates = self.candidates(name, query, k.saturating_mul(factor.max(1)), filter, records)?;
+        // The scalar indexes may plan a superset of the matches; drop the
+        // rest before truncating, or they could push matches out.
+        let matching = candidates.ids.into_iter()
+            .filter(|label| filter.is_none_or(|f| records.get(label).is_some_and(|r| f.matches(&r.fields))))
+            .collect();
+        let mut hits = self.exact_search(name, query, &matching, records)?;
+        hits.truncate(k);
+        Ok(hits)
+    }
+
+    /// Top-`k` candidates for `query`. With a filter, the scalar indexes
+    /// narrow the allowed labels first and a small allowed set is ranked
+    /// exactly; otherwise the filter is checked during the index search,
+    /// so up to `k` matching hits come back whatever the selectivity.
+    fn candidates(
+        &self,
+        name: &str,
+        query: IndexQuery<'_>,
+        k: usize,
+        filter: Option<&Filter>,
+        records: &HashMap<u64, Record>,
+    ) -> Result<SearchResult> {
+        let Some(filter) = filter else {
+            return self.index.search(name, query, k);
+        };
+        let allowed = plan_filter(&self.scalars, filter);
+        if let Some(allowed) = allowed.as_ref().filter(|a| a.len() <= EXACT_SCAN_LIMIT) {
+            return self.exact_search(name, query, allowed, records);
+        }
+        let allow = |label: u64| {
+            allowed.as_ref().is_none_or(|a| a.contains(&label))
+                && records.get(&label).is_some_and(|r| filter.matches(&r.fields))
+        };
+        self.index.search_filtered(name, query, k, &allow)
+    }
+
+    /// Rank every allowed label against the query.
+    fn exact_search(
+        &self,
+        name: &str,
+        query: IndexQuery<'_>,
+        allowed: &HashSet<u64>,
+        records: &HashMap<u64, Record>,
+    ) -> Result<SearchResult> {
+        let mut scored: Vec<(u64, f32)> = match (&self.index, query) {
+            (IndexBackend::Dense { index, field }, IndexQuery::Dense(q)) => {
+                if q.len() != index.dimension() {
+                    return Err(VectorDbError::DimensionMismatch { expected: index.dimension(), got: q.len() });
+                }
+                allowed.iter()
+                    .filter_map(|label| records.get(label))
+                    .filter_map(|r| Some((r.label, distance::compute_score(index.metric(), q, r.vector(field)?))))
+                    .collect()
+            }
+            _ => {
+                let hits = self.index.search_filtered(name, query, allowed.len(), &|label| allowed.contains(&label))?;
+                hits.ids.into_iter().zip(hits.scores).collect()
+            }
+        };
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+        Ok(SearchResult {
+            ids: scored.iter().map(|s| s.0).collect(),
+            scores: scored.iter().map(|s| s.1).collect(),
+        })
+    }
 }
 
 /// A Collection manages vectors and their metadata with index-backed search.
@@ -146,10 +680,19 @@ pub struct Collection {
     records: RwLock<HashMap<u64, Record>>,
     /// Named indexes.
     indexes: RwLock<HashMap<String, CollectionIndex>>,
+    /// Indexes being built in the background. Locked after `records` and
+    /// before `indexes`.
+    builds: Mutex<HashMap<String, Arc<Mutex<IndexBuild>>>>,
     /// Auto-increment ID counter.
     next_auto_id: RwLock<u64>,
+    /// Labels assigned to string primary keys.
+    pk_labels: RwLock<PkMap>,
     /// Optional persistence path.
     path: Option<PathBuf>,
+    /// Write-ahead log of mutations since the last checkpoint (persistent only).
+    wal: Option<Mutex<Wal>>,
+    /// How upserts handle values that do not fit the schema.
+    schema_mode: SchemaMode,
 }
 
 impl Collection {
@@ -159,18 +702,40 @@ impl Collection {
             config,
             records: RwLock::new(HashMap::new()),
             indexes: RwLock::new(HashMap::new()),
+            builds: Mutex::new(HashMap::new()),
             next_auto_id: RwLock::new(1),
+            pk_labels: RwLock::new(PkMap::default()),
             path: None,
+            wal: None,
+            schema_mode: SchemaMode::default(),
         }
     }
 
+    /// Set how upserts handle values that do not fit the schema.
+    pub fn with_schema_mode(mut self, mode: SchemaMode) -> Self {
+        self.schema_mode = mode;
+        self
+    }
+
     /// Create a persistent collection.
     pub fn with_path(config: CollectionConfig, path: PathBuf) -> Result<Self> {
+        Self::with_durability(config, path, Durability::default())
+    }
+
+    /// Create a persistent collection whose write-ahead log is synced
+    /// according to `durability`.
+    pub fn with_durability(config: CollectionConfig, path: PathBuf, durability: Durability) -> Result<Self> {
         std::fs::create_dir_all(&path)?;
         let mut coll = Self::new(config);
         coll.path = Some(path.clone());
         // Try to recover
         coll.try_recover()?;
+        // Replay mutations made after the last checkpoint
+        let (wal, entries) = Wal::open(&path.join(WAL_FILE), durability)?;
+        for entry in entries {
+            coll.apply(entry)?;
+        }
+        coll.wal = Some(Mutex::new(wal));
         Ok(coll)
     }
 
@@ -182,33 +747,233 @@ impl Collection {
         &self.config.name
     }
 
-    /// Get the dimension of vectors in this collection.
+    /// Dimension of the default (first) vector field.
     pub fn dimension(&self) -> usize {
         self.config.dimension()
     }
 
     /// Create a named index.
+    ///
+    /// The index is filled from the records before it is registered, so
+    /// searches on the other indexes keep running meanwhile; writes wait.
+    /// [`Collection::spawn_create_index`] builds without holding up writes.
     pub fn create_index(&self, name: &str, cfg: IndexConfig) -> Result<()> {
-        let mut indexes = self.indexes.write();
-        if indexes.contains_key(name) {
+        let records = self.records.read();
+        if self.index_taken(name, &self.builds.lock()) {
             return Err(VectorDbError::IndexAlreadyExists(name.to_string()));
         }
+        let cfg = self.check_index_config(cfg)?;
 
-        let dim = self.dimension();
-        let index: Box<dyn VectorIndex> = match cfg.index_type.as_str() {
-            "hnsw" => Box::new(HnswIndex::new(dim, cfg.distance)),
-            _ => Box::new(FlatIndex::new(dim, cfg.distance)),
-        };
+        let index = new_index(&self.config, &cfg);
 
         // Insert all existing records into the new index
+        populate_index(&index, &records)?;
+        let scalars = self.scalar_indexes(&cfg.scalar_index_fields, &records);
+
+        let mut builds = self.builds.lock();
+        // Another caller may have created it while this one was filled.
+        if self.index_taken(name, &builds) {
+            return Err(VectorDbError::IndexAlreadyExists(name.to_string()));
+        }
+        builds.remove(name);
+        self.indexes.write().insert(name.to_string(), CollectionIndex { config: cfg, index, scalars });
+        Ok(())
+    }
+
+    /// Create a named index on a background thread.
+    ///
+    /// Returns once the index is registered as [`IndexStatus::Building`];
+    /// the handle yields the outcome of the build. Writes made meanwhile
+    /// are applied to the index before it turns ready, and searches on it
+    /// fail with [`VectorDbError::IndexNotReady`] until then. An index
+    /// still building at a checkpoint is not saved.
+    pub fn spawn_create_index(self: &Arc<Self>, name: &str, cfg: IndexConfig) -> Result<JoinHandle<Result<()>>> {
+        let (build, labels, cfg) = {
+            let records = self.records.read();
+            let mut builds = self.builds.lock();
+            if self.index_taken(name, &builds) {
+                return Err(VectorDbError::IndexAlreadyExists(name.to_string()));
+            }
+            let cfg = self.check_index_config(cfg)?;
+            let build = Arc::new(Mutex::new(IndexBuild::default()));
+            builds.insert(name.to_string(), Arc::clone(&build));
+            (build, records.keys().copied().collect::<Vec<_>>(), cfg)
+        };
+        let collection = Arc::clone(self);
+        let name = name.to_string();
+        Ok(std::thread::spawn(move || {
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| collection.build_index(&name, cfg, &build, &labels)))
+                .unwrap_or_else(|_| Err(anyhow::anyhow!("building index {name} panicked").into()));
+            if let Err(e) = &result {
+                build.lock().error = Some(e.to_string());
+            }
+            result
+        }))
+    }
+
+    /// Build state of the named index; `None` if there is no such index.
+    pub fn index_status(&self, name: &str) -> Option<IndexStatus> {
+        if let Some(build) = self.builds.lock().get(name) {
+            return Some(match &build.lock().error {
+                Some(e) => IndexStatus::Failed(e.clone()),
+                None => IndexStatus::Building,
+            });
+        }
+        self.indexes.read().contains_key(name).then_some(IndexStatus::Ready)
+    }
+
+    /// Whether `name` belongs to a ready index or one still building.
+    fn index_taken(&self, name: &str, builds: &HashMap<String, Arc<Mutex<IndexBuild>>>) -> bool {
+        self.indexes.read().contains_key(name) || builds.get(name).is_some_and(|b| b.lock().error.is_none())
+    }
+
+    /// Fill an index registered by [`Collection::spawn_create_index`] from
+    /// the `labels` snapshot, a chunk at a time so writers are not held
+    /// up, then replay the writes made meanwhile and make it searchable.
+    fn build_index(&self, name: &str, cfg: IndexConfig, build: &Arc<Mutex<IndexBuild>>, labels: &[u64]) -> Result<()> {
+        // Dropped, or dropped and created again.
+        let current = |builds: &HashMap<String, Arc<Mutex<IndexBuild>>>| {
+            builds.get(name).is_some_and(|b| Arc::ptr_eq(b, build))
+        };
+        let dropped = || VectorDbError::IndexNotFound(name.to_string());
+        let empty = || CollectionIndex {
+            index: new_index(&self.config, &cfg),
+            scalars: self.scalar_indexes(&cfg.scalar_index_fields, &HashMap::new()),
+            config: cfg.clone(),
+        };
+
+        let mut ci = empty();
+        for chunk in labels.chunks(BUILD_CHUNK) {
+            if !current(&self.builds.lock()) {
+                return Err(dropped());
+            }
+            let batch: Vec<Record> = {
+                let records = self.records.read();
+                chunk.iter().filter_map(|label| records.get(label).cloned()).collect()
+            };
+            ci.insert_batch(&batch.iter().collect::<Vec<_>>())?;
+        }
+
         let records = self.records.read();
-        for record in records.values() {
-            if !record.vector.is_empty() {
-                let _ = index.insert(record.label, &record.vector);
+        let mut builds = self.builds.lock();
+        if !current(&builds) {
+            return Err(dropped());
+        }
+        let writes = std::mem::take(&mut *build.lock());
+        if writes.cleared {
+            // Everything copied so far was deleted; later writes are in `touched`.
+            ci = empty();
+        }
+        let (present, gone): (Vec<u64>, Vec<u64>) = writes.touched.into_iter().partition(|label| records.contains_key(label));
+        for label in gone {
+            ci.delete(label);
+        }
+        ci.insert_batch(&present.iter().map(|label| &records[label]).collect::<Vec<_>>())?;
+        builds.remove(name);
+        self.indexes.write().insert(name.to_string(), ci);
+        Ok(())
+    }
+
+    /// Note written labels on the indexes being built.
+    fn touch_builds(&self, labels: impl IntoIterator<Item = u64> + Clone) {
+        for build in self.builds.lock().values() {
+            build.lock().touched.extend(labels.clone());
+        }
+    }
+
+    /// Validate an index definition against the schema, resolving its
+    /// vector field.
+    fn check_index_config(&self, mut cfg: IndexConfig) -> Result<IndexConfig> {
+        if cfg.index_type == "fulltext" {
+            self.check_text_fields(&cfg.text_fields)?;
+        }
+        self.check_scalar_fields(&cfg.scalar_index_fields)?;
+        if is_dense(&cfg.index_type) {
+            cfg.vector_field = self.resolve_vector_field(cfg.vector_field.as_deref())?;
+            let dim = self.config.vector_dimension(cfg.vector_field.as_deref().unwrap_or_default());
+            cfg.quantization.validate(dim)?;
+        } else if cfg.quantization != Quantization::None {
+            return Err(VectorDbError::InvalidConfig(format!("{} indexes cannot be quantized", cfg.index_type)));
+        }
+        if cfg.mmap && cfg.index_type != "hnsw" {
+            return Err(VectorDbError::InvalidConfig(format!("{} indexes cannot be memory-mapped", cfg.index_type)));
+        }
+        if (cfg.nlist.is_some() || cfg.nprobe.is_some()) && cfg.index_type != "ivf" {
+            return Err(VectorDbError::InvalidConfig(format!("{} indexes have no nlist or nprobe", cfg.index_type)));
+        }
+        if cfg.nlist == Some(0) || cfg.nprobe == Some(0) {
+            return Err(VectorDbError::InvalidConfig("nlist and nprobe must be positive".into()));
+        }
+        Ok(cfg)
+    }
+
+    /// The vector field a dense index binds to: `name` if it is a vector
+    /// field, else the default vector field. `None` if the schema has no
+    /// vector field at all.
+    fn resolve_vector_field(&self, name: Option<&str>) -> Result<Option<String>> {
+        let Some(name) = name else {
+            return Ok(self.config.vector_field().map(|f| f.name.clone()));
+        };
+        match self.config.fields.iter().find(|f| f.name == name) {
+            Some(f) if f.field_type == FieldType::Vector && f.dim.is_some() => Ok(Some(name.to_string())),
+            Some(f) if f.field_type == FieldType::Vector => {
+                Err(VectorDbError::InvalidConfig(format!("vector field {name} has no dimension")))
             }
+            Some(f) => Err(VectorDbError::InvalidConfig(format!(
+                "field {name} is {:?}; dense indexes need a vector field",
+                f.field_type
+            ))),
+            None => Err(VectorDbError::InvalidConfig(format!("unknown vector field {name}"))),
         }
+    }
 
-        indexes.insert(name.to_string(), CollectionIndex { config: cfg, index });
+    fn check_scalar_fields(&self, names: &[String]) -> Result<()> {
+        for name in names {
+            match self.config.fields.iter().find(|f| &f.name == name) {
+                Some(f) if scalar_indexable(&f.field_type) => {}
+                Some(f) => {
+                    return Err(VectorDbError::InvalidConfig(format!(
+                        "field {name} is {:?}; scalar indexes need scalar or list fields",
+                        f.field_type
+                    )))
+                }
+                None => return Err(VectorDbError::InvalidConfig(format!("unknown scalar index field {name}"))),
+            }
+        }
+        Ok(())
+    }
+
+    /// Scalar indexes over `names`, populated from `records`. Fields the
+    /// schema cannot index are skipped.
+    fn scalar_indexes(&self, names: &[String], records: &HashMap<u64, Record>) -> HashMap<String, ScalarIndex> {
+        names.iter()
+            .filter(|name| self.config.fields.iter().any(|f| &f.name == *name && scalar_indexable(&f.field_type)))
+            .map(|name| {
+                let scalar = ScalarIndex::new();
+                for r in records.values() {
+                    scalar.insert(r.label, r.fields.get(name));
+                }
+                (name.clone(), scalar)
+            })
+            .collect()
+    }
+
+    fn check_text_fields(&self, names: &[String]) -> Result<()> {
+        if names.is_empty() {
+            return Err(VectorDbError::InvalidConfig("fulltext index needs at least one text field".into()));
+        }
+        for name in names {
+            match self.config.fields.iter().find(|f| &f.name == name) {
+                Some(f) if matches!(f.field_type, FieldType::String | FieldType::ListString) => {}
+                Some(f) => {
+                    return Err(VectorDbError::InvalidConfig(format!(
+                        "field {name} is {:?}; fulltext indexes need string fields",
+                        f.field_type
+                    )))
+                }
+                None => return Err(VectorDbError::InvalidConfig(format!("unknown text field {name}"))),
+            }
+        }
         Ok(())
     }
 
@@ -220,66 +985,65 @@ impl Collection {
         self.indexes.read().keys().cloned().collect()
     }
 
+    /// Drop an index, stopping its build if it is still building.
     pub fn drop_index(&self, name: &str) {
+        self.builds.lock().remove(name);
         self.indexes.write().remove(name);
+        if let Some(ref path) = self.path {
+            let _ = std::fs::remove_dir_all(path.join("indexes").join(name));
+        }
     }
 
     /// Upsert data records.
     pub fn upsert_data(&self, data_list: &[HashMap<String, Value>]) -> Result<UpsertResult> {
         let pk_name = self.config.primary_key().map(|s| s.to_string());
-        let vk_name = self.config.vector_field().map(|f| f.name.clone());
-        let dim = self.dimension();
-
-        let mut result = UpsertResult::default();
-        let mut records = self.records.write();
-        let indexes = self.indexes.read();
+        let sk_name = self.config.sparse_vector_field().map(|f| f.name.clone());
 
-        for data in data_list {
-            let label = if let Some(ref pk) = pk_name {
-                if let Some(pk_val) = data.get(pk) {
-                    value_to_u64(pk_val)
-                } else {
-                    let mut auto = self.next_auto_id.write();
-                    let id = *auto;
-                    *auto += 1;
-                    id
+        // Validate the whole batch before assigning labels or logging.
+        let mut issues = Vec::new();
+        let data_list: Vec<HashMap<String, Value>> = data_list.iter().enumerate()
+            .map(|(i, data)| schema::conform(&self.config, i, data, &mut issues))
+            .collect();
+        if self.schema_mode == SchemaMode::Strict && !issues.is_empty() {
+            return Err(VectorDbError::InvalidRecords(issues));
+        }
+        let mut vectors = Vec::with_capacity(data_list.len());
+        for data in &data_list {
+            let mut named = HashMap::new();
+            for vf in self.config.vector_fields() {
+                let vector = data.get(&vf.name).map(value_to_f32_vec).unwrap_or_default();
+                if vector.is_empty() {
+                    continue;
                 }
-            } else {
-                let mut auto = self.next_auto_id.write();
-                let id = *auto;
-                *auto += 1;
-                id
-            };
-
-            let vector = if let Some(ref vk) = vk_name {
-                if let Some(vec_val) = data.get(vk) {
-                    value_to_f32_vec(vec_val)
-                } else {
-                    Vec::new()
+                let dim = vf.dim.unwrap_or(0);
+                if vector.len() != dim {
+                    return Err(VectorDbError::DimensionMismatch {
+                        expected: dim,
+                        got: vector.len(),
+                    });
                 }
-            } else {
-                Vec::new()
-            };
-
-            if !vector.is_empty() && vector.len() != dim {
-                return Err(VectorDbError::DimensionMismatch {
-                    expected: dim,
-                    got: vector.len(),
-                });
+                named.insert(vf.name.clone(), vector);
             }
+            vectors.push(named);
+        }
 
-            // Build fields (exclude vector field)
-            let mut fields: HashMap<String, Value> = data.clone();
-            if let Some(ref vk) = vk_name {
-                fields.remove(vk);
-            }
+        let mut result = UpsertResult { warnings: issues, ..Default::default() };
+        let mut batch = Vec::with_capacity(data_list.len());
+        for (data, vectors) in data_list.iter().zip(vectors) {
+            let label = match pk_name.as_ref().and_then(|pk| data.get(pk)) {
+                Some(pk_val) => self.assign_label(pk_val),
+                None => self.next_label(),
+            };
 
-            // Update indexes
-            if !vector.is_empty() {
-                for ci in indexes.values() {
-                    let _ = ci.index.insert(label, &vector);
-                }
+            // Build fields (exclude vector fields)
+            let mut fields: HashMap<String, Value> = data.clone();
+            for vf in self.config.vector_fields() {
+                fields.remove(&vf.name);
             }
+            let sparse = sk_name.as_ref()
+                .and_then(|sk| fields.remove(sk))
+                .map(|v| value_to_sparse(&v))
+                .unwrap_or_default();
 
             let id_val = if let Some(ref pk) = pk_name {
                 data.get(pk).cloned().unwrap_or(Value::from(label))
@@ -287,10 +1051,11 @@ impl Collection {
                 Value::from(label)
             };
 
-            records.insert(label, Record { label, vector, fields });
+            batch.push(Record { label, vectors, sparse, fields, legacy_vector: Vec::new() });
             result.ids.push(id_val);
         }
 
+        self.commit(WalEntry::Upsert { records: batch })?;
         Ok(result)
     }
 
@@ -298,46 +1063,178 @@ impl Collection {
     pub fn fetch_data(&self, primary_keys: &[Value]) -> Vec<Option<HashMap<String, Value>>> {
         let records = self.records.read();
         primary_keys.iter().map(|pk| {
-            let label = value_to_u64(pk);
-            records.get(&label).map(|r| {
-                let mut fields = r.fields.clone();
-                // Add vector back
-                if let Some(vf) = self.config.vector_field() {
-                    fields.insert(vf.name.clone(), Value::from(r.vector.iter().map(|&f| Value::from(f as f64)).collect::<Vec<_>>()));
-                }
-                fields
-            })
+            let label = self.lookup_label(pk)?;
+            records.get(&label).map(|r| self.record_data(r))
         }).collect()
     }
 
-    /// Delete records by primary keys.
-    pub fn delete_data(&self, primary_keys: &[Value]) {
-        let mut records = self.records.write();
+    /// Page through the records matching `filters` (all records if
+    /// `None`) in label order. Pass the previous page's `next_cursor` to
+    /// continue; records written meanwhile are seen if they sort after it.
+    /// `limit` must be positive.
+    pub fn scroll(&self, filters: Option<&Value>, limit: usize, cursor: Option<u64>) -> Result<ScrollResult> {
+        if limit == 0 {
+            return Err(VectorDbError::InvalidConfig("scroll limit must be positive".into()));
+        }
+        let filter = self.parse_filter(filters)?;
+        let records = self.records.read();
+        let mut labels = self.matching_labels(&records, filter.as_ref(), cursor);
+        let more = labels.len() > limit;
+        if more {
+            labels.select_nth_unstable(limit);
+            labels.truncate(limit);
+        }
+        labels.sort_unstable();
+        let data = labels.iter()
+            .map(|label| {
+                let r = &records[label];
+                ScrollItem { id: self.record_id(r), fields: self.record_data(r) }
+            })
+            .collect();
+        let next_cursor = if more { labels.last().copied() } else { None };
+        Ok(ScrollResult { data, next_cursor })
+    }
+
+    /// Count the records matching `filters`.
+    pub fn count_by_filter(&self, filters: Option<&Value>) -> Result<usize> {
+        let filter = self.parse_filter(filters)?;
+        let records = self.records.read();
+        Ok(self.matching_labels(&records, filter.as_ref(), None).len())
+    }
+
+    /// Delete the records matching `filters`, returning how many there
+    /// were. The deletion is logged by primary key, like [`Collection::delete_data`].
+    pub fn delete_by_filter(&self, filters: &Value) -> Result<usize> {
+        let filter = Filter::from_json_with_schema_mode(filters, &self.config, self.schema_mode)?;
+        let primary_keys: Vec<Value> = {
+            let records = self.records.read();
+            self.matching_labels(&records, Some(&filter), None).iter()
+                .map(|label| self.record_id(&records[label]))
+                .collect()
+        };
+        if primary_keys.is_empty() {
+            return Ok(0);
+        }
+        let count = primary_keys.len();
+        self.delete_data(&primary_keys)?;
+        Ok(count)
+    }
+
+    /// Aggregate a scalar field over the records matching `filters` (all
+    /// records if `None`), reading the field from a scalar index where an
+    /// index has one.
+    pub fn aggregate(&self, aggregation: &Aggregation, filters: Option<&Value>) -> Result<AggregationResult> {
+        aggregation.validate()?;
+        for name in aggregation.fields() {
+            self.check_value_field(name, "aggregations")?;
+        }
+        let filter = self.parse_filter(filters)?;
+        let records = self.records.read();
+        let allowed: Option<HashSet<u64>> =
+            filter.map(|f| self.matching_labels(&records, Some(&f), None).into_iter().collect());
         let indexes = self.indexes.read();
-        for pk in primary_keys {
-            let label = value_to_u64(pk);
-            if records.remove(&label).is_some() {
-                for ci in indexes.values() {
-                    let _ = ci.index.delete(label);
-                }
+        let groups = |field: &str, allowed: Option<&HashSet<u64>>| {
+            if let Some(scalar) = indexes.values().find_map(|ci| ci.scalars.get(field)) {
+                return scalar.groups(allowed);
+            }
+            let in_scope: Vec<&Record> = match allowed {
+                Some(allowed) => allowed.iter().filter_map(|l| records.get(l)).collect(),
+                None => records.values().collect(),
+            };
+            group_values(in_scope.into_iter().filter_map(|r| r.fields.get(field).map(|v| (r.label, v))))
+        };
+        Ok(aggregate::run(aggregation, allowed.as_ref(), &groups))
+    }
+
+    /// Reject schema fields that `purpose` cannot read values of, and, in
+    /// strict mode, fields outside the schema, like filters do.
+    fn check_value_field(&self, name: &str, purpose: &str) -> Result<()> {
+        match self.config.fields.iter().find(|f| f.name == name) {
+            Some(f) if !scalar_indexable(&f.field_type) => Err(VectorDbError::InvalidConfig(format!(
+                "field {name} is {:?}; {purpose} need scalar or list fields",
+                f.field_type
+            ))),
+            None if self.schema_mode == SchemaMode::Strict => Err(VectorDbError::InvalidConfig(format!(
+                "field {name} is not in the schema; {purpose} need schema fields"
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Unordered labels after `cursor` of the records passing `filter`,
+    /// narrowed by the scalar indexes where one can plan the filter.
+    fn matching_labels(&self, records: &HashMap<u64, Record>, filter: Option<&Filter>, cursor: Option<u64>) -> Vec<u64> {
+        let after = |label: &u64| cursor.is_none_or(|c| *label > c);
+        let Some(filter) = filter else {
+            return records.keys().copied().filter(after).collect();
+        };
+        let planned = self.indexes.read().values()
+            .filter_map(|ci| plan_filter(&ci.scalars, filter))
+            .min_by_key(|labels| labels.len());
+        let passes = |label: &u64| after(label) && records.get(label).is_some_and(|r| filter.matches(&r.fields));
+        match planned {
+            Some(candidates) => candidates.into_iter().filter(passes).collect(),
+            None => records.keys().copied().filter(passes).collect(),
+        }
+    }
+
+    /// Primary key of a record, or its label if it has none.
+    fn record_id(&self, r: &Record) -> Value {
+        self.config.primary_key().and_then(|pk| r.fields.get(pk)).cloned().unwrap_or(Value::from(r.label))
+    }
+
+    /// Fields of a record with its vectors added back.
+    fn record_data(&self, r: &Record) -> HashMap<String, Value> {
+        let mut fields = r.fields.clone();
+        for (name, vector) in &r.vectors {
+            fields.insert(name.clone(), Value::from(vector.iter().map(|&f| Value::from(f as f64)).collect::<Vec<_>>()));
+        }
+        if let Some(sf) = self.config.sparse_vector_field() {
+            if !r.sparse.is_empty() {
+                let sparse = r.sparse.iter().map(|(t, &w)| (t.clone(), Value::from(w as f64))).collect();
+                fields.insert(sf.name.clone(), Value::Object(sparse));
             }
         }
+        fields
+    }
+
+    /// Delete records by primary keys.
+    pub fn delete_data(&self, primary_keys: &[Value]) -> Result<()> {
+        self.commit(WalEntry::Delete { primary_keys: primary_keys.to_vec() })
     }
 
     /// Delete all data.
-    pub fn delete_all_data(&self) {
-        let mut records = self.records.write();
-        records.clear();
-        // Recreate indexes (empty)
-        let mut indexes = self.indexes.write();
-        let dim = self.dimension();
-        for ci in indexes.values_mut() {
-            let new_index: Box<dyn VectorIndex> = match ci.config.index_type.as_str() {
-                "hnsw" => Box::new(HnswIndex::new(dim, ci.config.distance)),
-                _ => Box::new(FlatIndex::new(dim, ci.config.distance)),
-            };
-            ci.index = new_index;
+    pub fn delete_all_data(&self) -> Result<()> {
+        self.commit(WalEntry::DeleteAll)
+    }
+
+    /// Write a snapshot of records and indexes, then truncate the write-ahead log.
+    pub fn checkpoint(&self) -> Result<()> {
+        let Some(ref path) = self.path else { return Ok(()) };
+        let mut wal = self.wal.as_ref().map(|w| w.lock());
+        self.persist(path)?;
+        if let Some(wal) = wal.as_mut() {
+            wal.truncate()?;
+        }
+        Ok(())
+    }
+
+    /// Vacuum every index whose deleted entries have crossed its
+    /// threshold, returning how many entries were removed. Searches keep
+    /// running meanwhile.
+    pub fn vacuum(&self) -> Result<usize> {
+        let indexes = self.indexes.read();
+        let mut removed = 0;
+        for ci in indexes.values() {
+            removed += ci.index.vacuum()?;
         }
+        Ok(removed)
+    }
+
+    /// Run [`Collection::vacuum`] on a background thread.
+    pub fn spawn_vacuum(self: &Arc<Self>) -> JoinHandle<Result<usize>> {
+        let collection = Arc::clone(self);
+        std::thread::spawn(move || collection.vacuum())
     }
 
     /// Search by vector with optional filters.
@@ -349,52 +1246,180 @@ impl Collection {
         offset: usize,
         filters: Option<&Value>,
     ) -> Result<CollectionSearchResult> {
-        let indexes = self.indexes.read();
-        let ci = indexes.get(index_name).ok_or_else(|| VectorDbError::IndexNotFound(index_name.to_string()))?;
+        self.search_index(index_name, IndexQuery::Dense(dense_vector), limit, offset, filters)
+    }
+
+    /// Search by vector, then re-rank an over-fetched candidate set by
+    /// maximal marginal relevance over the records' stored vectors, so
+    /// near-duplicates give way to diverse hits. Scores are the exact
+    /// similarities to `dense_vector`.
+    pub fn search_by_vector_mmr(
+        &self,
+        index_name: &str,
+        dense_vector: &[f32],
+        mmr: &Mmr,
+        limit: usize,
+        offset: usize,
+        filters: Option<&Value>,
+    ) -> Result<CollectionSearchResult> {
+        mmr.validate()?;
+        let filter = self.parse_filter(filters)?;
+        let wanted = limit.saturating_add(offset);
+        let hits = self.ranked(index_name, IndexQuery::Dense(dense_vector), wanted.saturating_mul(mmr.candidates), filter.as_ref())?;
+        let (field, metric) = match self.indexes.read().get(index_name) {
+            Some(CollectionIndex { index: IndexBackend::Dense { index, field }, .. }) => (field.clone(), index.metric()),
+            Some(ci) => {
+                return Err(VectorDbError::InvalidConfig(format!("index {index_name} is {}; MMR needs a dense index", ci.index.kind())))
+            }
+            None => return Err(VectorDbError::IndexNotFound(index_name.to_string())),
+        };
+        let records = self.records.read();
+        let candidates: Vec<(&Record, &[f32])> = hits.ids.iter()
+            .filter_map(|label| records.get(label))
+            .filter(|r| filter.as_ref().is_none_or(|f| f.matches(&r.fields)))
+            .filter_map(|r| Some((r, r.vector(&field)?)))
+            .collect();
+        let vectors: Vec<&[f32]> = candidates.iter().map(|(_, v)| *v).collect();
+        let relevance: Vec<f32> = vectors.iter().map(|v| distance::compute_score(metric, dense_vector, v)).collect();
+        let data = mmr.select(metric, &relevance, &vectors, wanted).into_iter()
+            .skip(offset)
+            .map(|i| self.search_item(candidates[i].0, relevance[i]))
+            .collect();
+        Ok(CollectionSearchResult { data })
+    }
 
-        let filter = filters.and_then(Filter::from_json);
+    /// Search by vector on the named vector field, using a dense index
+    /// bound to it (the first by name if there are several).
+    pub fn search_by_vector_field(
+        &self,
+        vector_field: &str,
+        dense_vector: &[f32],
+        limit: usize,
+        offset: usize,
+        filters: Option<&Value>,
+    ) -> Result<CollectionSearchResult> {
+        let index_name = self.indexes.read().iter()
+            .filter(|(_, ci)| matches!(&ci.index, IndexBackend::Dense { field, .. } if field == vector_field))
+            .map(|(name, _)| name.clone())
+            .min()
+            .ok_or_else(|| VectorDbError::IndexNotFound(format!("dense index on vector field {vector_field}")))?;
+        self.search_by_vector(&index_name, dense_vector, limit, offset, filters)
+    }
 
-        // If no filter, use index search directly
-        if filter.is_none() {
-            let actual_limit = limit + offset;
-            let idx_result = ci.index.search(dense_vector, actual_limit)?;
-            let records = self.records.read();
+    /// Search a sparse index by inner product with optional filters.
+    pub fn search_by_sparse_vector(
+        &self,
+        index_name: &str,
+        sparse_vector: &SparseVector,
+        limit: usize,
+        offset: usize,
+        filters: Option<&Value>,
+    ) -> Result<CollectionSearchResult> {
+        self.search_index(index_name, IndexQuery::Sparse(sparse_vector), limit, offset, filters)
+    }
 
-            let mut data: Vec<SearchItem> = Vec::new();
-            for (i, (&id, &score)) in idx_result.ids.iter().zip(idx_result.scores.iter()).enumerate() {
-                if i < offset { continue; }
-                if data.len() >= limit { break; }
-                let fields = records.get(&id).map(|r| r.fields.clone()).unwrap_or_default();
-                let pk_val = self.label_to_pk(id);
-                data.push(SearchItem { id: pk_val, score, fields });
-            }
+    /// Search a full-text index by BM25 score with optional filters.
+    pub fn search_by_text(
+        &self,
+        index_name: &str,
+        text: &str,
+        limit: usize,
+        offset: usize,
+        filters: Option<&Value>,
+    ) -> Result<CollectionSearchResult> {
+        self.search_index(index_name, IndexQuery::Text(text), limit, offset, filters)
+    }
 
-            return Ok(CollectionSearchResult { data });
+    /// Search the named index and collapse the hits by a field, returning
+    /// the best hits of the top groups. Each group scores as its best hit.
+    /// Hits are fetched in growing batches until the groups fill up or the
+    /// index runs out.
+    pub fn search_grouped(
+        &self,
+        index_name: &str,
+        query: IndexQuery<'_>,
+        group_by: &GroupBy,
+        filters: Option<&Value>,
+    ) -> Result<GroupedSearchResult> {
+        self.check_value_field(&group_by.field, "grouped searches")?;
+        let filter = self.parse_filter(filters)?;
+        if group_by.groups == 0 || group_by.group_size == 0 {
+            return Ok(GroupedSearchResult::default());
+        }
+        let total = self.count();
+        let mut k = group_by.groups.saturating_mul(group_by.group_size).saturating_mul(2);
+        loop {
+            let hits = self.ranked(index_name, query, k, filter.as_ref())?;
+            let groups = self.group(&hits, group_by, filter.as_ref());
+            let full = groups.len() == group_by.groups && groups.iter().all(|g| g.hits.len() == group_by.group_size);
+            if full || hits.len() < k || k >= total {
+                return Ok(GroupedSearchResult { groups });
+            }
+            k = k.saturating_mul(2);
         }
+    }
 
-        // With filter: search more candidates, then filter
-        let filter = filter.unwrap();
-        let search_limit = (limit + offset) * 10; // over-fetch for filtering
-        let idx_result = ci.index.search(dense_vector, search_limit)?;
+    /// Search the named index and re-rank the hits by `boost`, which
+    /// combines similarity with functions of field values. Each item
+    /// carries the breakdown of its score.
+    pub fn search_boosted(
+        &self,
+        index_name: &str,
+        query: IndexQuery<'_>,
+        boost: &ScoreBoost,
+        limit: usize,
+        offset: usize,
+        filters: Option<&Value>,
+    ) -> Result<CollectionSearchResult> {
+        boost.validate()?;
+        for term in &boost.terms {
+            self.check_value_field(&term.field, "score boosts")?;
+        }
+        let filter = self.parse_filter(filters)?;
+        let k = limit.saturating_add(offset).saturating_mul(boost.candidates);
+        let hits = self.ranked(index_name, query, k, filter.as_ref())?;
+        let now = boost.now.unwrap_or_else(chrono::Utc::now).timestamp_micros();
         let records = self.records.read();
+        let mut data: Vec<SearchItem> = hits.ids.iter().zip(hits.scores.iter())
+            .filter_map(|(label, &similarity)| records.get(label).map(|r| (r, similarity)))
+            .filter(|(r, _)| filter.as_ref().is_none_or(|f| f.matches(&r.fields)))
+            .map(|(r, similarity)| {
+                let (score, explanation) = boost.score(similarity, &r.fields, now);
+                SearchItem { explanation: Some(explanation), ..self.search_item(r, score) }
+            })
+            .collect();
+        // Stable, so ties keep their similarity order.
+        data.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+        Ok(CollectionSearchResult { data: data.into_iter().skip(offset).take(limit).collect() })
+    }
 
-        let mut data: Vec<SearchItem> = Vec::new();
-        let mut skipped = 0;
-        for (&id, &score) in idx_result.ids.iter().zip(idx_result.scores.iter()) {
-            if let Some(record) = records.get(&id) {
-                if filter.matches(&record.fields) {
-                    if skipped < offset {
-                        skipped += 1;
-                        continue;
-                    }
-                    if data.len() >= limit { break; }
-                    let pk_val = self.label_to_pk(id);
-                    data.push(SearchItem { id: pk_val, score, fields: record.fields.clone() });
-                }
-            }
+    /// Search several indexes and fuse their rankings.
+    /// Scores in the result are fusion scores.
+    pub fn search_hybrid(
+        &self,
+        query: &HybridQuery<'_>,
+        limit: usize,
+        offset: usize,
+        filters: Option<&Value>,
+    ) -> Result<CollectionSearchResult> {
+        let filter = self.parse_filter(filters)?;
+        // Over-fetch each side so records ranked moderately by each still meet.
+        let k = limit.saturating_add(offset).saturating_mul(2);
+        let lists = {
+            let records = self.records.read();
+            let indexes = self.indexes.read();
+            query.parts.iter()
+                .map(|part| {
+                    let ci = indexes.get(part.index).ok_or_else(|| VectorDbError::IndexNotFound(part.index.to_string()))?;
+                    Ok((ci.search(part.index, part.query, k, filter.as_ref(), &records)?, part.weight))
+                })
+                .collect::<Result<Vec<_>>>()
         }
-
-        Ok(CollectionSearchResult { data })
+        .map_err(|e| self.unavailable(e))?;
+        let weighted: Vec<(&SearchResult, f32)> = lists.iter().map(|(r, w)| (r, *w)).collect();
+        let total = lists.iter().map(|(r, _)| r.len()).sum();
+        let fused = fuse(&weighted, query.fusion, total);
+        Ok(self.page(&fused, limit, offset, filter.as_ref()))
     }
 
     /// Get record count.
@@ -404,9 +1429,7 @@ impl Collection {
 
     /// Close the collection.
     pub fn close(&self) {
-        if let Some(ref path) = self.path {
-            let _ = self.persist(path);
-        }
+        let _ = self.checkpoint();
     }
 
     /// Drop the collection (remove all data and optionally files).
@@ -417,58 +1440,364 @@ impl Collection {
         }
     }
 
-    fn label_to_pk(&self, label: u64) -> Value {
-        if let Some(pk_name) = self.config.primary_key() {
+    /// Log a mutation (holding the log through the update so checkpoints
+    /// never split the two), then apply it.
+    fn commit(&self, entry: WalEntry) -> Result<()> {
+        let mut wal = self.wal.as_ref().map(|w| w.lock());
+        if let Some(wal) = wal.as_mut() {
+            wal.append(&entry)?;
+        }
+        self.apply(entry)
+    }
+
+    /// Parse and validate a request filter against the schema. JSON
+    /// `null` means no filter.
+    fn parse_filter(&self, filters: Option<&Value>) -> Result<Option<Filter>> {
+        filters.filter(|v| !v.is_null())
+            .map(|v| Filter::from_json_with_schema_mode(v, &self.config, self.schema_mode))
+            .transpose()
+    }
+
+    fn search_index(
+        &self,
+        index_name: &str,
+        query: IndexQuery<'_>,
+        limit: usize,
+        offset: usize,
+        filters: Option<&Value>,
+    ) -> Result<CollectionSearchResult> {
+        let filter = self.parse_filter(filters)?;
+        let hits = self.ranked(index_name, query, limit.saturating_add(offset), filter.as_ref())?;
+        Ok(self.page(&hits, limit, offset, filter.as_ref()))
+    }
+
+    /// Top-`k` hits of the named index for `query`.
+    fn ranked(&self, index_name: &str, query: IndexQuery<'_>, k: usize, filter: Option<&Filter>) -> Result<SearchResult> {
+        let hits = {
             let records = self.records.read();
-            if let Some(record) = records.get(&label) {
-                if let Some(val) = record.fields.get(pk_name) {
-                    return val.clone();
+            let indexes = self.indexes.read();
+            indexes.get(index_name)
+                .ok_or_else(|| VectorDbError::IndexNotFound(index_name.to_string()))
+                .and_then(|ci| ci.search(index_name, query, k, filter, &records))
+        };
+        hits.map_err(|e| self.unavailable(e))
+    }
+
+    /// Report a missing index that is still building as not ready. Called
+    /// without `indexes` held, which is locked after `builds`.
+    fn unavailable(&self, err: VectorDbError) -> VectorDbError {
+        match err {
+            VectorDbError::IndexNotFound(name) if self.builds.lock().contains_key(&name) => {
+                VectorDbError::IndexNotReady(name)
+            }
+            err => err,
+        }
+    }
+
+    /// Turn ranked labels into result items, applying the filter before
+    /// `offset` and `limit`.
+    fn page(&self, hits: &SearchResult, limit: usize, offset: usize, filter: Option<&Filter>) -> CollectionSearchResult {
+        let records = self.records.read();
+        let data = hits.ids.iter().zip(hits.scores.iter())
+            .filter_map(|(label, &score)| records.get(label).map(|r| (r, score)))
+            .filter(|(r, _)| filter.is_none_or(|f| f.matches(&r.fields)))
+            .skip(offset)
+            .take(limit)
+            .map(|(r, score)| self.search_item(r, score))
+            .collect();
+        CollectionSearchResult { data }
+    }
+
+    fn search_item(&self, r: &Record, score: f32) -> SearchItem {
+        SearchItem { id: self.record_id(r), score, fields: r.fields.clone(), explanation: None }
+    }
+
+    /// Collapse ranked hits into groups by `group_by.field`, in order of
+    /// their best hit, keeping up to `group_by.groups` groups of up to
+    /// `group_by.group_size` hits. Hits without the field are left out.
+    fn group(&self, hits: &SearchResult, group_by: &GroupBy, filter: Option<&Filter>) -> Vec<SearchGroup> {
+        let records = self.records.read();
+        let mut groups: Vec<SearchGroup> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        for (label, &score) in hits.ids.iter().zip(hits.scores.iter()) {
+            let Some(r) = records.get(label).filter(|r| filter.is_none_or(|f| f.matches(&r.fields))) else { continue };
+            let Some(key) = r.fields.get(&group_by.field) else { continue };
+            let position = match positions.get(&key.to_string()) {
+                Some(&position) => position,
+                None if groups.len() < group_by.groups => {
+                    positions.insert(key.to_string(), groups.len());
+                    groups.push(SearchGroup { key: key.clone(), score, hits: Vec::new() });
+                    groups.len() - 1
+                }
+                None => continue,
+            };
+            if groups[position].hits.len() < group_by.group_size {
+                groups[position].hits.push(self.search_item(r, score));
+            }
+        }
+        groups
+    }
+
+    fn apply(&self, entry: WalEntry) -> Result<()> {
+        match entry {
+            WalEntry::Upsert { records } => return self.apply_upsert(records),
+            WalEntry::Delete { primary_keys } => self.apply_delete(&primary_keys),
+            WalEntry::DeleteAll => self.apply_delete_all(),
+        }
+        Ok(())
+    }
+
+    /// Store a batch and index it. The records are kept even if an index
+    /// rejects them, matching the log; the first index error is returned.
+    fn apply_upsert(&self, mut batch: Vec<Record>) -> Result<()> {
+        let pk_name = self.config.primary_key();
+        let mut records = self.records.write();
+        for record in &mut batch {
+            // Log entries from before named vector fields.
+            record.upgrade(&self.config);
+            // Re-register labels so replayed records resolve like live ones.
+            if let Some(Value::String(s)) = pk_name.and_then(|pk| record.fields.get(pk)) {
+                self.pk_labels.write().insert(s.clone(), record.label);
+            }
+            {
+                let mut auto = self.next_auto_id.write();
+                if record.label >= *auto {
+                    *auto = record.label.saturating_add(1);
                 }
             }
         }
-        Value::from(label)
+        self.touch_builds(batch.iter().map(|r| r.label));
+        let refs: Vec<&Record> = batch.iter().collect();
+        let mut indexed = Ok(());
+        for ci in self.indexes.read().values() {
+            let result = ci.insert_batch(&refs);
+            indexed = indexed.and(result);
+        }
+        for record in batch {
+            records.insert(record.label, record);
+        }
+        indexed
+    }
+
+    fn apply_delete(&self, primary_keys: &[Value]) {
+        let mut records = self.records.write();
+        let mut removed = Vec::new();
+        for pk in primary_keys {
+            let Some(label) = self.lookup_label(pk) else { continue };
+            if let Value::String(s) = pk {
+                self.pk_labels.write().remove(s);
+            }
+            if records.remove(&label).is_some() {
+                removed.push(label);
+            }
+        }
+        self.touch_builds(removed.iter().copied());
+        for ci in self.indexes.read().values() {
+            for &label in &removed {
+                ci.delete(label);
+            }
+        }
+    }
+
+    fn apply_delete_all(&self) {
+        let mut records = self.records.write();
+        records.clear();
+        self.pk_labels.write().clear();
+        for build in self.builds.lock().values() {
+            let mut build = build.lock();
+            build.cleared = true;
+            build.touched.clear();
+        }
+        // Recreate indexes (empty)
+        let mut indexes = self.indexes.write();
+        for ci in indexes.values_mut() {
+            ci.index = new_index(&self.config, &ci.config);
+            for scalar in ci.scalars.values_mut() {
+                *scalar = ScalarIndex::new();
+            }
+        }
+    }
+
+    /// Label of an existing record with this primary key.
+    fn lookup_label(&self, pk: &Value) -> Option<u64> {
+        match pk {
+            Value::Number(_) => Some(value_to_u64(pk)),
+            Value::String(s) => self.pk_labels.read().label(s),
+            _ => None,
+        }
+    }
+
+    /// Label for a primary key being written, allocating one for new string keys.
+    fn assign_label(&self, pk: &Value) -> u64 {
+        match pk {
+            Value::Number(_) => {
+                let label = value_to_u64(pk);
+                // Keep auto-assigned labels clear of explicit numeric keys.
+                let mut auto = self.next_auto_id.write();
+                if label >= *auto {
+                    *auto = label.saturating_add(1);
+                }
+                label
+            }
+            Value::String(s) => {
+                // Look up and allocate under one guard, so concurrent
+                // upserts of a new key agree on its label.
+                let pk_labels = self.pk_labels.upgradable_read();
+                if let Some(label) = pk_labels.label(s) {
+                    return label;
+                }
+                let mut pk_labels = RwLockUpgradableReadGuard::upgrade(pk_labels);
+                let label = self.next_label();
+                pk_labels.insert(s.clone(), label);
+                label
+            }
+            _ => self.next_label(),
+        }
+    }
+
+    fn next_label(&self) -> u64 {
+        let mut auto = self.next_auto_id.write();
+        let id = *auto;
+        *auto += 1;
+        id
     }
 
     fn persist(&self, path: &Path) -> Result<()> {
         std::fs::create_dir_all(path)?;
+        // Snapshot files are replaced atomically so a crash mid-checkpoint
+        // leaves the previous snapshot plus the log intact.
+        let store = FileStore::new(Some(path.to_path_buf()));
+        let put = |key: &str, bytes: &[u8]| -> Result<()> {
+            if store.put(key, bytes) {
+                Ok(())
+            } else {
+                Err(VectorDbError::Storage(format!("failed to write {}", path.join(key).display())))
+            }
+        };
+
         // Save config
-        let config_path = path.join("collection_config.json");
         let config_bytes = serde_json::to_vec_pretty(&self.config)
             .map_err(|e| VectorDbError::Serialization(e.to_string()))?;
-        std::fs::write(&config_path, &config_bytes)?;
+        put("collection_config.json", &config_bytes)?;
 
-        // Save records
+        // Save records along with their string primary key labels
         let records = self.records.read();
-        let records_vec: Vec<&Record> = records.values().collect();
-        let records_bytes = serde_json::to_vec(&records_vec)
+        let pk_labels = self.pk_labels.read();
+        let snapshot = SnapshotRef { records: records.values().collect(), pk_labels: &pk_labels };
+        let records_bytes = serde_json::to_vec(&snapshot)
             .map_err(|e| VectorDbError::Serialization(e.to_string()))?;
-        std::fs::write(path.join("records.json"), &records_bytes)?;
+        put("records.json", &records_bytes)?;
+        drop(pk_labels);
+        // Superseded by the map inside records.json.
+        let _ = std::fs::remove_file(path.join(LEGACY_PK_LABELS_FILE));
 
         // Save indexes
         let indexes = self.indexes.read();
         for (name, ci) in indexes.iter() {
             let index_path = path.join("indexes").join(name);
-            let _ = ci.index.save(&index_path);
+            ci.index.save(&index_path)?;
+            let meta_bytes = serde_json::to_vec_pretty(&ci.config.to_meta(name))
+                .map_err(|e| VectorDbError::Serialization(e.to_string()))?;
+            put(&format!("indexes/{name}/{INDEX_META_FILE}"), &meta_bytes)?;
         }
 
         Ok(())
     }
 
     fn try_recover(&mut self) -> Result<()> {
+        let mut relabeled = false;
         if let Some(ref path) = self.path {
             let records_path = path.join("records.json");
             if records_path.exists() {
                 let data = std::fs::read(&records_path)?;
-                if let Ok(records_vec) = serde_json::from_slice::<Vec<Record>>(&data) {
-                    let mut records = self.records.write();
-                    let mut max_id = 0u64;
-                    for r in records_vec {
-                        if r.label > max_id { max_id = r.label; }
-                        records.insert(r.label, r);
+                // An unreadable snapshot is an error: coming up empty would
+                // let the next checkpoint overwrite it.
+                let snapshot = serde_json::from_slice::<SnapshotFile>(&data)
+                    .map_err(|e| VectorDbError::Serialization(format!("{}: {e}", records_path.display())))?;
+                let (mut records_vec, mut pk_labels) = match snapshot {
+                    SnapshotFile::Current { records, pk_labels } => (records, pk_labels),
+                    SnapshotFile::Legacy(records) => {
+                        let pk_labels = std::fs::read(path.join(LEGACY_PK_LABELS_FILE)).ok()
+                            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+                            .unwrap_or_default();
+                        (records, pk_labels)
                     }
-                    *self.next_auto_id.write() = max_id + 1;
+                };
+                for r in &mut records_vec {
+                    r.upgrade(&self.config);
+                }
+                let pk_name = self.config.primary_key().map(|s| s.to_string());
+                let string_pk = |r: &Record| -> Option<String> {
+                    pk_name.as_ref()
+                        .and_then(|pk| r.fields.get(pk))
+                        .and_then(|v| v.as_str())
+                        .map(|s| s.to_string())
+                };
+
+                // Records with a string key missing from the map carry a
+                // legacy hashed label and are relabeled below.
+                let (current, legacy): (Vec<Record>, Vec<Record>) = records_vec.into_iter()
+                    .partition(|r| match string_pk(r) {
+                        Some(pk) => pk_labels.label(&pk) == Some(r.label),
+                        None => true,
+                    });
+
+                let mut records = self.records.write();
+                let mut max_id = 0u64;
+                for r in current {
+                    if r.label > max_id { max_id = r.label; }
+                    records.insert(r.label, r);
+                }
+                pk_labels.retain_labels(|label| records.contains_key(&label));
+                max_id = max_id.max(pk_labels.max_label().unwrap_or(0));
+                relabeled = !legacy.is_empty();
+                for mut r in legacy {
+                    let pk = string_pk(&r).unwrap_or_default();
+                    max_id += 1;
+                    r.label = max_id;
+                    pk_labels.insert(pk, r.label);
+                    records.insert(r.label, r);
                 }
+                *self.next_auto_id.write() = max_id.saturating_add(1);
+                *self.pk_labels.write() = pk_labels;
             }
+            self.restore_indexes(&path.join("indexes"), relabeled)?;
+        }
+        Ok(())
+    }
+
+    /// Reload persisted indexes, rebuilding from records any index whose
+    /// file is missing, unreadable or out of step with the records.
+    fn restore_indexes(&self, indexes_dir: &Path, force_rebuild: bool) -> Result<()> {
+        if !indexes_dir.is_dir() {
+            return Ok(());
+        }
+        let records = self.records.read();
+        let mut indexes = self.indexes.write();
+        for entry in std::fs::read_dir(indexes_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() { continue; }
+            let name = entry.file_name().to_string_lossy().to_string();
+            let index_path = entry.path();
+            let Some(mut config) = read_index_config(&index_path) else { continue };
+            if is_dense(&config.index_type) && config.vector_field.is_none() {
+                config.vector_field = self.config.vector_field().map(|f| f.name.clone());
+            }
+
+            let mut index = new_index(&self.config, &config);
+            let loaded = !force_rebuild
+                && index.load(&index_path).is_ok()
+                && !matches!(&index, IndexBackend::Dense { index, field }
+                    if index.dimension() != self.config.vector_dimension(field))
+                && index.len() == records.values().filter(|r| index.covers(r)).count();
+            if !loaded {
+                tracing::warn!("rebuilding index {name} of collection {} from records", self.config.name);
+                index = new_index(&self.config, &config);
+                populate_index(&index, &records)?;
+            }
+            config.distance = index.metric();
+            let scalars = self.scalar_indexes(&config.scalar_index_fields, &records);
+            indexes.insert(name, CollectionIndex { config, index, scalars });
         }
         Ok(())
     }
@@ -476,28 +1805,133 @@ impl Collection {
 
 impl Drop for Collection {
     fn drop(&mut self) {
-        if let Some(ref path) = self.path {
-            let _ = self.persist(path);
-        }
+        let _ = self.checkpoint();
     }
 }
 
 // -- Helper functions --
 
+const INDEX_META_FILE: &str = "index_meta.json";
+
+/// String key labels as saved beside records.json before they moved into it.
+const LEGACY_PK_LABELS_FILE: &str = "pk_labels.json";
+
+/// Contents of records.json. Records and their string key labels are
+/// saved in one file, so a crash mid-checkpoint cannot pair one with a
+/// stale copy of the other.
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    records: Vec<&'a Record>,
+    pk_labels: &'a PkMap,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SnapshotFile {
+    Current { records: Vec<Record>, pk_labels: PkMap },
+    /// Records alone, with the labels in [`LEGACY_PK_LABELS_FILE`].
+    Legacy(Vec<Record>),
+}
+
+fn new_index(config: &CollectionConfig, cfg: &IndexConfig) -> IndexBackend {
+    let field = cfg.vector_field.clone().unwrap_or_default();
+    let dim = config.vector_dimension(&field);
+    match cfg.index_type.as_str() {
+        "hnsw" => IndexBackend::Dense {
+            index: Box::new(HnswIndex::new(dim, cfg.distance).with_quantization(cfg.quantization).with_mmap(cfg.mmap)),
+            field,
+        },
+        "ivf" => {
+            let mut index = IvfIndex::new(dim, cfg.distance).with_quantization(cfg.quantization);
+            if let Some(nlist) = cfg.nlist {
+                index = index.with_nlist(nlist);
+            }
+            if let Some(nprobe) = cfg.nprobe {
+                index = index.with_nprobe(nprobe);
+            }
+            IndexBackend::Dense { index: Box::new(index), field }
+        }
+        "sparse" => IndexBackend::Sparse(SparseIndex::new()),
+        "fulltext" => IndexBackend::FullText { index: FullTextIndex::new(), fields: cfg.text_fields.clone() },
+        _ => IndexBackend::Dense {
+            index: Box::new(FlatIndex::new(dim, cfg.distance).with_quantization(cfg.quantization)),
+            field,
+        },
+    }
+}
+
+/// Whether an index type is a dense ANN index bound to a vector field.
+fn is_dense(index_type: &str) -> bool {
+    !matches!(index_type, "sparse" | "fulltext")
+}
+
+fn populate_index(index: &IndexBackend, records: &HashMap<u64, Record>) -> Result<()> {
+    let covered: Vec<&Record> = records.values().filter(|r| index.covers(r)).collect();
+    index.insert_batch(&covered)
+}
+
+/// Allowed sets up to this size are ranked by exact scan.
+const EXACT_SCAN_LIMIT: usize = 4096;
+
+fn scalar_indexable(field_type: &FieldType) -> bool {
+    !matches!(field_type, FieldType::Vector | FieldType::SparseVector | FieldType::GeoPoint)
+}
+
+/// Read an index definition, inferring it from the index file for
+/// directories written before `index_meta.json` existed.
+fn read_index_config(index_path: &Path) -> Option<IndexConfig> {
+    if let Ok(bytes) = std::fs::read(index_path.join(INDEX_META_FILE)) {
+        if let Ok(meta) = serde_json::from_slice::<IndexMeta>(&bytes) {
+            return Some(IndexConfig::from_meta(&meta));
+        }
+    }
+    let index_type = if index_path.join("hnsw_index.bin").exists() || index_path.join("hnsw_index.json").exists() {
+        "hnsw"
+    } else if index_path.join("ivf_index.json").exists() {
+        "ivf"
+    } else if index_path.join("sparse_index.json").exists() {
+        "sparse"
+    } else if index_path.join("flat_index.bin").exists() {
+        "flat"
+    } else {
+        return None;
+    };
+    Some(IndexConfig { index_type: index_type.to_string(), ..IndexConfig::default() })
+}
+
+/// Label for a numeric primary key. String keys are mapped through the
+/// collection's persisted key map instead.
 pub fn value_to_u64(v: &Value) -> u64 {
     match v {
         Value::Number(n) => n.as_u64().or_else(|| n.as_i64().map(|i| i as u64)).unwrap_or(0),
-        Value::String(s) => {
-            // Hash string to u64 for primary key mapping
-            use std::hash::{Hash, Hasher};
-            let mut hasher = std::collections::hash_map::DefaultHasher::new();
-            s.hash(&mut hasher);
-            hasher.finish()
-        }
         _ => 0,
     }
 }
 
+/// Text of `fields` in a record, for full-text indexing. String lists
+/// contribute each element.
+fn record_text(fields: &[String], record: &Record) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for name in fields {
+        match record.fields.get(name) {
+            Some(Value::String(s)) => parts.push(s),
+            Some(Value::Array(items)) => parts.extend(items.iter().filter_map(|v| v.as_str())),
+            _ => {}
+        }
+    }
+    parts.join("\n")
+}
+
+/// Sparse vector from a `{term: weight}` object; other values yield an empty vector.
+fn value_to_sparse(v: &Value) -> SparseVector {
+    match v {
+        Value::Object(map) => map.iter()
+            .filter_map(|(t, w)| w.as_f64().map(|w| (t.clone(), w as f32)))
+            .collect(),
+        _ => SparseVector::new(),
+    }
+}
+
 fn value_to_f32_vec(v: &Value) -> Vec<f32> {
     match v {
         Value::Array(arr) => arr.iter().filter_map(|x| x.as_f64().map(|f| f as f32)).collect(),
@@ -0,0 +1,61 @@
+//! Bidirectional string primary key <-> label map.
+//!
+//! String primary keys get sequential labels from the collection's
+//! auto-id counter instead of a hash, so distinct keys never share a
+//! label and labels stay stable across restarts and toolchains.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(from = "HashMap<String, u64>", into = "HashMap<String, u64>")]
+pub(crate) struct PkMap {
+    to_label: HashMap<String, u64>,
+    to_pk: HashMap<u64, String>,
+}
+
+impl PkMap {
+    pub fn label(&self, pk: &str) -> Option<u64> {
+        self.to_label.get(pk).copied()
+    }
+
+    pub fn insert(&mut self, pk: String, label: u64) {
+        if let Some(old) = self.to_label.insert(pk.clone(), label) {
+            self.to_pk.remove(&old);
+        }
+        self.to_pk.insert(label, pk);
+    }
+
+    pub fn remove(&mut self, pk: &str) -> Option<u64> {
+        let label = self.to_label.remove(pk)?;
+        self.to_pk.remove(&label);
+        Some(label)
+    }
+
+    pub fn retain_labels(&mut self, mut keep: impl FnMut(u64) -> bool) {
+        self.to_label.retain(|_, label| keep(*label));
+        self.to_pk.retain(|label, _| keep(*label));
+    }
+
+    pub fn max_label(&self) -> Option<u64> {
+        self.to_pk.keys().copied().max()
+    }
+
+    pub fn clear(&mut self) {
+        self.to_label.clear();
+        self.to_pk.clear();
+    }
+}
+
+impl From<HashMap<String, u64>> for PkMap {
+    fn from(to_label: HashMap<String, u64>) -> Self {
+        let to_pk = to_label.iter().map(|(pk, &label)| (label, pk.clone())).collect();
+        Self { to_label, to_pk }
+    }
+}
+
+impl From<PkMap> for HashMap<String, u64> {
+    fn from(map: PkMap) -> Self {
+        map.to_label
+    }
+}
@@ -0,0 +1,200 @@
+//! Validation and coercion of upserted records against the collection
+//! schema.
+//!
+//! Values are converted to the canonical form of their `FieldType` where
+//! that loses nothing (`"42"` for an int64, a scalar for a list, an epoch
+//! for a datetime). Values that cannot be converted become
+//! [`SchemaIssue`]s, which [`SchemaMode`] turns into errors or warnings.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use chrono::{DateTime, NaiveDate, SecondsFormat};
+use serde_json::{Number, Value};
+
+use crate::filter::{parse_datetime, parse_geo_point};
+use super::{CollectionConfig, FieldType};
+
+/// How an upsert treats values that do not fit their field's type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SchemaMode {
+    /// Reject the whole batch, reporting every bad value and unknown field.
+    Strict,
+    /// Drop bad values, keep unknown fields, and report both as warnings
+    /// in the [`UpsertResult`](super::UpsertResult).
+    #[default]
+    Lenient,
+}
+
+/// A value in an upsert batch that does not fit the schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaIssue {
+    /// Position of the record in the batch.
+    pub record: usize,
+    pub field: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "record {}, field {:?}: {}", self.record, self.field, self.message)
+    }
+}
+
+/// Issues listed in an error message before the rest are counted.
+const SHOWN_ISSUES: usize = 5;
+
+pub(crate) fn format_issues(issues: &[SchemaIssue]) -> String {
+    let mut out = issues.iter().take(SHOWN_ISSUES).map(|i| i.to_string()).collect::<Vec<_>>().join("; ");
+    if issues.len() > SHOWN_ISSUES {
+        out.push_str(&format!("; and {} more", issues.len() - SHOWN_ISSUES));
+    }
+    out
+}
+
+/// Coerce the fields of record `index` to the schema. Values that cannot
+/// be coerced are left out and reported in `issues`; unknown fields are
+/// reported but kept.
+pub(crate) fn conform(
+    config: &CollectionConfig,
+    index: usize,
+    data: &HashMap<String, Value>,
+    issues: &mut Vec<SchemaIssue>,
+) -> HashMap<String, Value> {
+    let mut out = HashMap::with_capacity(data.len());
+    for (name, value) in data {
+        let issue = |message: String| SchemaIssue { record: index, field: name.clone(), message };
+        let Some(def) = config.fields.iter().find(|f| &f.name == name) else {
+            issues.push(issue("field is not in the schema".into()));
+            out.insert(name.clone(), value.clone());
+            continue;
+        };
+        if value.is_null() {
+            out.insert(name.clone(), Value::Null);
+            continue;
+        }
+        match coerce(&def.field_type, value) {
+            Ok(v) => {
+                out.insert(name.clone(), v);
+            }
+            Err(message) => issues.push(issue(message)),
+        }
+    }
+    out
+}
+
+fn coerce(ty: &FieldType, v: &Value) -> Result<Value, String> {
+    match ty {
+        FieldType::Int64 => coerce_int(v),
+        FieldType::Float32 => coerce_float(v),
+        FieldType::String => match v {
+            Value::String(_) => Ok(v.clone()),
+            Value::Number(_) | Value::Bool(_) => Ok(Value::String(v.to_string())),
+            _ => Err(format!("expected a string, got {}", kind(v))),
+        },
+        FieldType::Bool => match v {
+            Value::Bool(_) => Ok(v.clone()),
+            Value::String(s) if s.eq_ignore_ascii_case("true") => Ok(Value::Bool(true)),
+            Value::String(s) if s.eq_ignore_ascii_case("false") => Ok(Value::Bool(false)),
+            _ => Err(format!("expected a bool, got {}", kind(v))),
+        },
+        FieldType::Path => match v.as_str() {
+            Some("") => Err("expected a path, got an empty string".into()),
+            Some(s) if s.contains('\0') => Err("path contains a NUL character".into()),
+            Some(_) => Ok(v.clone()),
+            None => Err(format!("expected a path string, got {}", kind(v))),
+        },
+        FieldType::DateTime => coerce_datetime(v),
+        FieldType::GeoPoint => parse_geo_point(v)
+            .map(|(lon, lat)| Value::String(format!("{lon},{lat}")))
+            .ok_or_else(|| "expected a geo point as \"lon,lat\", [lon, lat] or {\"lon\", \"lat\"}".into()),
+        FieldType::ListString => coerce_list(v, &FieldType::String),
+        FieldType::ListInt64 => coerce_list(v, &FieldType::Int64),
+        FieldType::ListFloat32 => coerce_list(v, &FieldType::Float32),
+        FieldType::Vector => match v.as_array() {
+            Some(items) if items.iter().all(|x| x.as_f64().is_some_and(f64::is_finite)) => Ok(v.clone()),
+            Some(_) => Err("vector elements must be finite numbers".into()),
+            None => Err(format!("expected a vector array, got {}", kind(v))),
+        },
+        FieldType::SparseVector => match v.as_object() {
+            Some(map) => match map.iter().find(|(_, w)| !w.as_f64().is_some_and(f64::is_finite)) {
+                Some((term, _)) => Err(format!("sparse weight for {term:?} is not a finite number")),
+                None => Ok(v.clone()),
+            },
+            None => Err(format!("expected a term -> weight object, got {}", kind(v))),
+        },
+    }
+}
+
+fn coerce_int(v: &Value) -> Result<Value, String> {
+    let f = match v {
+        Value::Number(n) if n.is_i64() => return Ok(v.clone()),
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => match s.trim().parse::<i64>() {
+            Ok(i) => return Ok(Value::from(i)),
+            Err(_) => s.trim().parse::<f64>().ok(),
+        },
+        _ => None,
+    };
+    match f {
+        // 2^63 itself is out of range, hence `<`.
+        Some(f) if f.fract() == 0.0 && f >= i64::MIN as f64 && f < i64::MAX as f64 => Ok(Value::from(f as i64)),
+        _ => Err(format!("expected an int64, got {v}")),
+    }
+}
+
+fn coerce_float(v: &Value) -> Result<Value, String> {
+    let f = match v {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        _ => None,
+    };
+    match f.filter(|f| f.is_finite()) {
+        Some(_) if v.is_number() => Ok(v.clone()),
+        Some(f) => Number::from_f64(f).map(Value::Number).ok_or_else(|| format!("expected a float32, got {v}")),
+        None => Err(format!("expected a finite float32, got {v}")),
+    }
+}
+
+/// RFC 3339 strings are kept as given. Dates become midnight UTC and
+/// integers are read as Unix seconds.
+fn coerce_datetime(v: &Value) -> Result<Value, String> {
+    let err = || format!("expected an RFC 3339 datetime, got {v}");
+    match v {
+        Value::String(s) if parse_datetime(s).is_some() => Ok(v.clone()),
+        Value::String(s) => {
+            let date = NaiveDate::parse_from_str(s, "%Y-%m-%d").map_err(|_| err())?;
+            let t = date.and_hms_opt(0, 0, 0).ok_or_else(err)?.and_utc();
+            Ok(Value::String(t.to_rfc3339_opts(SecondsFormat::Secs, true)))
+        }
+        Value::Number(n) => {
+            let t = n.as_i64().and_then(|secs| DateTime::from_timestamp(secs, 0)).ok_or_else(err)?;
+            Ok(Value::String(t.to_rfc3339_opts(SecondsFormat::Secs, true)))
+        }
+        _ => Err(err()),
+    }
+}
+
+/// A list field takes an array of its element type; a lone scalar is
+/// wrapped.
+fn coerce_list(v: &Value, element: &FieldType) -> Result<Value, String> {
+    match v {
+        Value::Array(items) => items.iter().enumerate()
+            .map(|(i, x)| coerce(element, x).map_err(|e| format!("element {i}: {e}")))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array),
+        Value::Object(_) => Err(format!("expected a list, got {}", kind(v))),
+        scalar => Ok(Value::Array(vec![coerce(element, scalar)?])),
+    }
+}
+
+fn kind(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "a bool",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
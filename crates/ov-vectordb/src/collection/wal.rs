@@ -0,0 +1,151 @@
+//! Append-only write-ahead log for collection mutations.
+//!
+//! Each entry is framed as `len(u32) | crc32(u32) | json payload`. Replay
+//! stops at the first short or corrupt frame, which is where a crash cut
+//! the last append, and the file is truncated back to the last good entry.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::{Result, VectorDbError};
+use super::Record;
+
+/// File name of the log inside the collection directory.
+pub(crate) const WAL_FILE: &str = "wal.log";
+
+const FRAME_HEADER: usize = 8;
+
+/// When the write-ahead log is fsync'd.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// fsync after every mutation. Survives power loss.
+    #[default]
+    Always,
+    /// fsync once every `n` mutations; up to `n - 1` may be lost on power loss.
+    EveryN(usize),
+    /// Never fsync explicitly. Survives a process crash, not an OS crash.
+    Os,
+}
+
+/// A logged mutation.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub(crate) enum WalEntry {
+    Upsert { records: Vec<Record> },
+    Delete { primary_keys: Vec<Value> },
+    DeleteAll,
+}
+
+pub(crate) struct Wal {
+    file: File,
+    durability: Durability,
+    unsynced: usize,
+    /// A failed append could not be cut off the end of the file.
+    poisoned: bool,
+}
+
+impl Wal {
+    /// Open the log at `path`, returning it with every intact entry.
+    pub fn open(path: &Path, durability: Durability) -> Result<(Self, Vec<WalEntry>)> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+
+        let mut entries = Vec::new();
+        let mut offset = 0usize;
+        while let Some((entry, len)) = decode_frame(&buf[offset..]) {
+            entries.push(entry);
+            offset += len;
+        }
+        if offset < buf.len() {
+            tracing::warn!(
+                "discarding {} trailing bytes of torn write-ahead log {}",
+                buf.len() - offset,
+                path.display()
+            );
+            file.set_len(offset as u64)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::End(0))?;
+
+        let wal = Self { file, durability, unsynced: 0, poisoned: false };
+        Ok((wal, entries))
+    }
+
+    /// Append an entry, syncing according to the durability policy.
+    pub fn append(&mut self, entry: &WalEntry) -> Result<()> {
+        if self.poisoned {
+            return Err(VectorDbError::Storage("write-ahead log is unusable after a failed append".into()));
+        }
+        let payload = serde_json::to_vec(entry)
+            .map_err(|e| VectorDbError::Serialization(e.to_string()))?;
+        let mut frame = Vec::with_capacity(FRAME_HEADER + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        frame.extend_from_slice(&payload);
+        let start = self.file.stream_position()?;
+        if let Err(e) = self.write_frame(&frame) {
+            // Cut off the torn or unsynced frame: replay stops at the first
+            // bad frame, so entries appended after it would be lost. If the
+            // cut fails too, refuse further appends.
+            if self.file.set_len(start).and_then(|_| self.file.seek(SeekFrom::Start(start))).is_err() {
+                self.poisoned = true;
+            }
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn write_frame(&mut self, frame: &[u8]) -> Result<()> {
+        self.file.write_all(frame)?;
+        self.unsynced += 1;
+        let sync = match self.durability {
+            Durability::Always => true,
+            Durability::EveryN(n) => self.unsynced >= n.max(1),
+            Durability::Os => false,
+        };
+        if sync {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Flush pending entries to stable storage.
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.sync_data()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+
+    /// Discard all entries once they are covered by a checkpoint.
+    pub fn truncate(&mut self) -> Result<()> {
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.sync_all()?;
+        self.unsynced = 0;
+        Ok(())
+    }
+}
+
+fn decode_frame(buf: &[u8]) -> Option<(WalEntry, usize)> {
+    if buf.len() < FRAME_HEADER {
+        return None;
+    }
+    let len = u32::from_le_bytes(buf[0..4].try_into().ok()?) as usize;
+    let crc = u32::from_le_bytes(buf[4..8].try_into().ok()?);
+    let payload = buf.get(FRAME_HEADER..FRAME_HEADER + len)?;
+    if crc32fast::hash(payload) != crc {
+        return None;
+    }
+    let entry = serde_json::from_slice(payload).ok()?;
+    Some((entry, FRAME_HEADER + len))
+}
@@ -0,0 +1,213 @@
+//! Context-level index API on top of the label-based [`VectorIndex`] trait.
+//!
+//! [`ContextIndex`] speaks in terms of [`Context`] ids and URIs; the
+//! [`ContextIndexAdapter`] maps those to `u64` labels so any real index
+//! (`FlatIndex`, `HnswIndex`, ...) can serve it. Index work runs on the
+//! blocking thread pool so callers on an async runtime are never stalled.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ov_core::context::Context;
+use ov_core::types::EmbedResult;
+use parking_lot::RwLock;
+
+use crate::error::{Result, VectorDbError};
+use crate::index::VectorIndex;
+
+/// Async, Context-level vector index.
+#[async_trait]
+pub trait ContextIndex: Send + Sync {
+    /// Index a context's embedding, returning the context id.
+    async fn insert(&self, ctx: &Context, embedding: &EmbedResult) -> Result<String>;
+
+    /// Search for the top-k nearest contexts, returning `(context id, score)`.
+    async fn search(&self, vector: &[f32], top_k: usize) -> Result<Vec<(String, f32)>>;
+
+    /// Remove a context by id or URI. Unknown keys are ignored.
+    async fn delete(&self, id_or_uri: &str) -> Result<()>;
+
+    /// Verify that the index can hold vectors of `dimension` for collection `name`.
+    async fn ensure_collection(&self, name: &str, dimension: usize) -> Result<()>;
+}
+
+#[derive(Default)]
+struct LabelMap {
+    id_to_label: HashMap<String, u64>,
+    label_to_id: HashMap<u64, String>,
+    id_to_uri: HashMap<String, String>,
+    uri_to_id: HashMap<String, String>,
+    next_label: u64,
+}
+
+impl LabelMap {
+    fn resolve(&self, id_or_uri: &str) -> Option<String> {
+        if self.id_to_label.contains_key(id_or_uri) {
+            return Some(id_or_uri.to_string());
+        }
+        self.uri_to_id.get(id_or_uri).cloned()
+    }
+
+    fn remove(&mut self, id: &str) -> Option<u64> {
+        let label = self.id_to_label.remove(id)?;
+        self.label_to_id.remove(&label);
+        if let Some(uri) = self.id_to_uri.remove(id) {
+            self.uri_to_id.remove(&uri);
+        }
+        Some(label)
+    }
+}
+
+/// Adapter exposing any label-based [`VectorIndex`] as a [`ContextIndex`].
+///
+/// Each context id gets a stable label for the adapter's lifetime. Writing
+/// a new context at an existing URI replaces the previous one.
+#[derive(Clone)]
+pub struct ContextIndexAdapter {
+    index: Arc<dyn VectorIndex>,
+    labels: Arc<RwLock<LabelMap>>,
+}
+
+impl ContextIndexAdapter {
+    pub fn new(index: Arc<dyn VectorIndex>) -> Self {
+        Self {
+            index,
+            labels: Arc::new(RwLock::new(LabelMap::default())),
+        }
+    }
+
+    /// The wrapped label-based index.
+    pub fn index(&self) -> &Arc<dyn VectorIndex> {
+        &self.index
+    }
+
+    /// Label assigned to a context id or URI, if indexed.
+    pub fn label_of(&self, id_or_uri: &str) -> Option<u64> {
+        let labels = self.labels.read();
+        let id = labels.resolve(id_or_uri)?;
+        labels.id_to_label.get(&id).copied()
+    }
+
+    /// URI recorded for a context id.
+    pub fn uri_of(&self, id: &str) -> Option<String> {
+        self.labels.read().id_to_uri.get(id).cloned()
+    }
+
+    /// Number of indexed contexts.
+    pub fn len(&self) -> usize {
+        self.labels.read().id_to_label.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[async_trait]
+impl ContextIndex for ContextIndexAdapter {
+    async fn insert(&self, ctx: &Context, embedding: &EmbedResult) -> Result<String> {
+        let vector = embedding
+            .dense_vector
+            .clone()
+            .or_else(|| ctx.vector.clone())
+            .ok_or_else(|| VectorDbError::InvalidConfig(format!("no dense vector for {}", ctx.uri)))?;
+        if vector.len() != self.index.dimension() {
+            return Err(VectorDbError::DimensionMismatch {
+                expected: self.index.dimension(),
+                got: vector.len(),
+            });
+        }
+
+        let id = ctx.id.to_string();
+        let label = {
+            let mut labels = self.labels.write();
+            match labels.id_to_label.get(&id) {
+                Some(&label) => label,
+                None => {
+                    let label = labels.next_label;
+                    labels.next_label += 1;
+                    label
+                }
+            }
+        };
+
+        // The maps only change once the vector is in, so a failed insert
+        // leaves the previous context at this URI in place.
+        let index = self.index.clone();
+        run_blocking(move || index.insert(label, &vector)).await?;
+
+        let stale = {
+            let mut labels = self.labels.write();
+            let mut stale = Vec::new();
+            if let Some(old_id) = labels.uri_to_id.get(&ctx.uri).filter(|&old_id| *old_id != id).cloned() {
+                stale.extend(labels.remove(&old_id));
+            }
+            // A concurrent insert of the same new id may have mapped it first.
+            if let Some(previous) = labels.id_to_label.insert(id.clone(), label).filter(|&previous| previous != label) {
+                labels.label_to_id.remove(&previous);
+                stale.push(previous);
+            }
+            labels.label_to_id.insert(label, id.clone());
+            if let Some(old_uri) = labels.id_to_uri.insert(id.clone(), ctx.uri.clone()) {
+                labels.uri_to_id.remove(&old_uri);
+            }
+            labels.uri_to_id.insert(ctx.uri.clone(), id.clone());
+            stale
+        };
+
+        if !stale.is_empty() {
+            let index = self.index.clone();
+            run_blocking(move || stale.into_iter().try_for_each(|label| index.delete(label))).await?;
+        }
+        Ok(id)
+    }
+
+    async fn search(&self, vector: &[f32], top_k: usize) -> Result<Vec<(String, f32)>> {
+        let index = self.index.clone();
+        let query = vector.to_vec();
+        let result = run_blocking(move || index.search(&query, top_k)).await?;
+        let labels = self.labels.read();
+        Ok(result
+            .ids
+            .iter()
+            .zip(result.scores.iter())
+            .filter_map(|(label, &score)| labels.label_to_id.get(label).map(|id| (id.clone(), score)))
+            .collect())
+    }
+
+    async fn delete(&self, id_or_uri: &str) -> Result<()> {
+        let label = {
+            let mut labels = self.labels.write();
+            match labels.resolve(id_or_uri) {
+                Some(id) => labels.remove(&id),
+                None => None,
+            }
+        };
+        if let Some(label) = label {
+            let index = self.index.clone();
+            run_blocking(move || index.delete(label)).await?;
+        }
+        Ok(())
+    }
+
+    async fn ensure_collection(&self, name: &str, dimension: usize) -> Result<()> {
+        if dimension != self.index.dimension() {
+            return Err(VectorDbError::InvalidConfig(format!(
+                "collection {name} expects dimension {dimension}, index has {}",
+                self.index.dimension()
+            )));
+        }
+        Ok(())
+    }
+}
+
+async fn run_blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| VectorDbError::Other(anyhow::anyhow!("index task failed: {e}")))?
+}
@@ -2,6 +2,10 @@
 
 use std::fmt;
 
+mod simd;
+
+pub use simd::{scalar, SimdLevel};
+
 /// Supported distance metrics.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -45,25 +49,22 @@ impl DistanceMetric {
 #[inline]
 pub fn inner_product(a: &[f32], b: &[f32]) -> f32 {
     debug_assert_eq!(a.len(), b.len());
-    a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+    (simd::kernels().dot)(a, b)
 }
 
 /// Compute L2 squared distance.
 #[inline]
 pub fn l2_squared(a: &[f32], b: &[f32]) -> f32 {
     debug_assert_eq!(a.len(), b.len());
-    a.iter().zip(b.iter()).map(|(x, y)| {
-        let d = x - y;
-        d * d
-    }).sum()
+    (simd::kernels().l2)(a, b)
 }
 
 /// Compute cosine similarity (returns value in [-1, 1]).
 #[inline]
 pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     let dot = inner_product(a, b);
-    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_a = inner_product(a, a).sqrt();
+    let norm_b = inner_product(b, b).sqrt();
     if norm_a == 0.0 || norm_b == 0.0 {
         return 0.0;
     }
@@ -72,7 +73,7 @@ pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
 
 /// Normalize a vector in-place (L2 normalization).
 pub fn normalize_vector(v: &mut [f32]) {
-    let norm: f32 = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm = inner_product(v, v).sqrt();
     if norm > 0.0 {
         for x in v.iter_mut() {
             *x /= norm;
@@ -93,3 +94,30 @@ pub fn compute_score(metric: DistanceMetric, a: &[f32], b: &[f32]) -> f32 {
         DistanceMetric::Cosine => cosine_similarity(a, b),
     }
 }
+
+/// [`compute_score`] for vectors that are already unit length when the
+/// metric is Cosine, as the indexes store them, so Cosine is a plain dot
+/// product.
+#[inline]
+pub fn compute_score_normalized(metric: DistanceMetric, a: &[f32], b: &[f32]) -> f32 {
+    match metric {
+        DistanceMetric::L2 => 1.0 / (1.0 + l2_squared(a, b)),
+        DistanceMetric::Ip | DistanceMetric::Cosine => inner_product(a, b),
+    }
+}
+
+/// [`compute_score_normalized`] of `query` against each of `rows`,
+/// written to `out`. Several rows share each pass over the query.
+pub fn compute_scores_normalized(metric: DistanceMetric, query: &[f32], rows: &[&[f32]], out: &mut [f32]) {
+    debug_assert_eq!(rows.len(), out.len());
+    let kernels = simd::kernels();
+    match metric {
+        DistanceMetric::L2 => {
+            (kernels.l2_batch)(query, rows, out);
+            for score in out.iter_mut() {
+                *score = 1.0 / (1.0 + *score);
+            }
+        }
+        DistanceMetric::Ip | DistanceMetric::Cosine => (kernels.dot_batch)(query, rows, out),
+    }
+}
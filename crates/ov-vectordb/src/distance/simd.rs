@@ -0,0 +1,332 @@
+//! Explicit SIMD distance kernels, picked once per process from the
+//! instruction sets the CPU reports. The scalar kernels are the reference
+//! every other level must agree with (up to summation order).
+
+use std::sync::OnceLock;
+
+/// Instruction set the distance kernels run on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SimdLevel {
+    /// Plain Rust loops; available everywhere.
+    Scalar,
+    /// 128-bit SSE (x86_64).
+    Sse,
+    /// 256-bit AVX2 with FMA (x86_64).
+    Avx2,
+    /// 512-bit AVX-512F (x86_64).
+    Avx512,
+}
+
+impl SimdLevel {
+    /// The widest level this CPU supports.
+    pub fn detect() -> Self {
+        Self::available().into_iter().max().unwrap_or(Self::Scalar)
+    }
+
+    /// Every level this CPU supports, narrowest first.
+    pub fn available() -> Vec<Self> {
+        [Self::Scalar, Self::Sse, Self::Avx2, Self::Avx512]
+            .into_iter()
+            .filter(|level| level.is_supported())
+            .collect()
+    }
+
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse => is_x86_feature_detected!("sse"),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma"),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => is_x86_feature_detected!("avx512f"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+
+    /// Inner product on this level, or on the scalar kernels if the CPU
+    /// lacks it.
+    pub fn inner_product(self, a: &[f32], b: &[f32]) -> f32 {
+        (self.kernels().dot)(a, b)
+    }
+
+    /// L2 squared distance on this level, or on the scalar kernels if the
+    /// CPU lacks it.
+    pub fn l2_squared(self, a: &[f32], b: &[f32]) -> f32 {
+        (self.kernels().l2)(a, b)
+    }
+
+    /// Inner product of `query` with each of `rows` into `out`.
+    pub fn inner_product_batch(self, query: &[f32], rows: &[&[f32]], out: &mut [f32]) {
+        (self.kernels().dot_batch)(query, rows, out)
+    }
+
+    /// L2 squared distance of `query` to each of `rows` into `out`.
+    pub fn l2_squared_batch(self, query: &[f32], rows: &[&[f32]], out: &mut [f32]) {
+        (self.kernels().l2_batch)(query, rows, out)
+    }
+
+    pub(crate) fn kernels(self) -> Kernels {
+        if !self.is_supported() {
+            return SCALAR;
+        }
+        match self {
+            Self::Scalar => SCALAR,
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse => x86::SSE,
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => x86::AVX2,
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => x86::AVX512,
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => SCALAR,
+        }
+    }
+}
+
+type PairFn = fn(&[f32], &[f32]) -> f32;
+type BatchFn = fn(&[f32], &[&[f32]], &mut [f32]);
+
+/// Kernel table for one [`SimdLevel`].
+#[derive(Clone, Copy)]
+pub(crate) struct Kernels {
+    pub dot: PairFn,
+    pub l2: PairFn,
+    pub dot_batch: BatchFn,
+    pub l2_batch: BatchFn,
+}
+
+/// Kernels for [`SimdLevel::detect`], resolved on first use.
+pub(crate) fn kernels() -> &'static Kernels {
+    static KERNELS: OnceLock<Kernels> = OnceLock::new();
+    KERNELS.get_or_init(|| SimdLevel::detect().kernels())
+}
+
+const SCALAR: Kernels = Kernels {
+    dot: scalar::inner_product,
+    l2: scalar::l2_squared,
+    dot_batch: |q, rows, out| batch_by_pair(scalar::inner_product, q, rows, out),
+    l2_batch: |q, rows, out| batch_by_pair(scalar::l2_squared, q, rows, out),
+};
+
+fn batch_by_pair(pair: PairFn, query: &[f32], rows: &[&[f32]], out: &mut [f32]) {
+    for (o, row) in out.iter_mut().zip(rows) {
+        *o = pair(query, row);
+    }
+}
+
+/// Reference kernels.
+pub mod scalar {
+    pub fn inner_product(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b.iter()).map(|(x, y)| x * y).sum()
+    }
+
+    pub fn l2_squared(a: &[f32], b: &[f32]) -> f32 {
+        a.iter().zip(b.iter()).map(|(x, y)| {
+            let d = x - y;
+            d * d
+        }).sum()
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    //! The `unsafe` kernels are only reachable through the tables below,
+    //! which [`SimdLevel::kernels`](super::SimdLevel::kernels) hands out
+    //! after checking the CPU supports them.
+
+    use std::arch::x86_64::*;
+    use super::{batch_by_pair, scalar, Kernels};
+
+    pub const SSE: Kernels = Kernels {
+        dot: |a, b| unsafe { dot_sse(a, b) },
+        l2: |a, b| unsafe { l2_sse(a, b) },
+        dot_batch: |q, rows, out| batch_by_pair(|a, b| unsafe { dot_sse(a, b) }, q, rows, out),
+        l2_batch: |q, rows, out| batch_by_pair(|a, b| unsafe { l2_sse(a, b) }, q, rows, out),
+    };
+
+    pub const AVX2: Kernels = Kernels {
+        dot: |a, b| unsafe { dot_avx2(a, b) },
+        l2: |a, b| unsafe { l2_avx2(a, b) },
+        dot_batch: |q, rows, out| unsafe { batch_avx2::<false>(q, rows, out) },
+        l2_batch: |q, rows, out| unsafe { batch_avx2::<true>(q, rows, out) },
+    };
+
+    pub const AVX512: Kernels = Kernels {
+        dot: |a, b| unsafe { dot_avx512(a, b) },
+        l2: |a, b| unsafe { l2_avx512(a, b) },
+        dot_batch: |q, rows, out| unsafe { batch_avx512::<false>(q, rows, out) },
+        l2_batch: |q, rows, out| unsafe { batch_avx512::<true>(q, rows, out) },
+    };
+
+    /// Rows scored together by the batch kernels, sharing each query load.
+    const BATCH_ROWS: usize = 4;
+
+    #[target_feature(enable = "sse")]
+    unsafe fn hsum128(v: __m128) -> f32 {
+        let sums = _mm_add_ps(v, _mm_movehl_ps(v, v));
+        _mm_cvtss_f32(_mm_add_ss(sums, _mm_shuffle_ps(sums, sums, 1)))
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn hsum256(v: __m256) -> f32 {
+        hsum128(_mm_add_ps(_mm256_castps256_ps128(v), _mm256_extractf128_ps(v, 1)))
+    }
+
+    #[target_feature(enable = "sse")]
+    unsafe fn dot_sse(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len().min(b.len());
+        let mut acc = _mm_setzero_ps();
+        let mut i = 0;
+        while i + 4 <= n {
+            acc = _mm_add_ps(acc, _mm_mul_ps(_mm_loadu_ps(a.as_ptr().add(i)), _mm_loadu_ps(b.as_ptr().add(i))));
+            i += 4;
+        }
+        hsum128(acc) + scalar::inner_product(&a[i..n], &b[i..n])
+    }
+
+    #[target_feature(enable = "sse")]
+    unsafe fn l2_sse(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len().min(b.len());
+        let mut acc = _mm_setzero_ps();
+        let mut i = 0;
+        while i + 4 <= n {
+            let d = _mm_sub_ps(_mm_loadu_ps(a.as_ptr().add(i)), _mm_loadu_ps(b.as_ptr().add(i)));
+            acc = _mm_add_ps(acc, _mm_mul_ps(d, d));
+            i += 4;
+        }
+        hsum128(acc) + scalar::l2_squared(&a[i..n], &b[i..n])
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn dot_avx2(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len().min(b.len());
+        let mut acc = _mm256_setzero_ps();
+        let mut i = 0;
+        while i + 8 <= n {
+            acc = _mm256_fmadd_ps(_mm256_loadu_ps(a.as_ptr().add(i)), _mm256_loadu_ps(b.as_ptr().add(i)), acc);
+            i += 8;
+        }
+        hsum256(acc) + scalar::inner_product(&a[i..n], &b[i..n])
+    }
+
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn l2_avx2(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len().min(b.len());
+        let mut acc = _mm256_setzero_ps();
+        let mut i = 0;
+        while i + 8 <= n {
+            let d = _mm256_sub_ps(_mm256_loadu_ps(a.as_ptr().add(i)), _mm256_loadu_ps(b.as_ptr().add(i)));
+            acc = _mm256_fmadd_ps(d, d, acc);
+            i += 8;
+        }
+        hsum256(acc) + scalar::l2_squared(&a[i..n], &b[i..n])
+    }
+
+    /// Scores [`BATCH_ROWS`] rows per pass over the query; `L2` selects
+    /// squared distance over inner product.
+    #[target_feature(enable = "avx2,fma")]
+    unsafe fn batch_avx2<const L2: bool>(query: &[f32], rows: &[&[f32]], out: &mut [f32]) {
+        let n = query.len();
+        let mut r = 0;
+        while r + BATCH_ROWS <= rows.len().min(out.len()) {
+            let group = &rows[r..r + BATCH_ROWS];
+            if group.iter().any(|row| row.len() != n) {
+                for (o, row) in out[r..r + BATCH_ROWS].iter_mut().zip(group) {
+                    *o = if L2 { l2_avx2(query, row) } else { dot_avx2(query, row) };
+                }
+                r += BATCH_ROWS;
+                continue;
+            }
+            let mut acc = [_mm256_setzero_ps(); BATCH_ROWS];
+            let mut i = 0;
+            while i + 8 <= n {
+                let q = _mm256_loadu_ps(query.as_ptr().add(i));
+                for (acc, row) in acc.iter_mut().zip(group) {
+                    let x = _mm256_loadu_ps(row.as_ptr().add(i));
+                    *acc = if L2 {
+                        let d = _mm256_sub_ps(q, x);
+                        _mm256_fmadd_ps(d, d, *acc)
+                    } else {
+                        _mm256_fmadd_ps(q, x, *acc)
+                    };
+                }
+                i += 8;
+            }
+            for ((o, acc), row) in out[r..r + BATCH_ROWS].iter_mut().zip(acc).zip(group) {
+                let tail = if L2 { scalar::l2_squared(&query[i..], &row[i..]) } else { scalar::inner_product(&query[i..], &row[i..]) };
+                *o = hsum256(acc) + tail;
+            }
+            r += BATCH_ROWS;
+        }
+        for (o, row) in out[r..].iter_mut().zip(&rows[r..]) {
+            *o = if L2 { l2_avx2(query, row) } else { dot_avx2(query, row) };
+        }
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn dot_avx512(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len().min(b.len());
+        let mut acc = _mm512_setzero_ps();
+        let mut i = 0;
+        while i + 16 <= n {
+            acc = _mm512_fmadd_ps(_mm512_loadu_ps(a.as_ptr().add(i)), _mm512_loadu_ps(b.as_ptr().add(i)), acc);
+            i += 16;
+        }
+        _mm512_reduce_add_ps(acc) + scalar::inner_product(&a[i..n], &b[i..n])
+    }
+
+    #[target_feature(enable = "avx512f")]
+    unsafe fn l2_avx512(a: &[f32], b: &[f32]) -> f32 {
+        let n = a.len().min(b.len());
+        let mut acc = _mm512_setzero_ps();
+        let mut i = 0;
+        while i + 16 <= n {
+            let d = _mm512_sub_ps(_mm512_loadu_ps(a.as_ptr().add(i)), _mm512_loadu_ps(b.as_ptr().add(i)));
+            acc = _mm512_fmadd_ps(d, d, acc);
+            i += 16;
+        }
+        _mm512_reduce_add_ps(acc) + scalar::l2_squared(&a[i..n], &b[i..n])
+    }
+
+    /// AVX-512 counterpart of [`batch_avx2`].
+    #[target_feature(enable = "avx512f")]
+    unsafe fn batch_avx512<const L2: bool>(query: &[f32], rows: &[&[f32]], out: &mut [f32]) {
+        let n = query.len();
+        let mut r = 0;
+        while r + BATCH_ROWS <= rows.len().min(out.len()) {
+            let group = &rows[r..r + BATCH_ROWS];
+            if group.iter().any(|row| row.len() != n) {
+                for (o, row) in out[r..r + BATCH_ROWS].iter_mut().zip(group) {
+                    *o = if L2 { l2_avx512(query, row) } else { dot_avx512(query, row) };
+                }
+                r += BATCH_ROWS;
+                continue;
+            }
+            let mut acc = [_mm512_setzero_ps(); BATCH_ROWS];
+            let mut i = 0;
+            while i + 16 <= n {
+                let q = _mm512_loadu_ps(query.as_ptr().add(i));
+                for (acc, row) in acc.iter_mut().zip(group) {
+                    let x = _mm512_loadu_ps(row.as_ptr().add(i));
+                    *acc = if L2 {
+                        let d = _mm512_sub_ps(q, x);
+                        _mm512_fmadd_ps(d, d, *acc)
+                    } else {
+                        _mm512_fmadd_ps(q, x, *acc)
+                    };
+                }
+                i += 16;
+            }
+            for ((o, acc), row) in out[r..r + BATCH_ROWS].iter_mut().zip(acc).zip(group) {
+                let tail = if L2 { scalar::l2_squared(&query[i..], &row[i..]) } else { scalar::inner_product(&query[i..], &row[i..]) };
+                *o = _mm512_reduce_add_ps(acc) + tail;
+            }
+            r += BATCH_ROWS;
+        }
+        for (o, row) in out[r..].iter_mut().zip(&rows[r..]) {
+            *o = if L2 { l2_avx512(query, row) } else { dot_avx512(query, row) };
+        }
+    }
+}
@@ -10,10 +10,16 @@ pub enum VectorDbError {
     IndexNotFound(String),
     #[error("Index already exists: {0}")]
     IndexAlreadyExists(String),
+    #[error("Index not ready: {0}")]
+    IndexNotReady(String),
     #[error("Dimension mismatch: expected {expected}, got {got}")]
     DimensionMismatch { expected: usize, got: usize },
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
+    #[error("Invalid filter at {path}: {message}")]
+    InvalidFilter { path: String, message: String },
+    #[error("Invalid records: {}", crate::collection::format_issues(.0))]
+    InvalidRecords(Vec<crate::collection::SchemaIssue>),
     #[error("Storage error: {0}")]
     Storage(String),
     #[error("Serialization error: {0}")]
@@ -1,9 +1,13 @@
 //! Filter operations for scalar field filtering.
 //!
-//! Supports: must, must_not, range, range_out, prefix, contains, regex, and/or logic.
+//! Supports: must, must_not, range, range_out, prefix, contains, regex,
+//! exists, not_exists, subtree, geo_range, and/or logic.
 
+use regex::Regex;
 use serde_json::Value;
 use std::collections::HashMap;
+use crate::collection::{CollectionConfig, FieldType, SchemaMode};
+use crate::error::{Result, VectorDbError};
 
 /// A filter condition tree.
 #[derive(Debug, Clone)]
@@ -14,72 +18,40 @@ pub enum Filter {
     RangeOut { field: String, gte: Option<Value>, lte: Option<Value> },
     Prefix { field: String, prefix: String },
     Contains { field: String, substring: String },
-    Regex { field: String, pattern: String },
+    /// Pattern compiled when the filter is parsed.
+    Regex { field: String, regex: Regex },
+    /// Field is present and not null.
+    Exists { field: String },
+    NotExists { field: String },
+    /// Path equals `path` or lies below it; unlike `Prefix`, `/a/b` does
+    /// not match `/a/bc`.
+    Subtree { field: String, path: String },
+    /// GeoPoint within `radius` meters of (`lon`, `lat`).
+    GeoRange { field: String, lon: f64, lat: f64, radius: f64 },
     And(Vec<Filter>),
     Or(Vec<Filter>),
 }
 
 impl Filter {
     /// Parse a filter from a JSON value (matching the Python DSL).
-    pub fn from_json(v: &Value) -> Option<Self> {
-        let obj = v.as_object()?;
-        let op = obj.get("op")?.as_str()?;
-        match op {
-            "must" => {
-                let field = obj.get("field")?.as_str()?.to_string();
-                let conds = obj.get("conds")?.as_array()?.clone();
-                Some(Filter::Must { field, values: conds })
-            }
-            "must_not" => {
-                let field = obj.get("field")?.as_str()?.to_string();
-                let conds = obj.get("conds")?.as_array()?.clone();
-                Some(Filter::MustNot { field, values: conds })
-            }
-            "range" => {
-                let field = obj.get("field")?.as_str()?.to_string();
-                Some(Filter::Range {
-                    field,
-                    gt: obj.get("gt").cloned(),
-                    gte: obj.get("gte").cloned(),
-                    lt: obj.get("lt").cloned(),
-                    lte: obj.get("lte").cloned(),
-                })
-            }
-            "range_out" => {
-                let field = obj.get("field")?.as_str()?.to_string();
-                Some(Filter::RangeOut {
-                    field,
-                    gte: obj.get("gte").cloned(),
-                    lte: obj.get("lte").cloned(),
-                })
-            }
-            "prefix" => {
-                let field = obj.get("field")?.as_str()?.to_string();
-                let prefix = obj.get("prefix")?.as_str()?.to_string();
-                Some(Filter::Prefix { field, prefix })
-            }
-            "contains" => {
-                let field = obj.get("field")?.as_str()?.to_string();
-                let substring = obj.get("substring")?.as_str()?.to_string();
-                Some(Filter::Contains { field, substring })
-            }
-            "regex" => {
-                let field = obj.get("field")?.as_str()?.to_string();
-                let pattern = obj.get("pattern")?.as_str()?.to_string();
-                Some(Filter::Regex { field, pattern })
-            }
-            "and" => {
-                let conds = obj.get("conds")?.as_array()?;
-                let filters: Vec<Filter> = conds.iter().filter_map(Filter::from_json).collect();
-                Some(Filter::And(filters))
-            }
-            "or" => {
-                let conds = obj.get("conds")?.as_array()?;
-                let filters: Vec<Filter> = conds.iter().filter_map(Filter::from_json).collect();
-                Some(Filter::Or(filters))
-            }
-            _ => None,
-        }
+    ///
+    /// Malformed conditions are errors rather than being dropped; the error
+    /// names the offending JSON path, e.g. `$.conds[1].op`.
+    pub fn from_json(v: &Value) -> Result<Self> {
+        Parser { schema: None, mode: SchemaMode::Lenient }.filter(v, "$")
+    }
+
+    /// Parse a filter, also checking that each field exists in `config`
+    /// and that each operand fits the field's type.
+    pub fn from_json_with_schema(v: &Value, config: &CollectionConfig) -> Result<Self> {
+        Self::from_json_with_schema_mode(v, config, SchemaMode::Strict)
+    }
+
+    /// Like [`Filter::from_json_with_schema`], but under
+    /// [`SchemaMode::Lenient`] fields outside the schema are accepted
+    /// unchecked, since lenient upserts keep them.
+    pub fn from_json_with_schema_mode(v: &Value, config: &CollectionConfig, mode: SchemaMode) -> Result<Self> {
+        Parser { schema: Some(config), mode }.filter(v, "$")
     }
 
     /// Evaluate the filter against a set of field values.
@@ -106,9 +78,10 @@ impl Filter {
                     true
                 }
             }
+            // List fields match when any element does.
             Filter::Range { field, gt, gte, lt, lte } => {
                 if let Some(field_val) = fields.get(field) {
-                    range_check(field_val, gt.as_ref(), gte.as_ref(), lt.as_ref(), lte.as_ref())
+                    elements(field_val).any(|v| range_check(v, gt.as_ref(), gte.as_ref(), lt.as_ref(), lte.as_ref()))
                 } else {
                     false
                 }
@@ -116,17 +89,19 @@ impl Filter {
             Filter::RangeOut { field, gte, lte } => {
                 // NOT in [gte, lte] means < gte OR > lte
                 if let Some(field_val) = fields.get(field) {
-                    let below = if let Some(g) = gte {
-                        compare_values(field_val, g) == Some(std::cmp::Ordering::Less)
-                    } else {
-                        false
-                    };
-                    let above = if let Some(l) = lte {
-                        compare_values(field_val, l) == Some(std::cmp::Ordering::Greater)
-                    } else {
-                        false
-                    };
-                    below || above
+                    elements(field_val).any(|v| {
+                        let below = if let Some(g) = gte {
+                            compare_values(v, g) == Some(std::cmp::Ordering::Less)
+                        } else {
+                            false
+                        };
+                        let above = if let Some(l) = lte {
+                            compare_values(v, l) == Some(std::cmp::Ordering::Greater)
+                        } else {
+                            false
+                        };
+                        below || above
+                    })
                 } else {
                     false
                 }
@@ -153,22 +128,214 @@ impl Filter {
                     false
                 }
             }
-            Filter::Regex { field, pattern } => {
-                if let Some(field_val) = fields.get(field) {
-                    if let Some(s) = field_val.as_str() {
-                        // Simple regex matching - we avoid pulling in the regex crate
-                        // by doing basic pattern matching for common cases
-                        simple_regex_match(s, pattern)
-                    } else {
-                        false
+            Filter::Regex { field, regex } => {
+                fields.get(field).and_then(Value::as_str).is_some_and(|s| regex.is_match(s))
+            }
+            Filter::Exists { field } => fields.get(field).is_some_and(|v| !v.is_null()),
+            Filter::NotExists { field } => fields.get(field).is_none_or(Value::is_null),
+            Filter::Subtree { field, path } => {
+                fields.get(field).and_then(Value::as_str).is_some_and(|s| in_subtree(s, path))
+            }
+            Filter::GeoRange { field, lon, lat, radius } => {
+                fields.get(field).and_then(parse_geo_point).is_some_and(|(plon, plat)| {
+                    haversine_meters(*lon, *lat, plon, plat) <= *radius
+                })
+            }
+            Filter::And(filters) => filters.iter().all(|f| f.matches(fields)),
+            Filter::Or(filters) => filters.iter().any(|f| f.matches(fields)),
+        }
+    }
+}
+
+fn invalid(path: impl Into<String>, message: impl Into<String>) -> VectorDbError {
+    VectorDbError::InvalidFilter { path: path.into(), message: message.into() }
+}
+
+/// Recursive-descent filter parser. Each method takes the JSON path of the
+/// value it parses, for error messages.
+struct Parser<'a> {
+    schema: Option<&'a CollectionConfig>,
+    /// Whether fields outside the schema are errors.
+    mode: SchemaMode,
+}
+
+impl Parser<'_> {
+    fn filter(&self, v: &Value, path: &str) -> Result<Filter> {
+        let obj = v.as_object().ok_or_else(|| invalid(path, "expected a filter object"))?;
+        let op = string(obj, path, "op")?;
+        match op {
+            "must" | "must_not" => {
+                let (field, ty) = self.field(obj, path)?;
+                let values = array(obj, path, "conds")?;
+                if values.is_empty() {
+                    return Err(invalid(format!("{path}.conds"), "expected at least one value"));
+                }
+                for (i, v) in values.iter().enumerate() {
+                    check_equal_operand(ty, v, &format!("{path}.conds[{i}]"))?;
+                }
+                let values = values.clone();
+                Ok(if op == "must" { Filter::Must { field, values } } else { Filter::MustNot { field, values } })
+            }
+            "range" | "range_out" => {
+                let (field, ty) = self.field(obj, path)?;
+                let keys: &[&str] = if op == "range" { &["gt", "gte", "lt", "lte"] } else { &["gte", "lte"] };
+                let mut bounds = Vec::with_capacity(keys.len());
+                for key in keys {
+                    let bound = obj.get(*key).filter(|v| !v.is_null());
+                    if let Some(b) = bound {
+                        check_range_operand(ty, b, &format!("{path}.{key}"))?;
                     }
+                    bounds.push(bound.cloned());
+                }
+                if bounds.iter().all(Option::is_none) {
+                    return Err(invalid(path, format!("{op} needs at least one of {}", keys.join(", "))));
+                }
+                let mut bounds = bounds.into_iter();
+                let mut next = || bounds.next().flatten();
+                Ok(if op == "range" {
+                    Filter::Range { field, gt: next(), gte: next(), lt: next(), lte: next() }
                 } else {
-                    false
+                    Filter::RangeOut { field, gte: next(), lte: next() }
+                })
+            }
+            "prefix" | "contains" | "regex" => {
+                let (field, ty) = self.field(obj, path)?;
+                if let Some(ty) = ty.filter(|t| !is_string_type(t)) {
+                    return Err(invalid(format!("{path}.field"), format!("{op} needs a string field, {field:?} is {}", ty.as_str())));
                 }
+                let key = match op {
+                    "prefix" => "prefix",
+                    "contains" => "substring",
+                    _ => "pattern",
+                };
+                let operand = string(obj, path, key)?.to_string();
+                Ok(match op {
+                    "prefix" => Filter::Prefix { field, prefix: operand },
+                    "contains" => Filter::Contains { field, substring: operand },
+                    _ => {
+                        let regex = Regex::new(&operand)
+                            .map_err(|e| invalid(format!("{path}.pattern"), format!("invalid regex: {e}")))?;
+                        Filter::Regex { field, regex }
+                    }
+                })
             }
-            Filter::And(filters) => filters.iter().all(|f| f.matches(fields)),
-            Filter::Or(filters) => filters.iter().any(|f| f.matches(fields)),
+            "exists" => Ok(Filter::Exists { field: self.field(obj, path)?.0 }),
+            "not_exists" => Ok(Filter::NotExists { field: self.field(obj, path)?.0 }),
+            "subtree" => {
+                let (field, ty) = self.field(obj, path)?;
+                if let Some(ty) = ty.filter(|t| !matches!(t, FieldType::Path | FieldType::String)) {
+                    return Err(invalid(format!("{path}.field"), format!("subtree needs a path field, {field:?} is {}", ty.as_str())));
+                }
+                let root = string(obj, path, "path")?.to_string();
+                Ok(Filter::Subtree { field, path: root })
+            }
+            "geo_range" => {
+                let (field, ty) = self.field(obj, path)?;
+                if let Some(ty) = ty.filter(|t| **t != FieldType::GeoPoint) {
+                    return Err(invalid(format!("{path}.field"), format!("geo_range needs a geo_point field, {field:?} is {}", ty.as_str())));
+                }
+                let center = array(obj, path, "center")?;
+                let (lon, lat) = match center.as_slice() {
+                    [lon, lat] => (lon.as_f64(), lat.as_f64()),
+                    _ => (None, None),
+                };
+                let (Some(lon), Some(lat)) = (lon, lat) else {
+                    return Err(invalid(format!("{path}.center"), "expected [longitude, latitude]"));
+                };
+                if !(-180.0..=180.0).contains(&lon) || !(-90.0..=90.0).contains(&lat) {
+                    return Err(invalid(format!("{path}.center"), "coordinates out of range"));
+                }
+                let radius = obj.get("radius").and_then(Value::as_f64).filter(|r| *r >= 0.0)
+                    .ok_or_else(|| invalid(format!("{path}.radius"), "expected a non-negative number of meters"))?;
+                Ok(Filter::GeoRange { field, lon, lat, radius })
+            }
+            "and" | "or" => {
+                let filters = array(obj, path, "conds")?.iter().enumerate()
+                    .map(|(i, c)| self.filter(c, &format!("{path}.conds[{i}]")))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(if op == "and" { Filter::And(filters) } else { Filter::Or(filters) })
+            }
+            _ => Err(invalid(format!("{path}.op"), format!("unknown op {op:?}"))),
+        }
+    }
+
+    /// The `field` name and, with a schema, its type; `None` for fields a
+    /// lenient schema does not list.
+    fn field<'s>(&'s self, obj: &serde_json::Map<String, Value>, path: &str) -> Result<(String, Option<&'s FieldType>)> {
+        let name = string(obj, path, "field")?;
+        let Some(config) = self.schema else {
+            return Ok((name.to_string(), None));
+        };
+        let Some(def) = config.fields.iter().find(|f| f.name == name) else {
+            return match self.mode {
+                SchemaMode::Strict => Err(invalid(format!("{path}.field"), format!("unknown field {name:?}"))),
+                SchemaMode::Lenient => Ok((name.to_string(), None)),
+            };
+        };
+        if matches!(def.field_type, FieldType::Vector | FieldType::SparseVector) {
+            return Err(invalid(format!("{path}.field"), format!("{} field {name:?} cannot be filtered", def.field_type.as_str())));
+        }
+        Ok((name.to_string(), Some(&def.field_type)))
+    }
+}
+
+fn string<'v>(obj: &'v serde_json::Map<String, Value>, path: &str, key: &str) -> Result<&'v str> {
+    match obj.get(key) {
+        Some(Value::String(s)) => Ok(s),
+        Some(_) => Err(invalid(format!("{path}.{key}"), "expected a string")),
+        None => Err(invalid(path, format!("missing {key:?}"))),
+    }
+}
+
+fn array<'v>(obj: &'v serde_json::Map<String, Value>, path: &str, key: &str) -> Result<&'v Vec<Value>> {
+    match obj.get(key) {
+        Some(Value::Array(a)) => Ok(a),
+        Some(_) => Err(invalid(format!("{path}.{key}"), "expected an array")),
+        None => Err(invalid(path, format!("missing {key:?}"))),
+    }
+}
+
+fn is_string_type(ty: &FieldType) -> bool {
+    matches!(ty, FieldType::String | FieldType::Path | FieldType::DateTime)
+}
+
+/// A `must`/`must_not` value: a scalar, of the field's element type when
+/// the schema is known.
+fn check_equal_operand(ty: Option<&FieldType>, v: &Value, path: &str) -> Result<()> {
+    let ok = match ty {
+        None => v.is_string() || v.is_number() || v.is_boolean(),
+        Some(FieldType::Int64 | FieldType::ListInt64) => v.is_i64() || v.is_u64(),
+        Some(FieldType::Float32 | FieldType::ListFloat32) => v.is_number(),
+        Some(FieldType::Bool) => v.is_boolean(),
+        Some(t) if is_string_type(t) || *t == FieldType::ListString => v.is_string(),
+        Some(t) => return Err(invalid(path, format!("{} fields do not support equality", t.as_str()))),
+    };
+    if ok {
+        return Ok(());
+    }
+    Err(invalid(path, match ty {
+        None => "expected a string, number or bool".to_string(),
+        Some(t) => format!("expected a value of type {}, got {v}", t.as_str()),
+    }))
+}
+
+/// A range bound: a number or string, matching the field's ordering when
+/// the schema is known.
+fn check_range_operand(ty: Option<&FieldType>, v: &Value, path: &str) -> Result<()> {
+    match ty {
+        None if v.is_number() || v.is_string() => Ok(()),
+        None => Err(invalid(path, "expected a number or string")),
+        Some(FieldType::Int64 | FieldType::Float32 | FieldType::ListInt64 | FieldType::ListFloat32) => {
+            if v.is_number() { Ok(()) } else { Err(invalid(path, format!("expected a number, got {v}"))) }
         }
+        Some(FieldType::DateTime) => match v.as_str().and_then(parse_datetime) {
+            Some(_) => Ok(()),
+            None => Err(invalid(path, format!("expected an RFC 3339 datetime, got {v}"))),
+        },
+        Some(FieldType::String | FieldType::Path | FieldType::ListString) => {
+            if v.is_string() { Ok(()) } else { Err(invalid(path, format!("expected a string, got {v}"))) }
+        }
+        Some(t) => Err(invalid(path, format!("{} fields do not support ranges", t.as_str()))),
     }
 }
 
@@ -195,11 +362,42 @@ fn compare_values(a: &Value, b: &Value) -> Option<std::cmp::Ordering> {
             let fb = nb.as_f64()?;
             fa.partial_cmp(&fb)
         }
+        (Value::String(sa), Value::String(sb)) if looks_like_datetime(sa) && looks_like_datetime(sb) => {
+            match (parse_datetime(sa), parse_datetime(sb)) {
+                // RFC 3339 timestamps compare by instant, whatever their offsets.
+                (Some(ta), Some(tb)) => Some(ta.cmp(&tb)),
+                _ => Some(sa.cmp(sb)),
+            }
+        }
         (Value::String(sa), Value::String(sb)) => Some(sa.cmp(sb)),
         _ => None,
     }
 }
 
+/// Whether `s` starts like an RFC 3339 timestamp (`YYYY-MM-DDT...`), so
+/// ordinary strings compare without attempting a full parse.
+fn looks_like_datetime(s: &str) -> bool {
+    let b = s.as_bytes();
+    b.len() >= 20
+        && b[..4].iter().all(u8::is_ascii_digit)
+        && b[4] == b'-'
+        && b[7] == b'-'
+        && matches!(b[10], b'T' | b't' | b' ')
+}
+
+/// Microseconds since the Unix epoch of an RFC 3339 timestamp.
+pub(crate) fn parse_datetime(s: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(s).ok().map(|t| t.timestamp_micros())
+}
+
+/// The elements of a list value, or the value itself.
+fn elements(v: &Value) -> std::slice::Iter<'_, Value> {
+    match v {
+        Value::Array(items) => items.iter(),
+        _ => std::slice::from_ref(v).iter(),
+    }
+}
+
 fn range_check(val: &Value, gt: Option<&Value>, gte: Option<&Value>, lt: Option<&Value>, lte: Option<&Value>) -> bool {
     if let Some(g) = gt {
         if compare_values(val, g) != Some(std::cmp::Ordering::Greater) {
@@ -228,36 +426,41 @@ fn range_check(val: &Value, gt: Option<&Value>, gte: Option<&Value>, lt: Option<
     true
 }
 
-/// Simple regex-like matching for common patterns.
-/// Supports: ^prefix, suffix$, .*substring.*, literal
-fn simple_regex_match(s: &str, pattern: &str) -> bool {
-    // Handle ^...$ anchored patterns
-    if pattern.starts_with('^') && pattern.ends_with('$') {
-        let inner = &pattern[1..pattern.len()-1];
-        // Handle alternation (a|b)
-        if inner.contains('|') {
-            return inner.split('|').any(|alt| {
-                let alt = alt.trim_matches('(').trim_matches(')');
-                s == alt
-            });
-        }
-        return s == inner;
+/// Whether `path` is `root` or a descendant of it. Trailing slashes are
+/// ignored on both sides.
+fn in_subtree(path: &str, root: &str) -> bool {
+    let root = root.trim_end_matches('/');
+    let path = path.trim_end_matches('/');
+    match path.strip_prefix(root) {
+        Some(rest) => rest.is_empty() || rest.starts_with('/'),
+        None => false,
     }
-    // Handle ^prefix
-    if pattern.starts_with('^') {
-        let prefix = &pattern[1..];
-        // Handle alternation like ^(a|d)
-        if prefix.starts_with('(') && prefix.ends_with(')') {
-            let inner = &prefix[1..prefix.len()-1];
-            return inner.split('|').any(|alt| s.starts_with(alt));
+}
+
+/// `(lon, lat)` of a GeoPoint value: a `"lon,lat"` string, a `[lon, lat]`
+/// array or a `{"lon": .., "lat": ..}` object.
+pub(crate) fn parse_geo_point(value: &Value) -> Option<(f64, f64)> {
+    let (lon, lat) = match value {
+        Value::String(s) => {
+            let (lon, lat) = s.split_once(',')?;
+            (lon.trim().parse().ok()?, lat.trim().parse().ok()?)
         }
-        return s.starts_with(prefix);
-    }
-    // Handle suffix$
-    if pattern.ends_with('$') {
-        let suffix = &pattern[..pattern.len()-1];
-        return s.ends_with(suffix);
-    }
-    // Fallback: contains
-    s.contains(pattern)
+        Value::Array(a) => match a.as_slice() {
+            [lon, lat] => (lon.as_f64()?, lat.as_f64()?),
+            _ => return None,
+        },
+        Value::Object(o) => (o.get("lon")?.as_f64()?, o.get("lat")?.as_f64()?),
+        _ => return None,
+    };
+    ((-180.0..=180.0).contains(&lon) && (-90.0..=90.0).contains(&lat)).then_some((lon, lat))
+}
+
+/// Great-circle distance in meters between two (lon, lat) points.
+fn haversine_meters(lon1: f64, lat1: f64, lon2: f64, lat2: f64) -> f64 {
+    const EARTH_RADIUS_M: f64 = 6_371_008.8;
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let dphi = (lat2 - lat1).to_radians();
+    let dlambda = (lon2 - lon1).to_radians();
+    let h = (dphi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (dlambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().min(1.0).asin()
 }
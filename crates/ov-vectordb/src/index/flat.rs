@@ -1,21 +1,26 @@
 use std::collections::HashMap;
 use std::path::Path;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use crate::distance::{self, DistanceMetric};
 use crate::error::{Result, VectorDbError};
+use super::quantize::{self, Code, CodeRef, Quantization, Quantizer};
 use super::{SearchResult, traits::VectorIndex};
 
 /// Brute-force (flat) vector index.
-/// Exact nearest-neighbor search by scanning all vectors.
+/// Exact nearest-neighbor search by scanning all vectors, or approximate
+/// when the vectors are quantized.
 pub struct FlatIndex {
     dimension: usize,
     metric: DistanceMetric,
     inner: RwLock<FlatInner>,
+    /// Held while PQ codebooks train, so concurrent inserts train once.
+    training: Mutex<()>,
 }
 
 struct FlatInner {
     labels: Vec<u64>,
-    vectors: Vec<Vec<f32>>,
+    vectors: Vec<Code>,
+    quantizer: Quantizer,
     label_to_idx: HashMap<u64, usize>,
     /// Tracks deleted slots for compaction.
     deleted_count: usize,
@@ -29,9 +34,11 @@ impl FlatIndex {
             inner: RwLock::new(FlatInner {
                 labels: Vec::new(),
                 vectors: Vec::new(),
+                quantizer: Quantizer::new(Quantization::None, dimension),
                 label_to_idx: HashMap::new(),
                 deleted_count: 0,
             }),
+            training: Mutex::new(()),
         }
     }
 
@@ -43,11 +50,93 @@ impl FlatIndex {
             inner: RwLock::new(FlatInner {
                 labels: Vec::with_capacity(capacity),
                 vectors: Vec::with_capacity(capacity),
+                quantizer: Quantizer::new(Quantization::None, dimension),
                 label_to_idx: HashMap::with_capacity(capacity),
                 deleted_count: 0,
             }),
+            training: Mutex::new(()),
         }
     }
+
+    /// Store vectors with the given encoding. Set before inserting.
+    pub fn with_quantization(self, quantization: Quantization) -> Self {
+        self.inner.write().quantizer = Quantizer::new(quantization, self.dimension);
+        self
+    }
+
+    pub fn quantization(&self) -> Quantization {
+        self.inner.read().quantizer.kind()
+    }
+
+    /// Bytes used by the stored vectors, including PQ codebooks.
+    pub fn vector_bytes(&self) -> usize {
+        let inner = self.inner.read();
+        inner.quantizer.size(inner.vectors.iter().map(Code::view))
+    }
+
+    /// Score every vector whose label passes `allow` and keep the top-k.
+    fn scan(&self, query: &[f32], top_k: usize, allow: &dyn Fn(u64) -> bool) -> Result<SearchResult> {
+        if query.len() != self.dimension {
+            return Err(VectorDbError::DimensionMismatch {
+                expected: self.dimension,
+                got: query.len(),
+            });
+        }
+        let inner = self.inner.read();
+        if inner.labels.is_empty() || top_k == 0 {
+            return Ok(SearchResult::empty());
+        }
+
+        let query_vec = if self.metric == DistanceMetric::Cosine {
+            let mut q = query.to_vec();
+            distance::normalize_vector(&mut q);
+            q
+        } else {
+            query.to_vec()
+        };
+
+        let effective_metric = if self.metric == DistanceMetric::Cosine {
+            DistanceMetric::Ip // normalized vectors: cosine = IP
+        } else {
+            self.metric
+        };
+
+        // Compute all scores
+        let scorer = inner.quantizer.scorer(effective_metric, &query_vec);
+        let (labels, codes): (Vec<u64>, Vec<CodeRef<'_>>) = inner.labels.iter().zip(inner.vectors.iter())
+            .filter(|(&label, _)| allow(label))
+            .map(|(&label, code)| (label, code.view()))
+            .unzip();
+        let mut scores = vec![0.0; codes.len()];
+        scorer.score_many(&codes, &mut scores);
+        let mut scored: Vec<(u64, f32)> = labels.into_iter().zip(scores).collect();
+
+        // Sort by score descending
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored.truncate(top_k);
+
+        Ok(SearchResult {
+            ids: scored.iter().map(|s| s.0).collect(),
+            scores: scored.iter().map(|s| s.1).collect(),
+        })
+    }
+
+    /// Train the PQ codebooks on a copy of the held vectors, then swap
+    /// them in and encode everything under a short write lock.
+    fn train_quantizer(&self) {
+        let Some(_training) = self.training.try_lock() else { return };
+        let (quantizer, samples) = {
+            let inner = self.inner.read();
+            if !inner.quantizer.wants_training(inner.vectors.len()) {
+                return;
+            }
+            (inner.quantizer.clone(), quantize::full_vectors(inner.vectors.iter().map(Code::view)))
+        };
+        let codebooks = quantizer.train(&samples);
+        let mut inner = self.inner.write();
+        let FlatInner { quantizer, vectors, .. } = &mut *inner;
+        quantizer.install(codebooks, vectors);
+    }
 }
 
 impl VectorIndex for FlatIndex {
@@ -64,15 +153,21 @@ impl VectorIndex for FlatIndex {
         if self.metric == DistanceMetric::Cosine {
             distance::normalize_vector(&mut vec);
         }
+        let code = inner.quantizer.encode(&vec);
         if let Some(&idx) = inner.label_to_idx.get(&label) {
             // Update existing
-            inner.vectors[idx] = vec;
+            inner.vectors[idx] = code;
         } else {
             let idx = inner.labels.len();
             inner.labels.push(label);
-            inner.vectors.push(vec);
+            inner.vectors.push(code);
             inner.label_to_idx.insert(label, idx);
         }
+        let train = inner.quantizer.wants_training(inner.vectors.len());
+        drop(inner);
+        if train {
+            self.train_quantizer();
+        }
         Ok(())
     }
 
@@ -94,47 +189,11 @@ impl VectorIndex for FlatIndex {
     }
 
     fn search(&self, query: &[f32], top_k: usize) -> Result<SearchResult> {
-        if query.len() != self.dimension {
-            return Err(VectorDbError::DimensionMismatch {
-                expected: self.dimension,
-                got: query.len(),
-            });
-        }
-        let inner = self.inner.read();
-        if inner.labels.is_empty() || top_k == 0 {
-            return Ok(SearchResult::empty());
-        }
-
-        let query_vec = if self.metric == DistanceMetric::Cosine {
-            let mut q = query.to_vec();
-            distance::normalize_vector(&mut q);
-            q
-        } else {
-            query.to_vec()
-        };
-
-        let effective_metric = if self.metric == DistanceMetric::Cosine {
-            DistanceMetric::Ip // normalized vectors: cosine = IP
-        } else {
-            self.metric
-        };
-
-        // Compute all scores
-        let mut scored: Vec<(u64, f32)> = inner.labels.iter().zip(inner.vectors.iter())
-            .map(|(&label, vec)| {
-                let score = distance::compute_score(effective_metric, &query_vec, vec);
-                (label, score)
-            })
-            .collect();
-
-        // Sort by score descending
-        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
-        scored.truncate(top_k);
+        self.scan(query, top_k, &|_| true)
+    }
 
-        Ok(SearchResult {
-            ids: scored.iter().map(|s| s.0).collect(),
-            scores: scored.iter().map(|s| s.1).collect(),
-        })
+    fn search_filtered(&self, query: &[f32], top_k: usize, allow: &dyn Fn(u64) -> bool) -> Result<SearchResult> {
+        self.scan(query, top_k, allow)
     }
 
     fn len(&self) -> usize {
@@ -154,6 +213,12 @@ impl VectorIndex for FlatIndex {
         let inner = self.inner.read();
         let dir = path;
         std::fs::create_dir_all(dir)?;
+        if inner.quantizer.kind() != Quantization::None {
+            let data = QuantizedSer { quantizer: &inner.quantizer, labels: &inner.labels, codes: &inner.vectors };
+            let json = serde_json::to_vec(&data).map_err(|e| VectorDbError::Serialization(e.to_string()))?;
+            std::fs::write(dir.join(QUANTIZED_FILE), json)?;
+            return Ok(());
+        }
         let file_path = dir.join("flat_index.bin");
         let mut f = std::fs::File::create(&file_path)?;
 
@@ -164,7 +229,7 @@ impl VectorIndex for FlatIndex {
         f.write_all(&count.to_le_bytes())?;
         for i in 0..inner.labels.len() {
             f.write_all(&inner.labels[i].to_le_bytes())?;
-            for &val in &inner.vectors[i] {
+            for &val in inner.quantizer.decode(inner.vectors[i].view()).iter() {
                 f.write_all(&val.to_le_bytes())?;
             }
         }
@@ -181,6 +246,20 @@ impl VectorIndex for FlatIndex {
 
     fn load(&mut self, path: &Path) -> Result<()> {
         use std::io::Read;
+        if self.quantization() != Quantization::None {
+            let data = std::fs::read(path.join(QUANTIZED_FILE))?;
+            let de: QuantizedDe = serde_json::from_slice(&data)
+                .map_err(|e| VectorDbError::Serialization(e.to_string()))?;
+            if de.labels.len() != de.codes.len() {
+                return Err(VectorDbError::Serialization("flat index labels and codes differ in length".into()));
+            }
+            let mut inner = self.inner.write();
+            inner.label_to_idx = de.labels.iter().enumerate().map(|(i, &l)| (l, i)).collect();
+            inner.labels = de.labels;
+            inner.vectors = de.codes;
+            inner.quantizer = de.quantizer;
+            return Ok(());
+        }
         let file_path = path.join("flat_index.bin");
         let mut f = std::fs::File::open(&file_path)?;
         let mut buf4 = [0u8; 4];
@@ -206,8 +285,9 @@ impl VectorIndex for FlatIndex {
             }
             inner.label_to_idx.insert(label, i);
             inner.labels.push(label);
-            inner.vectors.push(vec);
+            inner.vectors.push(Code::Full(vec));
         }
+        inner.quantizer = Quantizer::new(Quantization::None, dim);
         self.dimension = dim;
         Ok(())
     }
@@ -216,3 +296,21 @@ impl VectorIndex for FlatIndex {
         false
     }
 }
+
+/// File of a quantized flat index, which stores codes instead of the
+/// `flat_index.bin` vectors.
+const QUANTIZED_FILE: &str = "flat_index_quantized.json";
+
+#[derive(serde::Serialize)]
+struct QuantizedSer<'a> {
+    quantizer: &'a Quantizer,
+    labels: &'a [u64],
+    codes: &'a [Code],
+}
+
+#[derive(serde::Deserialize)]
+struct QuantizedDe {
+    quantizer: Quantizer,
+    labels: Vec<u64>,
+    codes: Vec<Code>,
+}
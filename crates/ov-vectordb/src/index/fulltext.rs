@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::path::Path;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use crate::error::{Result, VectorDbError};
+use super::SearchResult;
+
+/// Split text into lowercase search terms.
+///
+/// Runs of letters and digits form one term. CJK text has no word
+/// separators, so each Han, kana or Hangul character is a term and so is
+/// each pair of adjacent ones; a query for a two-character word then
+/// matches its bigram rather than two unrelated characters.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut terms = Vec::new();
+    let mut word = String::new();
+    let mut prev_cjk: Option<char> = None;
+    for c in text.chars() {
+        if is_cjk(c) {
+            flush_word(&mut word, &mut terms);
+            terms.push(c.to_string());
+            if let Some(p) = prev_cjk {
+                terms.push([p, c].iter().collect());
+            }
+            prev_cjk = Some(c);
+        } else {
+            prev_cjk = None;
+            if c.is_alphanumeric() || c == '_' {
+                word.extend(c.to_lowercase());
+            } else {
+                flush_word(&mut word, &mut terms);
+            }
+        }
+    }
+    flush_word(&mut word, &mut terms);
+    terms
+}
+
+fn flush_word(word: &mut String, terms: &mut Vec<String>) {
+    if !word.is_empty() {
+        terms.push(std::mem::take(word));
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30ff}'     // Hiragana, Katakana
+        | '\u{3400}'..='\u{4dbf}'   // CJK Extension A
+        | '\u{4e00}'..='\u{9fff}'   // CJK Unified Ideographs
+        | '\u{ac00}'..='\u{d7af}'   // Hangul syllables
+        | '\u{f900}'..='\u{faff}'   // CJK Compatibility Ideographs
+    )
+}
+
+/// BM25 full-text index.
+///
+/// Parameters:
+/// - `k1`: Term-frequency saturation (default 1.2)
+/// - `b`: Document-length normalization (default 0.75)
+pub struct FullTextIndex {
+    k1: f32,
+    b: f32,
+    inner: RwLock<FullTextInner>,
+}
+
+#[derive(Default)]
+struct FullTextInner {
+    /// term -> label -> term frequency.
+    postings: HashMap<String, HashMap<u64, u32>>,
+    docs: HashMap<u64, Doc>,
+    total_len: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Doc {
+    len: u32,
+    terms: HashMap<String, u32>,
+}
+
+impl FullTextInner {
+    fn unlink(&mut self, label: u64) {
+        let Some(doc) = self.docs.remove(&label) else { return };
+        self.total_len -= doc.len as u64;
+        for term in doc.terms.keys() {
+            if let Some(list) = self.postings.get_mut(term) {
+                list.remove(&label);
+                if list.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+    }
+
+    fn link(&mut self, label: u64, doc: Doc) {
+        for (term, &tf) in &doc.terms {
+            self.postings.entry(term.clone()).or_default().insert(label, tf);
+        }
+        self.total_len += doc.len as u64;
+        self.docs.insert(label, doc);
+    }
+}
+
+impl FullTextIndex {
+    pub fn new() -> Self {
+        Self::with_params(1.2, 0.75)
+    }
+
+    pub fn with_params(k1: f32, b: f32) -> Self {
+        Self { k1, b, inner: RwLock::new(FullTextInner::default()) }
+    }
+
+    /// Index `text` under `label`, replacing any previous text. Text
+    /// without terms leaves the label unindexed.
+    pub fn insert(&self, label: u64, text: &str) -> Result<()> {
+        let tokens = tokenize(text);
+        let mut inner = self.inner.write();
+        inner.unlink(label);
+        if tokens.is_empty() {
+            return Ok(());
+        }
+        let mut terms: HashMap<String, u32> = HashMap::new();
+        for t in &tokens {
+            *terms.entry(t.clone()).or_insert(0) += 1;
+        }
+        inner.link(label, Doc { len: tokens.len() as u32, terms });
+        Ok(())
+    }
+
+    pub fn delete(&self, label: u64) -> Result<()> {
+        self.inner.write().unlink(label);
+        Ok(())
+    }
+
+    /// Top-k labels by BM25 score for `query`. Only labels containing at
+    /// least one query term are returned.
+    pub fn search(&self, query: &str, top_k: usize) -> Result<SearchResult> {
+        if top_k == 0 {
+            return Ok(SearchResult::empty());
+        }
+        let inner = self.inner.read();
+        let n = inner.docs.len() as f32;
+        if n == 0.0 {
+            return Ok(SearchResult::empty());
+        }
+        let avg_len = inner.total_len as f32 / n;
+
+        let mut query_terms = tokenize(query);
+        query_terms.sort();
+        query_terms.dedup();
+
+        let mut acc: HashMap<u64, f32> = HashMap::new();
+        for term in &query_terms {
+            let Some(list) = inner.postings.get(term) else { continue };
+            let df = list.len() as f32;
+            let idf = ((n - df + 0.5) / (df + 0.5) + 1.0).ln();
+            for (&label, &tf) in list {
+                let len = inner.docs[&label].len as f32;
+                let tf = tf as f32;
+                let norm = tf * (self.k1 + 1.0) / (tf + self.k1 * (1.0 - self.b + self.b * len / avg_len));
+                *acc.entry(label).or_insert(0.0) += idf * norm;
+            }
+        }
+
+        let mut scored: Vec<(u64, f32)> = acc.into_iter().collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+        scored.truncate(top_k);
+
+        Ok(SearchResult {
+            ids: scored.iter().map(|s| s.0).collect(),
+            scores: scored.iter().map(|s| s.1).collect(),
+        })
+    }
+
+    /// Number of indexed documents.
+    pub fn len(&self) -> usize {
+        self.inner.read().docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::create_dir_all(path)?;
+        let inner = self.inner.read();
+        let json = serde_json::to_vec(&inner.docs).map_err(|e| VectorDbError::Serialization(e.to_string()))?;
+        std::fs::write(path.join("fulltext_index.json"), json)?;
+        Ok(())
+    }
+
+    /// Load documents saved by [`FullTextIndex::save`], rebuilding the postings.
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        let data = std::fs::read(path.join("fulltext_index.json"))?;
+        let docs: HashMap<u64, Doc> = serde_json::from_slice(&data)
+            .map_err(|e| VectorDbError::Serialization(e.to_string()))?;
+        let mut inner = FullTextInner::default();
+        for (label, doc) in docs {
+            inner.link(label, doc);
+        }
+        *self.inner.write() = inner;
+        Ok(())
+    }
+}
+
+impl Default for FullTextIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
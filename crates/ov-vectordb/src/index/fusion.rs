@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use super::SearchResult;
+
+/// How ranked result lists from different indexes are merged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fusion {
+    /// Min-max normalize each list's scores to `[0, 1]`, then add them up
+    /// scaled by the list weights. Normalizing keeps an unbounded sparse
+    /// score from drowning out a cosine score.
+    WeightedSum,
+    /// Reciprocal rank fusion: each list adds `weight / (k + rank)`, with
+    /// ranks starting at 1. Raw scores are ignored.
+    Rrf { k: f32 },
+}
+
+impl Default for Fusion {
+    fn default() -> Self {
+        Self::Rrf { k: 60.0 }
+    }
+}
+
+/// Merge `(results, weight)` lists into one list of at most `top_k` labels.
+pub fn fuse(lists: &[(&SearchResult, f32)], fusion: Fusion, top_k: usize) -> SearchResult {
+    let mut acc: HashMap<u64, f32> = HashMap::new();
+    for &(list, weight) in lists {
+        match fusion {
+            Fusion::WeightedSum => {
+                let (min, max) = list.scores.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &s| {
+                    (lo.min(s), hi.max(s))
+                });
+                let range = max - min;
+                for (&label, &score) in list.ids.iter().zip(list.scores.iter()) {
+                    // A single hit (or all-equal scores) counts as a full match.
+                    let norm = if range > 0.0 { (score - min) / range } else { 1.0 };
+                    *acc.entry(label).or_insert(0.0) += weight * norm;
+                }
+            }
+            Fusion::Rrf { k } => {
+                for (rank, &label) in list.ids.iter().enumerate() {
+                    *acc.entry(label).or_insert(0.0) += weight / (k + rank as f32 + 1.0);
+                }
+            }
+        }
+    }
+
+    let mut scored: Vec<(u64, f32)> = acc.into_iter().collect();
+    scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+    scored.truncate(top_k);
+
+    SearchResult {
+        ids: scored.iter().map(|s| s.0).collect(),
+        scores: scored.iter().map(|s| s.1).collect(),
+    }
+}
@@ -0,0 +1,518 @@
+//! Binary on-disk layout of an [`HnswIndex`](super::HnswIndex).
+//!
+//! Integers are little-endian. A fixed header is followed by the graph
+//! section and then a contiguous vector block:
+//!
+//! ```text
+//! header   magic "OVHNSW\0\0" | version u32 | metric u8 | code kind u8 | 2 pad
+//!          | dimension, m, ef_construction, ef_search, nodes, entry point
+//!            (u64::MAX for none), max level, graph length: u64 each
+//!          | graph crc32 u32 | vector crc32 u32
+//! graph    quantizer: kind u8 | pq m u64 | codebook count u64, then per
+//!            codebook its length u64 and f32 values
+//!          labels u64 × nodes | levels u32 × nodes
+//!          deleted count u64 | ids u32 × count
+//!          layer count u64, then per layer: slot count u64 | offsets u64 ×
+//!            (slots + 1) | neighbour ids u32 × last offset
+//! padding  zeros up to a multiple of VECTOR_ALIGN
+//! vectors  nodes × stride bytes: f32 × dimension, or min f32 | scale f32 |
+//!          u8 × dimension for int8, or u8 × m for PQ
+//! ```
+//!
+//! The graph checksum is verified on every load. The vector checksum is
+//! only verified by eager loads; a mapped load would otherwise have to
+//! read the whole block it is trying to avoid reading.
+
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use memmap2::Mmap;
+use crate::distance::DistanceMetric;
+use crate::error::{Result, VectorDbError};
+use crate::index::quantize::{Code, CodeRef, Quantization, Quantizer};
+
+/// File holding the index inside its directory.
+pub(super) const INDEX_FILE: &str = "hnsw_index.bin";
+
+const MAGIC: &[u8; 8] = b"OVHNSW\0\0";
+const VERSION: u32 = 1;
+const HEADER_LEN: usize = 8 + 4 + 4 + 8 * 8 + 4 + 4;
+/// Alignment of the vector block within the file.
+const VECTOR_ALIGN: usize = 64;
+
+/// Layout of each stored vector, uniform across the index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CodeKind {
+    Full,
+    Int8,
+    Pq,
+}
+
+impl CodeKind {
+    fn of(quantizer: &Quantizer) -> Self {
+        match quantizer.kind() {
+            Quantization::None => Self::Full,
+            Quantization::Int8 => Self::Int8,
+            // Untrained PQ indexes still hold full-precision vectors.
+            Quantization::Pq { .. } if quantizer.codebooks().is_empty() => Self::Full,
+            Quantization::Pq { .. } => Self::Pq,
+        }
+    }
+
+    fn stride(self, dim: usize, quantization: Quantization) -> usize {
+        match (self, quantization) {
+            (Self::Full, _) => dim * 4,
+            (Self::Int8, _) => 8 + dim,
+            (Self::Pq, Quantization::Pq { m }) => m,
+            (Self::Pq, _) => 0,
+        }
+    }
+
+    fn tag(self) -> u8 {
+        match self {
+            Self::Full => 0,
+            Self::Int8 => 1,
+            Self::Pq => 2,
+        }
+    }
+
+    fn from_tag(tag: u8) -> Result<Self> {
+        match tag {
+            0 => Ok(Self::Full),
+            1 => Ok(Self::Int8),
+            2 => Ok(Self::Pq),
+            _ => Err(corrupt(format!("unknown vector encoding {tag}"))),
+        }
+    }
+}
+
+/// Index contents as written to or read from disk.
+pub(super) struct HnswFile {
+    pub dimension: usize,
+    pub metric: DistanceMetric,
+    pub m: usize,
+    pub ef_construction: usize,
+    pub ef_search: usize,
+    pub entry_point: Option<usize>,
+    pub max_level: usize,
+    pub quantizer: Quantizer,
+    pub id_to_label: Vec<u64>,
+    pub node_levels: Vec<usize>,
+    pub deleted: Vec<usize>,
+    pub layers: Vec<Vec<Vec<usize>>>,
+    pub vectors: Vectors,
+}
+
+/// Vectors by internal id: owned codes, or the vector block of a mapped
+/// file, which is copied into memory on the first write.
+pub(super) enum Vectors {
+    Owned(Vec<Code>),
+    Mapped(MappedVectors),
+}
+
+impl Vectors {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Owned(codes) => codes.len(),
+            Self::Mapped(mapped) => mapped.count,
+        }
+    }
+
+    pub fn get(&self, id: usize) -> CodeRef<'_> {
+        match self {
+            Self::Owned(codes) => codes[id].view(),
+            Self::Mapped(mapped) => mapped.get(id),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = CodeRef<'_>> {
+        (0..self.len()).map(|id| self.get(id))
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        matches!(self, Self::Mapped(_))
+    }
+
+    /// The codes for writing, copying a mapped block out first.
+    pub fn owned_mut(&mut self) -> &mut Vec<Code> {
+        if let Self::Mapped(mapped) = self {
+            *self = Self::Owned((0..mapped.count).map(|id| mapped.get(id).to_code()).collect());
+        }
+        match self {
+            Self::Owned(codes) => codes,
+            Self::Mapped(_) => unreachable!(),
+        }
+    }
+}
+
+/// Read-only view of the vector block of a memory-mapped index file.
+pub(super) struct MappedVectors {
+    map: Mmap,
+    offset: usize,
+    stride: usize,
+    kind: CodeKind,
+    count: usize,
+}
+
+impl MappedVectors {
+    fn get(&self, id: usize) -> CodeRef<'_> {
+        let start = self.offset + id * self.stride;
+        decode_code(self.kind, &self.map[start..start + self.stride])
+    }
+}
+
+/// Borrowed parts of a live index to write. The links are copied out,
+/// as concurrent inserts may change them while the file is written.
+pub(super) struct HnswFileRef<'a> {
+    pub dimension: usize,
+    pub metric: DistanceMetric,
+    pub m: usize,
+    pub ef_construction: usize,
+    pub ef_search: usize,
+    pub entry_point: Option<usize>,
+    pub max_level: usize,
+    pub quantizer: &'a Quantizer,
+    pub id_to_label: &'a [u64],
+    pub node_levels: &'a [usize],
+    pub deleted: Vec<usize>,
+    pub layers: Vec<Vec<Vec<usize>>>,
+    pub vectors: Box<dyn Iterator<Item = CodeRef<'a>> + 'a>,
+}
+
+/// Write the index to `path` through a temporary file, so a mapped copy
+/// of the previous file stays valid.
+pub(super) fn write(path: &Path, index: HnswFileRef<'_>) -> Result<()> {
+    let nodes = index.id_to_label.len();
+    if nodes > u32::MAX as usize {
+        return Err(VectorDbError::Storage(format!("{nodes} nodes exceed the HNSW file format")));
+    }
+    let kind = CodeKind::of(index.quantizer);
+    let stride = kind.stride(index.dimension, index.quantizer.kind());
+    let graph = encode_graph(&index)?;
+
+    let tmp = path.with_extension("bin.tmp");
+    let mut f = BufWriter::new(File::create(&tmp)?);
+    f.write_all(&[0; HEADER_LEN])?;
+    f.write_all(&graph)?;
+    let padding = padding(HEADER_LEN + graph.len());
+    f.write_all(&vec![0; padding])?;
+    let mut vector_crc = crc32fast::Hasher::new();
+    let mut buf = Vec::with_capacity(stride);
+    for code in index.vectors {
+        buf.clear();
+        encode_code(kind, code, &mut buf)?;
+        if buf.len() != stride {
+            return Err(VectorDbError::Storage("HNSW vectors differ in encoding".into()));
+        }
+        vector_crc.update(&buf);
+        f.write_all(&buf)?;
+    }
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.write_u32::<LittleEndian>(VERSION)?;
+    header.write_u8(metric_tag(index.metric))?;
+    header.write_u8(kind.tag())?;
+    header.write_u16::<LittleEndian>(0)?;
+    for value in [
+        index.dimension as u64,
+        index.m as u64,
+        index.ef_construction as u64,
+        index.ef_search as u64,
+        nodes as u64,
+        index.entry_point.map_or(u64::MAX, |ep| ep as u64),
+        index.max_level as u64,
+        graph.len() as u64,
+    ] {
+        header.write_u64::<LittleEndian>(value)?;
+    }
+    header.write_u32::<LittleEndian>(crc32fast::hash(&graph))?;
+    header.write_u32::<LittleEndian>(vector_crc.finalize())?;
+
+    let mut f = f.into_inner().map_err(|e| e.into_error())?;
+    f.seek(SeekFrom::Start(0))?;
+    f.write_all(&header)?;
+    f.sync_all()?;
+    drop(f);
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Read the index at `path`, either into memory or, with `mapped`,
+/// leaving the vector block in a memory map of the file.
+pub(super) fn read(path: &Path, mapped: bool) -> Result<HnswFile> {
+    let file = File::open(path)?;
+    // Mapped f32 vectors are read in place, which needs a little-endian host.
+    if mapped && cfg!(target_endian = "little") {
+        // SAFETY: index files are only replaced by rename, never modified
+        // in place, so the mapped bytes do not change under the map.
+        let map = unsafe { Mmap::map(&file)? };
+        let (mut index, layout) = parse(&map)?;
+        if layout.kind == CodeKind::Full && !(map.as_ptr() as usize + layout.offset).is_multiple_of(4) {
+            return Err(corrupt("misaligned vector block".into()));
+        }
+        index.vectors = Vectors::Mapped(MappedVectors {
+            map,
+            offset: layout.offset,
+            stride: layout.stride,
+            kind: layout.kind,
+            count: layout.count,
+        });
+        return Ok(index);
+    }
+
+    let data = std::fs::read(path)?;
+    let (mut index, layout) = parse(&data)?;
+    let block = &data[layout.offset..layout.offset + layout.count * layout.stride];
+    if crc32fast::hash(block) != layout.crc {
+        return Err(corrupt("vector checksum mismatch".into()));
+    }
+    index.vectors = Vectors::Owned(match layout.stride {
+        0 => vec![Code::Full(Vec::new()); layout.count],
+        stride => block.chunks_exact(stride).map(|c| read_code(layout.kind, c)).collect(),
+    });
+    Ok(index)
+}
+
+/// Where the vector block sits in the file.
+struct VectorLayout {
+    offset: usize,
+    stride: usize,
+    kind: CodeKind,
+    count: usize,
+    crc: u32,
+}
+
+/// Parse the header and graph; the returned index has no vectors yet.
+fn parse(data: &[u8]) -> Result<(HnswFile, VectorLayout)> {
+    if data.len() < HEADER_LEN || &data[..8] != MAGIC {
+        return Err(corrupt("not an HNSW index file".into()));
+    }
+    let mut r = &data[8..HEADER_LEN];
+    let version = r.read_u32::<LittleEndian>()?;
+    if version != VERSION {
+        return Err(corrupt(format!("unsupported version {version}")));
+    }
+    let metric = metric_from_tag(r.read_u8()?)?;
+    let kind = CodeKind::from_tag(r.read_u8()?)?;
+    r.read_u16::<LittleEndian>()?;
+    let mut next = || -> Result<usize> { Ok(r.read_u64::<LittleEndian>()? as usize) };
+    let (dimension, m, ef_construction, ef_search) = (next()?, next()?, next()?, next()?);
+    let (nodes, entry_point, max_level, graph_len) = (next()?, next()?, next()?, next()?);
+    let graph_crc = r.read_u32::<LittleEndian>()?;
+    let vector_crc = r.read_u32::<LittleEndian>()?;
+
+    let graph = HEADER_LEN.checked_add(graph_len)
+        .and_then(|end| data.get(HEADER_LEN..end))
+        .ok_or_else(|| corrupt("truncated graph".into()))?;
+    if crc32fast::hash(graph) != graph_crc {
+        return Err(corrupt("graph checksum mismatch".into()));
+    }
+    let mut r = graph;
+    let quantizer = decode_quantizer(&mut r, dimension)?;
+    let id_to_label = (0..nodes).map(|_| r.read_u64::<LittleEndian>()).collect::<std::io::Result<Vec<_>>>()?;
+    let node_levels = (0..nodes)
+        .map(|_| r.read_u32::<LittleEndian>().map(|l| l as usize))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    let deleted_count = r.read_u64::<LittleEndian>()? as usize;
+    let deleted = read_ids(&mut r, deleted_count, nodes)?;
+    let layer_count = r.read_u64::<LittleEndian>()? as usize;
+    let mut layers = Vec::with_capacity(layer_count.min(64));
+    for _ in 0..layer_count {
+        let slots = r.read_u64::<LittleEndian>()? as usize;
+        if slots > nodes {
+            return Err(corrupt("layer larger than the graph".into()));
+        }
+        let offsets = (0..=slots)
+            .map(|_| r.read_u64::<LittleEndian>().map(|o| o as usize))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        let neighbours = read_ids(&mut r, *offsets.last().unwrap_or(&0), nodes)?;
+        let mut layer = Vec::with_capacity(slots);
+        for w in offsets.windows(2) {
+            layer.push(neighbours.get(w[0]..w[1]).ok_or_else(|| corrupt("bad adjacency offsets".into()))?.to_vec());
+        }
+        layers.push(layer);
+    }
+
+    let offset = HEADER_LEN + graph_len + padding(HEADER_LEN + graph_len);
+    let stride = kind.stride(dimension, quantizer.kind());
+    if CodeKind::of(&quantizer) != kind || (kind == CodeKind::Pq && stride == 0) {
+        return Err(corrupt("vector encoding does not match the quantizer".into()));
+    }
+    let end = nodes.checked_mul(stride).and_then(|len| len.checked_add(offset));
+    if end.is_none_or(|end| end > data.len()) {
+        return Err(corrupt("truncated vector block".into()));
+    }
+    let entry_point = (entry_point != u64::MAX as usize).then_some(entry_point);
+    if entry_point.is_some_and(|ep| ep >= nodes) {
+        return Err(corrupt("entry point outside the graph".into()));
+    }
+
+    let index = HnswFile {
+        dimension,
+        metric,
+        m,
+        ef_construction,
+        ef_search,
+        entry_point,
+        max_level,
+        quantizer,
+        id_to_label,
+        node_levels,
+        deleted,
+        layers,
+        vectors: Vectors::Owned(Vec::new()),
+    };
+    Ok((index, VectorLayout { offset, stride, kind, count: nodes, crc: vector_crc }))
+}
+
+fn encode_graph(index: &HnswFileRef<'_>) -> Result<Vec<u8>> {
+    let mut g = Vec::new();
+    let (kind, pq_m) = match index.quantizer.kind() {
+        Quantization::None => (0, 0),
+        Quantization::Int8 => (1, 0),
+        Quantization::Pq { m } => (2, m),
+    };
+    g.write_u8(kind)?;
+    g.write_u64::<LittleEndian>(pq_m as u64)?;
+    g.write_u64::<LittleEndian>(index.quantizer.codebooks().len() as u64)?;
+    for book in index.quantizer.codebooks() {
+        g.write_u64::<LittleEndian>(book.len() as u64)?;
+        for &x in book {
+            g.write_f32::<LittleEndian>(x)?;
+        }
+    }
+    for &label in index.id_to_label {
+        g.write_u64::<LittleEndian>(label)?;
+    }
+    for &level in index.node_levels {
+        g.write_u32::<LittleEndian>(level as u32)?;
+    }
+    g.write_u64::<LittleEndian>(index.deleted.len() as u64)?;
+    for &id in &index.deleted {
+        g.write_u32::<LittleEndian>(id as u32)?;
+    }
+    g.write_u64::<LittleEndian>(index.layers.len() as u64)?;
+    for layer in &index.layers {
+        g.write_u64::<LittleEndian>(layer.len() as u64)?;
+        let mut offset = 0u64;
+        g.write_u64::<LittleEndian>(0)?;
+        for neighbours in layer {
+            offset += neighbours.len() as u64;
+            g.write_u64::<LittleEndian>(offset)?;
+        }
+        for &id in layer.iter().flatten() {
+            g.write_u32::<LittleEndian>(id as u32)?;
+        }
+    }
+    Ok(g)
+}
+
+fn decode_quantizer(r: &mut &[u8], dim: usize) -> Result<Quantizer> {
+    let tag = r.read_u8()?;
+    let m = r.read_u64::<LittleEndian>()? as usize;
+    let kind = match tag {
+        0 => Quantization::None,
+        1 => Quantization::Int8,
+        2 => Quantization::Pq { m },
+        _ => return Err(corrupt(format!("unknown quantization {tag}"))),
+    };
+    let books = r.read_u64::<LittleEndian>()? as usize;
+    let mut codebooks = Vec::with_capacity(books.min(dim));
+    for _ in 0..books {
+        let len = r.read_u64::<LittleEndian>()? as usize;
+        if len > r.len() / 4 {
+            return Err(corrupt("truncated codebook".into()));
+        }
+        codebooks.push((0..len).map(|_| r.read_f32::<LittleEndian>()).collect::<std::io::Result<Vec<_>>>()?);
+    }
+    Quantizer::from_parts(kind, dim, codebooks)
+}
+
+fn encode_code(kind: CodeKind, code: CodeRef<'_>, out: &mut Vec<u8>) -> Result<()> {
+    match (kind, code) {
+        (CodeKind::Full, CodeRef::Full(v)) => {
+            for &x in v {
+                out.write_f32::<LittleEndian>(x)?;
+            }
+        }
+        (CodeKind::Int8, CodeRef::Int8 { min, scale, bytes }) => {
+            out.write_f32::<LittleEndian>(min)?;
+            out.write_f32::<LittleEndian>(scale)?;
+            out.extend_from_slice(bytes);
+        }
+        (CodeKind::Pq, CodeRef::Pq(bytes)) => out.extend_from_slice(bytes),
+        _ => return Err(VectorDbError::Storage("HNSW vectors differ in encoding".into())),
+    }
+    Ok(())
+}
+
+/// Copy one stride of the vector block out as a code.
+fn read_code(kind: CodeKind, bytes: &[u8]) -> Code {
+    match kind {
+        CodeKind::Full => Code::Full(
+            bytes.chunks_exact(4).map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]])).collect(),
+        ),
+        _ => decode_code(kind, bytes).to_code(),
+    }
+}
+
+/// View one stride of a mapped vector block as a code.
+fn decode_code(kind: CodeKind, bytes: &[u8]) -> CodeRef<'_> {
+    match kind {
+        CodeKind::Full => {
+            let (head, floats, tail) = unsafe {
+                // SAFETY: every bit pattern is a valid f32; misaligned or
+                // partial bytes end up in `head`/`tail`, which are checked.
+                bytes.align_to::<f32>()
+            };
+            assert!(head.is_empty() && tail.is_empty(), "misaligned HNSW vector block");
+            CodeRef::Full(floats)
+        }
+        CodeKind::Int8 => CodeRef::Int8 {
+            min: f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            scale: f32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            bytes: &bytes[8..],
+        },
+        CodeKind::Pq => CodeRef::Pq(bytes),
+    }
+}
+
+fn read_ids(r: &mut &[u8], count: usize, nodes: usize) -> Result<Vec<usize>> {
+    if count > r.len() / 4 {
+        return Err(corrupt("truncated id list".into()));
+    }
+    let ids = (0..count)
+        .map(|_| r.read_u32::<LittleEndian>().map(|id| id as usize))
+        .collect::<std::io::Result<Vec<_>>>()?;
+    if ids.iter().any(|&id| id >= nodes) {
+        return Err(corrupt("node id outside the graph".into()));
+    }
+    Ok(ids)
+}
+
+fn padding(len: usize) -> usize {
+    (VECTOR_ALIGN - len % VECTOR_ALIGN) % VECTOR_ALIGN
+}
+
+fn metric_tag(metric: DistanceMetric) -> u8 {
+    match metric {
+        DistanceMetric::Cosine => 0,
+        DistanceMetric::L2 => 1,
+        DistanceMetric::Ip => 2,
+    }
+}
+
+fn metric_from_tag(tag: u8) -> Result<DistanceMetric> {
+    match tag {
+        0 => Ok(DistanceMetric::Cosine),
+        1 => Ok(DistanceMetric::L2),
+        2 => Ok(DistanceMetric::Ip),
+        _ => Err(corrupt(format!("unknown metric {tag}"))),
+    }
+}
+
+fn corrupt(message: String) -> VectorDbError {
+    VectorDbError::Serialization(format!("HNSW index file: {message}"))
+}
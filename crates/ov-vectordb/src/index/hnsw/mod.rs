@@ -0,0 +1,864 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::cmp::Reverse;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use parking_lot::{Mutex, RwLock, RwLockReadGuard, RwLockUpgradableReadGuard, RwLockWriteGuard};
+use ordered_float::OrderedFloat;
+use rand::Rng;
+use rayon::prelude::*;
+use crate::distance::{self, DistanceMetric};
+use crate::error::{Result, VectorDbError};
+use super::quantize::{self, Code, Quantization, Quantizer, Scorer};
+use super::{SearchResult, traits::VectorIndex};
+use format::{HnswFile, HnswFileRef, Vectors, INDEX_FILE};
+
+mod format;
+
+/// Index file written before the binary format; migrated on load.
+const LEGACY_JSON_FILE: &str = "hnsw_index.json";
+
+/// Tombstone ratio above which [`VectorIndex::needs_rebuild`] reports true.
+const DEFAULT_VACUUM_THRESHOLD: f32 = 0.25;
+
+/// HNSW (Hierarchical Navigable Small World) index.
+///
+/// Parameters:
+/// - `m`: Number of connections per layer (default 16)
+/// - `ef_construction`: Size of dynamic candidate list during construction (default 200)
+/// - `ef_search`: Size of dynamic candidate list during search (default 50)
+///
+/// Inserts hold the index lock exclusively only while storing vectors.
+/// Linking a node into the graph happens under a shared lock with each
+/// adjacency list locked on its own, so [`VectorIndex::insert_batch`]
+/// links on all cores and searches keep running.
+pub struct HnswIndex {
+    dimension: usize,
+    metric: DistanceMetric,
+    m: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    /// Memory-map the vectors on load instead of reading them in.
+    mmap: bool,
+    /// Tombstone ratio at which the index asks to be vacuumed.
+    vacuum_threshold: f32,
+    inner: RwLock<HnswInner>,
+    /// Held while PQ codebooks train, so concurrent inserts train once.
+    training: Mutex<()>,
+}
+
+struct HnswInner {
+    /// All vectors stored by internal id, encoded by `quantizer`.
+    vectors: Vectors,
+    quantizer: Quantizer,
+    /// Map from user label to internal id.
+    label_to_id: HashMap<u64, usize>,
+    /// Map from internal id to user label.
+    id_to_label: Vec<u64>,
+    /// Adjacency lists per layer. layers[level][node_id] = neighbors.
+    layers: Vec<Vec<Links>>,
+    /// Maximum level for each node.
+    node_levels: Vec<usize>,
+    entry: Mutex<EntryPoint>,
+    /// Deleted set.
+    deleted: HashSet<usize>,
+    /// ML = 1/ln(M)
+    ml: f64,
+    /// Bumped whenever links or the entry point change, so a vacuum can
+    /// tell whether inserts were linking while it compacted.
+    edits: AtomicUsize,
+}
+
+/// Neighbours of one node on one layer.
+type Links = RwLock<Vec<usize>>;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct EntryPoint {
+    /// Internal id searches start from.
+    id: Option<usize>,
+    /// Maximum level in the graph.
+    max_level: usize,
+}
+
+impl HnswIndex {
+    pub fn new(dimension: usize, metric: DistanceMetric) -> Self {
+        Self::with_params(dimension, metric, 16, 200, 50)
+    }
+
+    pub fn with_params(
+        dimension: usize,
+        metric: DistanceMetric,
+        m: usize,
+        ef_construction: usize,
+        ef_search: usize,
+    ) -> Self {
+        let ml = 1.0 / (m as f64).ln();
+        Self {
+            dimension,
+            metric,
+            m,
+            ef_construction,
+            ef_search,
+            mmap: false,
+            vacuum_threshold: DEFAULT_VACUUM_THRESHOLD,
+            inner: RwLock::new(HnswInner {
+                vectors: Vectors::Owned(Vec::new()),
+                quantizer: Quantizer::new(Quantization::None, dimension),
+                label_to_id: HashMap::new(),
+                id_to_label: Vec::new(),
+                layers: Vec::new(),
+                node_levels: Vec::new(),
+                entry: Mutex::new(EntryPoint::default()),
+                deleted: HashSet::new(),
+                ml,
+                edits: AtomicUsize::new(0),
+            }),
+            training: Mutex::new(()),
+        }
+    }
+
+    /// Store vectors with the given encoding; graph traversal then scores
+    /// against the codes. Set before inserting.
+    pub fn with_quantization(self, quantization: Quantization) -> Self {
+        self.inner.write().quantizer = Quantizer::new(quantization, self.dimension);
+        self
+    }
+
+    pub fn quantization(&self) -> Quantization {
+        self.inner.read().quantizer.kind()
+    }
+
+    /// Memory-map the vectors of a loaded index file rather than reading
+    /// them in, so large indexes open without reading every vector. The
+    /// vectors are copied into memory on the first insert.
+    pub fn with_mmap(mut self, mmap: bool) -> Self {
+        self.mmap = mmap;
+        self
+    }
+
+    /// Whether the vectors are currently served from a mapped file.
+    pub fn is_mapped(&self) -> bool {
+        self.inner.read().vectors.is_mapped()
+    }
+
+    /// Bytes used by the stored vectors, including PQ codebooks.
+    pub fn vector_bytes(&self) -> usize {
+        let inner = self.inner.read();
+        inner.quantizer.size(inner.vectors.iter())
+    }
+
+    /// Report [`VectorIndex::needs_rebuild`] once this share of the nodes
+    /// are tombstones.
+    pub fn with_vacuum_threshold(mut self, threshold: f32) -> Self {
+        self.vacuum_threshold = threshold;
+        self
+    }
+
+    /// Share of the graph's nodes that are deleted but not yet vacuumed.
+    pub fn tombstone_ratio(&self) -> f32 {
+        let inner = self.inner.read();
+        match inner.vectors.len() {
+            0 => 0.0,
+            total => inner.deleted.len() as f32 / total as f32,
+        }
+    }
+
+    /// Neighbour labels of the live node for `label`, one list per level
+    /// from 0 up.
+    pub fn neighbors(&self, label: u64) -> Option<Vec<Vec<u64>>> {
+        let inner = self.inner.read();
+        let &id = inner.label_to_id.get(&label)?;
+        let levels = (0..=inner.node_levels[id])
+            .map(|lev| inner.layers[lev][id].read().iter().map(|&n| inner.id_to_label[n]).collect())
+            .collect();
+        Some(levels)
+    }
+
+    /// Run [`VectorIndex::vacuum`] on a background thread. Searches keep
+    /// running meanwhile; inserts and deletes wait for it to finish.
+    pub fn spawn_vacuum(self: &Arc<Self>) -> JoinHandle<Result<usize>> {
+        let index = Arc::clone(self);
+        std::thread::spawn(move || index.vacuum())
+    }
+
+    /// The graph without its deleted nodes, with live ids renumbered in
+    /// order. Nodes that linked to a deleted node are relinked to the live
+    /// nodes reachable through it.
+    fn compact(&self, inner: &HnswInner) -> HnswInner {
+        let live: Vec<usize> = (0..inner.vectors.len()).filter(|id| !inner.deleted.contains(id)).collect();
+        let mut new_ids = vec![usize::MAX; inner.vectors.len()];
+        for (new, &old) in live.iter().enumerate() {
+            new_ids[old] = new;
+        }
+        let node_levels: Vec<usize> = live.iter().map(|&old| inner.node_levels[old]).collect();
+
+        let mut layers = Vec::with_capacity(inner.layers.len());
+        for (lev, layer) in inner.layers.iter().enumerate() {
+            let max_neighbors = if lev == 0 { self.m * 2 } else { self.m };
+            // Slots run up to the last live node that reaches this level.
+            let slots = node_levels.iter().rposition(|&l| l >= lev).map_or(0, |i| i + 1);
+            if slots == 0 {
+                break;
+            }
+            let mut new_layer: Vec<Vec<usize>> = live[..slots].iter()
+                .map(|&old| if inner.node_levels[old] >= lev && old < layer.len() {
+                    self.repaired_neighbors(inner, lev, old, max_neighbors).into_iter().map(|n| new_ids[n]).collect()
+                } else {
+                    Vec::new()
+                })
+                .collect();
+            // A node whose only in-links came through deleted nodes would be
+            // unreachable; link it back from its first neighbour.
+            let mut linked = vec![false; slots];
+            for n in new_layer.iter().flatten() {
+                linked[*n] = true;
+            }
+            for id in 0..slots {
+                if !linked[id] {
+                    if let Some(&first) = new_layer[id].first() {
+                        new_layer[first].push(id);
+                    }
+                }
+            }
+            layers.push(new_layer.into_iter().map(Links::new).collect());
+        }
+
+        let max_level = node_levels.iter().copied().max().unwrap_or(0);
+        let entry_point = match inner.entry.lock().id {
+            Some(ep) if !inner.deleted.contains(&ep) && inner.node_levels[ep] == max_level => Some(new_ids[ep]),
+            _ => node_levels.iter().position(|&l| l == max_level),
+        };
+        let id_to_label: Vec<u64> = live.iter().map(|&old| inner.id_to_label[old]).collect();
+        HnswInner {
+            vectors: Vectors::Owned(live.iter().map(|&old| inner.vectors.get(old).to_code()).collect()),
+            quantizer: inner.quantizer.clone(),
+            label_to_id: id_to_label.iter().enumerate().map(|(id, &label)| (label, id)).collect(),
+            id_to_label,
+            layers,
+            node_levels,
+            entry: Mutex::new(EntryPoint { id: entry_point, max_level }),
+            deleted: HashSet::new(),
+            ml: inner.ml,
+            edits: AtomicUsize::new(0),
+        }
+    }
+
+    /// Live neighbours of `id` at `lev`. Deleted neighbours are replaced by
+    /// the live nodes they link to, keeping the `max_neighbors` closest.
+    fn repaired_neighbors(&self, inner: &HnswInner, lev: usize, id: usize, max_neighbors: usize) -> Vec<usize> {
+        let direct = inner.layers[lev][id].read().clone();
+        if !direct.iter().any(|n| inner.deleted.contains(n)) {
+            return direct;
+        }
+        let mut candidates = Vec::new();
+        let mut seen = HashSet::from([id]);
+        let mut pending = direct;
+        while let Some(n) = pending.pop() {
+            if !seen.insert(n) {
+                continue;
+            }
+            if !inner.deleted.contains(&n) {
+                candidates.push(n);
+            } else if candidates.len() < max_neighbors * 2 {
+                pending.extend(inner.layers[lev].get(n).map(|links| links.read().clone()).into_iter().flatten());
+            }
+        }
+        if candidates.len() > max_neighbors {
+            let v = inner.vectors.get(id);
+            let mut scored: Vec<(usize, f32)> = candidates.iter()
+                .map(|&n| (n, inner.quantizer.score_pair(self.metric, v, inner.vectors.get(n))))
+                .collect();
+            scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+            candidates = scored.into_iter().take(max_neighbors).map(|(n, _)| n).collect();
+        }
+        candidates
+    }
+
+    fn compute_score_inner(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self.metric {
+            DistanceMetric::Cosine => distance::cosine_similarity(a, b),
+            DistanceMetric::Ip => distance::inner_product(a, b),
+            DistanceMetric::L2 => {
+                let d = distance::l2_squared(a, b);
+                1.0 / (1.0 + d)
+            }
+        }
+    }
+
+    fn graph<'a>(&self, inner: &'a HnswInner) -> Graph<'a> {
+        Graph {
+            vectors: &inner.vectors,
+            layers: &inner.layers,
+            deleted: &inner.deleted,
+        }
+    }
+
+    /// Top-k search, returning only labels that pass `allow`.
+    ///
+    /// Filtering happens inside the level-0 traversal. When the beam stops
+    /// before `top_k` labels pass, `ef` is doubled and the layer searched
+    /// again, until enough pass or every reachable node has been seen.
+    fn search_with(&self, query: &[f32], top_k: usize, allow: Option<&dyn Fn(u64) -> bool>) -> Result<SearchResult> {
+        if query.len() != self.dimension {
+            return Err(VectorDbError::DimensionMismatch {
+                expected: self.dimension,
+                got: query.len(),
+            });
+        }
+        let inner = self.inner.read();
+        let entry = *inner.entry.lock();
+        let Some(ep) = entry.id else {
+            return Ok(SearchResult::empty());
+        };
+        if top_k == 0 {
+            return Ok(SearchResult::empty());
+        }
+
+        let query_vec = if self.metric == DistanceMetric::Cosine {
+            let mut q = query.to_vec();
+            distance::normalize_vector(&mut q);
+            q
+        } else {
+            query.to_vec()
+        };
+
+        let graph = self.graph(&inner);
+        let scorer = inner.quantizer.scorer(self.metric, &query_vec);
+        let mut curr_ep = ep;
+
+        // Traverse from top level down to level 1
+        for lev in (1..=entry.max_level).rev() {
+            curr_ep = graph.greedy_closest(lev, curr_ep, &scorer);
+        }
+
+        // Search at level 0, widening the beam while too few labels pass
+        let accept = |id: usize| allow.is_none_or(|f| f(inner.id_to_label[id]));
+        let mut ef = std::cmp::max(self.ef_search, top_k);
+        let candidates = loop {
+            let layer = graph.search_layer(0, curr_ep, &scorer, ef, Some(&accept));
+            if layer.hits.len() >= top_k || layer.exhausted || ef >= inner.label_to_id.len() {
+                break layer.hits;
+            }
+            ef = ef.saturating_mul(2);
+        };
+
+        let results: Vec<(u64, f32)> = candidates.into_iter()
+            .take(top_k)
+            .map(|(id, score)| (inner.id_to_label[id], score))
+            .collect();
+
+        Ok(SearchResult {
+            ids: results.iter().map(|r| r.0).collect(),
+            scores: results.iter().map(|r| r.1).collect(),
+        })
+    }
+
+    /// Check a vector's dimension and normalize it for cosine.
+    fn prepare(&self, vector: &[f32]) -> Result<Vec<f32>> {
+        if vector.len() != self.dimension {
+            return Err(VectorDbError::DimensionMismatch {
+                expected: self.dimension,
+                got: vector.len(),
+            });
+        }
+        let mut vec = vector.to_vec();
+        if self.metric == DistanceMetric::Cosine {
+            distance::normalize_vector(&mut vec);
+        }
+        Ok(vec)
+    }
+
+    /// Store `vec` under `label`, returning its node id: the label's node
+    /// updated in place, or a new node with empty links that
+    /// [`HnswIndex::link`] must then connect.
+    fn store(&self, inner: &mut HnswInner, label: u64, vec: &[f32]) -> usize {
+        let code = inner.quantizer.encode(vec);
+
+        // Check if updating existing
+        if let Some(&id) = inner.label_to_id.get(&label) {
+            inner.vectors.owned_mut()[id] = code;
+            inner.deleted.remove(&id);
+            return id;
+        }
+
+        let new_id = inner.vectors.len();
+        let level = Self::random_level(inner.ml);
+
+        inner.vectors.owned_mut().push(code);
+        inner.label_to_id.insert(label, new_id);
+        inner.id_to_label.push(label);
+        inner.node_levels.push(level);
+
+        // Ensure layers exist up to this level
+        while inner.layers.len() <= level {
+            inner.layers.push(Vec::new());
+        }
+        for l in 0..=level {
+            while inner.layers[l].len() <= new_id {
+                inner.layers[l].push(Links::default());
+            }
+        }
+        new_id
+    }
+
+    /// Train the PQ codebooks on a copy of the stored vectors, then swap
+    /// them in and encode everything under a short write lock.
+    fn train_quantizer(&self) {
+        let Some(_training) = self.training.try_lock() else { return };
+        let (quantizer, samples) = {
+            let inner = self.inner.read();
+            if !inner.quantizer.wants_training(inner.vectors.len()) {
+                return;
+            }
+            (inner.quantizer.clone(), quantize::full_vectors(inner.vectors.iter()))
+        };
+        let codebooks = quantizer.train(&samples);
+        let mut inner = self.inner.write();
+        let HnswInner { quantizer, vectors, .. } = &mut *inner;
+        quantizer.install(codebooks, vectors.owned_mut());
+    }
+
+    /// Connect stored node `id` to its nearest neighbours on each of its
+    /// levels. Needs only a shared lock: an adjacency list is locked just
+    /// while it is rewritten, so several nodes can link at once.
+    fn link(&self, inner: &HnswInner, id: usize, vec: &[f32]) {
+        let level = inner.node_levels[id];
+        let (ep, max_level) = {
+            let mut entry = inner.entry.lock();
+            let Some(ep) = entry.id else {
+                *entry = EntryPoint { id: Some(id), max_level: level };
+                inner.edits.fetch_add(1, Ordering::SeqCst);
+                return;
+            };
+            (ep, entry.max_level)
+        };
+        let graph = self.graph(inner);
+        let mut curr_ep = ep;
+
+        // Traverse from top level down to level+1 with greedy search
+        let query = inner.quantizer.scorer(self.metric, vec);
+        for lev in (level + 1..=max_level).rev() {
+            curr_ep = graph.greedy_closest(lev, curr_ep, &query);
+        }
+
+        // For levels [min(level, max_level) down to 0], do ef_construction search
+        let mut plan = Vec::with_capacity(level.min(max_level) + 1);
+        for lev in (0..=level.min(max_level)).rev() {
+            let candidates = graph.search_layer(lev, curr_ep, &query, self.ef_construction, None).hits;
+
+            // Select M best neighbors
+            let max_neighbors = if lev == 0 { self.m * 2 } else { self.m };
+            let neighbors: Vec<usize> = candidates.iter()
+                .filter(|&&(n, _)| n != id)
+                .take(max_neighbors)
+                .map(|&(n, _)| n)
+                .collect();
+            plan.push((lev, neighbors));
+
+            if let Some(&(best, _)) = candidates.first() {
+                curr_ep = best;
+            }
+        }
+
+        // Connect from level 0 up, so a search reaching the node on one
+        // level finds it linked on every level below.
+        for (lev, neighbors) in plan.into_iter().rev() {
+            let max_neighbors = if lev == 0 { self.m * 2 } else { self.m };
+            // Merge rather than overwrite: nodes linking at the same time
+            // may already have added back-links to this one.
+            self.connect(inner, lev, id, &neighbors, max_neighbors);
+
+            // Bidirectional connections
+            for &neighbor in &neighbors {
+                self.connect(inner, lev, neighbor, &[id], max_neighbors);
+            }
+            inner.edits.fetch_add(1, Ordering::SeqCst);
+        }
+
+        if level > max_level {
+            let mut entry = inner.entry.lock();
+            if level > entry.max_level {
+                *entry = EntryPoint { id: Some(id), max_level: level };
+                inner.edits.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Add `extra` to the adjacency list of `node` on `lev`, keeping its
+    /// `max_neighbors` closest if that over-connects it.
+    fn connect(&self, inner: &HnswInner, lev: usize, node: usize, extra: &[usize], max_neighbors: usize) {
+        let mut links = inner.layers[lev][node].write();
+        for &n in extra {
+            if n != node && !links.contains(&n) {
+                links.push(n);
+            }
+        }
+        if links.len() > max_neighbors {
+            let nv = inner.vectors.get(node);
+            let mut scored: Vec<(usize, f32)> = links.iter()
+                .map(|&n| (n, inner.quantizer.score_pair(self.metric, nv, inner.vectors.get(n))))
+                .collect();
+            scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            scored.truncate(max_neighbors);
+            *links = scored.into_iter().map(|(n, _)| n).collect();
+        }
+    }
+
+    fn random_level(ml: f64) -> usize {
+        let mut rng = rand::thread_rng();
+        let r: f64 = rng.gen();
+        (-r.ln() * ml).floor() as usize
+    }
+
+    /// Replace the index with the contents of a loaded file.
+    fn restore(&mut self, file: HnswFile) {
+        self.dimension = file.dimension;
+        self.m = file.m;
+        self.ef_construction = file.ef_construction;
+        self.ef_search = file.ef_search;
+        self.metric = file.metric;
+
+        let mut inner = self.inner.write();
+        inner.quantizer = file.quantizer;
+        inner.vectors = file.vectors;
+        inner.id_to_label = file.id_to_label;
+        inner.node_levels = file.node_levels;
+        inner.layers = file.layers.into_iter().map(|layer| layer.into_iter().map(Links::new).collect()).collect();
+        inner.entry = Mutex::new(EntryPoint { id: file.entry_point, max_level: file.max_level });
+        inner.ml = 1.0 / (self.m as f64).ln();
+        inner.deleted = file.deleted.into_iter().collect();
+        let live = inner.id_to_label.iter().enumerate()
+            .filter(|(id, _)| !inner.deleted.contains(id))
+            .map(|(id, &label)| (label, id))
+            .collect();
+        inner.label_to_id = live;
+    }
+}
+
+impl VectorIndex for HnswIndex {
+    fn insert(&self, label: u64, vector: &[f32]) -> Result<()> {
+        let vec = self.prepare(vector)?;
+        let mut inner = self.inner.write();
+        let fresh = inner.vectors.len();
+        let id = self.store(&mut inner, label, &vec);
+        let train = inner.quantizer.wants_training(inner.vectors.len());
+        if id >= fresh {
+            self.link(&RwLockWriteGuard::downgrade(inner), id, &vec);
+        } else {
+            drop(inner);
+        }
+        if train {
+            self.train_quantizer();
+        }
+        Ok(())
+    }
+
+    /// Store every vector, then link the new nodes into the graph in
+    /// parallel.
+    fn insert_batch(&self, labels: &[u64], vectors: &[Vec<f32>]) -> Result<()> {
+        let prepared = vectors.par_iter().map(|v| self.prepare(v)).collect::<Result<Vec<_>>>()?;
+        let mut inner = self.inner.write();
+        let fresh = inner.vectors.len();
+        // New nodes with the vector to link them by; a label repeated in
+        // the batch links by its last vector.
+        let mut pending: HashMap<usize, &[f32]> = HashMap::new();
+        for (&label, vec) in labels.iter().zip(&prepared) {
+            let id = self.store(&mut inner, label, vec);
+            if id >= fresh {
+                pending.insert(id, vec);
+            }
+        }
+        let mut pending: Vec<(usize, &[f32])> = pending.into_iter().collect();
+        pending.sort_unstable_by_key(|&(id, _)| id);
+
+        let train = inner.quantizer.wants_training(inner.vectors.len());
+        {
+            let inner = RwLockWriteGuard::downgrade(inner);
+            let inner: &HnswInner = &inner;
+            pending.par_iter().for_each(|&(id, vec)| self.link(inner, id, vec));
+        }
+        if train {
+            self.train_quantizer();
+        }
+        Ok(())
+    }
+
+    fn delete(&self, label: u64) -> Result<()> {
+        let mut inner = self.inner.write();
+        if let Some(&id) = inner.label_to_id.get(&label) {
+            inner.deleted.insert(id);
+            inner.label_to_id.remove(&label);
+        }
+        Ok(())
+    }
+
+    fn search(&self, query: &[f32], top_k: usize) -> Result<SearchResult> {
+        self.search_with(query, top_k, None)
+    }
+
+    fn search_filtered(&self, query: &[f32], top_k: usize, allow: &dyn Fn(u64) -> bool) -> Result<SearchResult> {
+        self.search_with(query, top_k, Some(allow))
+    }
+
+    fn len(&self) -> usize {
+        let inner = self.inner.read();
+        inner.label_to_id.len()
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        std::fs::create_dir_all(path)?;
+        let inner = self.inner.read();
+        let entry = *inner.entry.lock();
+        let mut deleted: Vec<usize> = inner.deleted.iter().copied().collect();
+        deleted.sort_unstable();
+        format::write(&path.join(INDEX_FILE), HnswFileRef {
+            dimension: self.dimension,
+            metric: self.metric,
+            m: self.m,
+            ef_construction: self.ef_construction,
+            ef_search: self.ef_search,
+            entry_point: entry.id,
+            max_level: entry.max_level,
+            quantizer: &inner.quantizer,
+            id_to_label: &inner.id_to_label,
+            node_levels: &inner.node_levels,
+            deleted,
+            layers: inner.layers.iter().map(|layer| layer.iter().map(|links| links.read().clone()).collect()).collect(),
+            vectors: Box::new(inner.vectors.iter()),
+        })
+    }
+
+    fn load(&mut self, path: &Path) -> Result<()> {
+        let file_path = path.join(INDEX_FILE);
+        let legacy_path = path.join(LEGACY_JSON_FILE);
+        if !file_path.exists() && legacy_path.exists() {
+            let data = std::fs::read(&legacy_path)?;
+            let deser: HnswDeserData = serde_json::from_slice(&data)
+                .map_err(|e| VectorDbError::Serialization(e.to_string()))?;
+            self.restore(deser.into_file());
+            match self.save(path) {
+                Ok(()) => std::fs::remove_file(&legacy_path)?,
+                Err(e) => tracing::warn!("keeping JSON HNSW index at {}: {e}", path.display()),
+            }
+            return Ok(());
+        }
+        self.restore(format::read(&file_path, self.mmap)?);
+        Ok(())
+    }
+
+    fn needs_rebuild(&self) -> bool {
+        let ratio = self.tombstone_ratio();
+        ratio > 0.0 && ratio >= self.vacuum_threshold
+    }
+
+    /// Remove deleted nodes from the graph and reconnect their neighbours.
+    /// The new graph is built under a shared lock, so searches continue
+    /// until it is swapped in.
+    fn vacuum(&self) -> Result<usize> {
+        let inner = self.inner.upgradable_read();
+        let removed = inner.deleted.len();
+        if removed == 0 {
+            return Ok(0);
+        }
+        let edits = inner.edits.load(Ordering::SeqCst);
+        let compacted = self.compact(&inner);
+        let mut inner = RwLockUpgradableReadGuard::upgrade(inner);
+        // Links made by inserts while compacting would be lost, so then
+        // compact again with searches held off.
+        *inner = if inner.edits.load(Ordering::SeqCst) == edits { compacted } else { self.compact(&inner) };
+        Ok(removed)
+    }
+}
+
+// -- Helper functions --
+
+/// Borrowed view of the graph that the traversal routines walk.
+struct Graph<'a> {
+    vectors: &'a Vectors,
+    layers: &'a [Vec<Links>],
+    deleted: &'a HashSet<usize>,
+}
+
+/// Result of a single-layer search.
+struct LayerHits {
+    /// Accepted nodes sorted by score descending, at most `ef` of them.
+    hits: Vec<(usize, f32)>,
+    /// Whether every node reachable from the entry point was expanded, so
+    /// a wider beam could not find more.
+    exhausted: bool,
+}
+
+impl<'a> Graph<'a> {
+    fn score(&self, query: &Scorer, id: usize) -> f32 {
+        query.score(self.vectors.get(id))
+    }
+
+    /// The node's neighbours on `level`, locked against concurrent linking.
+    fn neighbors(&self, level: usize, id: usize) -> Option<RwLockReadGuard<'a, Vec<usize>>> {
+        self.layers.get(level).and_then(|l| l.get(id)).map(|n| n.read())
+    }
+
+    fn greedy_closest(&self, level: usize, start: usize, query: &Scorer) -> usize {
+        let mut current = start;
+        let mut current_score = self.score(query, current);
+
+        loop {
+            let mut changed = false;
+            for &neighbor in self.neighbors(level, current).iter().flat_map(|n| n.iter()) {
+                if self.deleted.contains(&neighbor) { continue; }
+                let score = self.score(query, neighbor);
+                if score > current_score {
+                    current = neighbor;
+                    current_score = score;
+                    changed = true;
+                }
+            }
+            if !changed { break; }
+        }
+        current
+    }
+
+    /// Beam search of one layer with a beam of `ef` live nodes.
+    ///
+    /// `accept` only decides which nodes are returned: rejected nodes are
+    /// still traversed and still shape the beam, so a selective filter
+    /// does not cut the search off from accepted nodes behind them.
+    fn search_layer(
+        &self,
+        level: usize,
+        entry: usize,
+        query: &Scorer,
+        ef: usize,
+        accept: Option<&dyn Fn(usize) -> bool>,
+    ) -> LayerHits {
+        type Scored = (OrderedFloat<f32>, usize);
+        let accepted = |id: usize| !self.deleted.contains(&id) && accept.is_none_or(|f| f(id));
+
+        let mut visited = HashSet::new();
+        // Max-heap of nodes to expand.
+        let mut candidates: BinaryHeap<Scored> = BinaryHeap::new();
+        // Min-heap of the best `ef` live nodes; its worst bounds the search.
+        let mut beam: BinaryHeap<Reverse<Scored>> = BinaryHeap::new();
+        // Min-heap of the best `ef` accepted nodes.
+        let mut hits: BinaryHeap<Reverse<Scored>> = BinaryHeap::new();
+
+        let mut visit = |id: usize, score: f32, beam: &mut BinaryHeap<Reverse<Scored>>| {
+            if self.deleted.contains(&id) {
+                return;
+            }
+            beam.push(Reverse((OrderedFloat(score), id)));
+            if beam.len() > ef {
+                beam.pop();
+            }
+            if accepted(id) {
+                hits.push(Reverse((OrderedFloat(score), id)));
+                if hits.len() > ef {
+                    hits.pop();
+                }
+            }
+        };
+
+        let entry_score = self.score(query, entry);
+        candidates.push((OrderedFloat(entry_score), entry));
+        visit(entry, entry_score, &mut beam);
+        visited.insert(entry);
+
+        // Cleared once a node is skipped, whether by the stop rule or
+        // because it could not enter the beam.
+        let mut exhausted = true;
+        while let Some((OrderedFloat(cand_score), cand_id)) = candidates.pop() {
+            // If worst result is better than best candidate, stop
+            if let Some(&Reverse((OrderedFloat(worst_score), _))) = beam.peek() {
+                if beam.len() >= ef && cand_score < worst_score {
+                    exhausted = false;
+                    break;
+                }
+            }
+
+            for &neighbor in self.neighbors(level, cand_id).iter().flat_map(|n| n.iter()) {
+                if !visited.insert(neighbor) { continue; }
+                let score = self.score(query, neighbor);
+
+                let should_add = match beam.peek() {
+                    Some(&Reverse((OrderedFloat(worst), _))) if beam.len() >= ef => score > worst,
+                    _ => true,
+                };
+
+                if should_add {
+                    candidates.push((OrderedFloat(score), neighbor));
+                    visit(neighbor, score, &mut beam);
+                } else {
+                    exhausted = false;
+                }
+            }
+        }
+
+        let mut hits: Vec<(usize, f32)> = hits.into_iter()
+            .map(|Reverse((OrderedFloat(score), id))| (id, score))
+            .collect();
+        hits.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        LayerHits { hits, exhausted }
+    }
+}
+
+/// Index file written as JSON before the binary format.
+#[derive(serde::Deserialize)]
+struct HnswDeserData {
+    dimension: usize,
+    m: usize,
+    ef_construction: usize,
+    ef_search: usize,
+    metric: DistanceMetric,
+    #[serde(default)]
+    vectors: Vec<Vec<f32>>,
+    /// Present for quantized indexes, which store `codes` instead of `vectors`.
+    #[serde(default)]
+    quantizer: Option<Quantizer>,
+    #[serde(default)]
+    codes: Vec<Code>,
+    id_to_label: Vec<u64>,
+    node_levels: Vec<usize>,
+    layers: Vec<Vec<Vec<usize>>>,
+    entry_point: Option<usize>,
+    max_level: usize,
+}
+
+impl HnswDeserData {
+    fn into_file(self) -> HnswFile {
+        let (quantizer, codes) = match self.quantizer {
+            Some(quantizer) => (quantizer, self.codes),
+            None => (Quantizer::new(Quantization::None, self.dimension), self.vectors.into_iter().map(Code::Full).collect()),
+        };
+        // The JSON format did not record deletions. A label stored more than
+        // once was deleted and re-inserted, so only its last node is live.
+        let mut last = HashMap::new();
+        for (id, &label) in self.id_to_label.iter().enumerate() {
+            last.insert(label, id);
+        }
+        let deleted = (0..self.id_to_label.len()).filter(|id| last[&self.id_to_label[*id]] != *id).collect();
+        HnswFile {
+            dimension: self.dimension,
+            metric: self.metric,
+            m: self.m,
+            ef_construction: self.ef_construction,
+            ef_search: self.ef_search,
+            entry_point: self.entry_point,
+            max_level: self.max_level,
+            quantizer,
+            id_to_label: self.id_to_label,
+            node_levels: self.node_levels,
+            deleted,
+            layers: self.layers,
+            vectors: Vectors::Owned(codes),
+        }
+    }
+}
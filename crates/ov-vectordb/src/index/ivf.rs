@@ -0,0 +1,429 @@
+use std::collections::HashMap;
+use std::path::Path;
+use parking_lot::{Mutex, RwLock};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use crate::distance::{self, DistanceMetric};
+use crate::error::{Result, VectorDbError};
+use super::quantize::{self, Code, CodeRef, Quantization, Quantizer};
+use super::{SearchResult, traits::VectorIndex};
+
+const INDEX_FILE: &str = "ivf_index.json";
+
+const DEFAULT_NLIST: usize = 256;
+const DEFAULT_NPROBE: usize = 16;
+
+/// Vectors per list the index collects before training its centroids.
+const TRAIN_PER_LIST: usize = 32;
+
+/// Training samples per list; larger indexes retrain on a random subset.
+const MAX_TRAIN_PER_LIST: usize = 256;
+
+/// Drift at which [`VectorIndex::needs_rebuild`] reports true.
+const DEFAULT_DRIFT_THRESHOLD: f32 = 2.0;
+
+/// IVF (inverted file) vector index.
+///
+/// Vectors are partitioned into lists around k-means centroids, and a
+/// query scans only the lists whose centroids are closest to it. The
+/// centroids are trained once `nlist * 32` vectors have arrived; until then
+/// every query scans all vectors. With [`Quantization::Pq`] this is IVF-PQ.
+///
+/// Parameters:
+/// - `nlist`: Number of lists (default 256)
+/// - `nprobe`: Lists scanned per query (default 16)
+pub struct IvfIndex {
+    dimension: usize,
+    metric: DistanceMetric,
+    nlist: usize,
+    nprobe: usize,
+    /// Drift at which the index asks to be retrained.
+    drift_threshold: f32,
+    inner: RwLock<IvfInner>,
+    /// Held while PQ codebooks train, so concurrent inserts train once.
+    training: Mutex<()>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct IvfInner {
+    quantizer: Quantizer,
+    /// Centroids of the lists back to back. Empty until trained.
+    centroids: Vec<f32>,
+    /// Vectors of each list; a single list holds everything until trained.
+    lists: Vec<InvertedList>,
+    /// Mean squared distance from the training vectors to their centroids.
+    trained_error: f32,
+    /// Summed squared distance from vectors inserted since training to
+    /// their centroids, and how many there were.
+    added_error: f64,
+    added_count: usize,
+    /// Map from label to (list, position).
+    #[serde(skip)]
+    slots: HashMap<u64, (usize, usize)>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct InvertedList {
+    labels: Vec<u64>,
+    codes: Vec<Code>,
+}
+
+impl IvfIndex {
+    pub fn new(dimension: usize, metric: DistanceMetric) -> Self {
+        Self::with_params(dimension, metric, DEFAULT_NLIST, DEFAULT_NPROBE)
+    }
+
+    pub fn with_params(dimension: usize, metric: DistanceMetric, nlist: usize, nprobe: usize) -> Self {
+        Self {
+            dimension,
+            metric,
+            nlist: nlist.max(1),
+            nprobe: nprobe.max(1),
+            drift_threshold: DEFAULT_DRIFT_THRESHOLD,
+            inner: RwLock::new(IvfInner {
+                quantizer: Quantizer::new(Quantization::None, dimension),
+                centroids: Vec::new(),
+                lists: vec![InvertedList::default()],
+                trained_error: 0.0,
+                added_error: 0.0,
+                added_count: 0,
+                slots: HashMap::new(),
+            }),
+            training: Mutex::new(()),
+        }
+    }
+
+    /// Number of lists to train. Set before inserting.
+    pub fn with_nlist(mut self, nlist: usize) -> Self {
+        self.nlist = nlist.max(1);
+        self
+    }
+
+    /// Lists scanned per query. More lists find more of the true nearest
+    /// neighbours at proportionally higher cost.
+    pub fn with_nprobe(mut self, nprobe: usize) -> Self {
+        self.nprobe = nprobe.max(1);
+        self
+    }
+
+    /// Store vectors with the given encoding. Set before inserting.
+    pub fn with_quantization(self, quantization: Quantization) -> Self {
+        self.inner.write().quantizer = Quantizer::new(quantization, self.dimension);
+        self
+    }
+
+    /// Report [`VectorIndex::needs_rebuild`] once [`IvfIndex::drift`]
+    /// reaches `threshold`.
+    pub fn with_drift_threshold(mut self, threshold: f32) -> Self {
+        self.drift_threshold = threshold;
+        self
+    }
+
+    pub fn quantization(&self) -> Quantization {
+        self.inner.read().quantizer.kind()
+    }
+
+    pub fn nlist(&self) -> usize {
+        self.nlist
+    }
+
+    pub fn nprobe(&self) -> usize {
+        self.nprobe
+    }
+
+    /// Whether the centroids have been trained.
+    pub fn is_trained(&self) -> bool {
+        self.inner.read().is_trained()
+    }
+
+    /// Bytes used by the stored vectors and centroids, including PQ
+    /// codebooks.
+    pub fn vector_bytes(&self) -> usize {
+        let inner = self.inner.read();
+        let codes = inner.lists.iter().flat_map(|l| l.codes.iter().map(Code::view));
+        inner.quantizer.size(codes) + std::mem::size_of_val(inner.centroids.as_slice())
+    }
+
+    /// Mean squared distance from the vectors inserted since training to
+    /// their centroids, relative to that of the training vectors. Near 1
+    /// while new data resembles the training data; 0 before training.
+    pub fn drift(&self) -> f32 {
+        let inner = self.inner.read();
+        if !inner.is_trained() || inner.added_count == 0 {
+            return 0.0;
+        }
+        let added = (inner.added_error / inner.added_count as f64) as f32;
+        added / inner.trained_error.max(f32::MIN_POSITIVE)
+    }
+
+    /// Train new centroids on the vectors now held and reassign every
+    /// vector to its list.
+    pub fn retrain(&self) {
+        self.inner.write().train(self.dimension, self.nlist);
+    }
+
+    /// Train the PQ codebooks on a copy of the vectors of every list, then
+    /// swap them in and encode everything under a short write lock.
+    fn train_quantizer(&self) {
+        let Some(_training) = self.training.try_lock() else { return };
+        let (quantizer, samples) = {
+            let inner = self.inner.read();
+            if !inner.quantizer.wants_training(inner.slots.len()) {
+                return;
+            }
+            let codes = inner.lists.iter().flat_map(|l| l.codes.iter().map(Code::view));
+            (inner.quantizer.clone(), quantize::full_vectors(codes))
+        };
+        let codebooks = quantizer.train(&samples);
+        let mut inner = self.inner.write();
+        let IvfInner { quantizer, lists, .. } = &mut *inner;
+        quantizer.install(codebooks, lists.iter_mut().flat_map(|l| l.codes.iter_mut()));
+    }
+
+    /// Score the vectors whose label passes `allow` in the `nprobe` lists
+    /// closest to the query and keep the top-k. Further lists are scanned
+    /// while fewer than `top_k` vectors have passed.
+    fn scan(&self, query: &[f32], top_k: usize, allow: &dyn Fn(u64) -> bool) -> Result<SearchResult> {
+        if query.len() != self.dimension {
+            return Err(VectorDbError::DimensionMismatch {
+                expected: self.dimension,
+                got: query.len(),
+            });
+        }
+        let inner = self.inner.read();
+        if inner.slots.is_empty() || top_k == 0 {
+            return Ok(SearchResult::empty());
+        }
+
+        let mut query = query.to_vec();
+        if self.metric == DistanceMetric::Cosine {
+            distance::normalize_vector(&mut query);
+        }
+        let scorer = inner.quantizer.scorer(self.metric, &query);
+        let mut scored: Vec<(u64, f32)> = Vec::new();
+        for (probed, list) in inner.probe_order(&query).into_iter().enumerate() {
+            if probed >= self.nprobe && scored.len() >= top_k {
+                break;
+            }
+            let list = &inner.lists[list];
+            let (labels, codes): (Vec<u64>, Vec<CodeRef<'_>>) = list.labels.iter().zip(list.codes.iter())
+                .filter(|(&label, _)| allow(label))
+                .map(|(&label, code)| (label, code.view()))
+                .unzip();
+            let mut scores = vec![0.0; codes.len()];
+            scorer.score_many(&codes, &mut scores);
+            scored.extend(labels.into_iter().zip(scores));
+        }
+
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scored.truncate(top_k);
+
+        Ok(SearchResult {
+            ids: scored.iter().map(|s| s.0).collect(),
+            scores: scored.iter().map(|s| s.1).collect(),
+        })
+    }
+}
+
+impl IvfInner {
+    fn is_trained(&self) -> bool {
+        !self.centroids.is_empty()
+    }
+
+    /// The list `v` belongs to and its squared distance to that list's
+    /// centroid.
+    fn assign(&self, v: &[f32]) -> (usize, f32) {
+        if !self.is_trained() {
+            return (0, 0.0);
+        }
+        let list = quantize::nearest_centroid(&self.centroids, v);
+        (list, distance::l2_squared(v, &self.centroids[list * v.len()..(list + 1) * v.len()]))
+    }
+
+    /// Lists in order of their centroid's distance to `query`.
+    fn probe_order(&self, query: &[f32]) -> Vec<usize> {
+        if !self.is_trained() {
+            return vec![0];
+        }
+        let mut order: Vec<(usize, f32)> = self.centroids.chunks_exact(query.len())
+            .map(|c| distance::l2_squared(query, c))
+            .enumerate()
+            .collect();
+        order.sort_by(|a, b| a.1.total_cmp(&b.1));
+        order.into_iter().map(|(list, _)| list).collect()
+    }
+
+    fn add(&mut self, label: u64, v: &[f32]) {
+        let (list, error) = self.assign(v);
+        if self.is_trained() {
+            self.added_error += error as f64;
+            self.added_count += 1;
+        }
+        let code = self.quantizer.encode(v);
+        let list_vectors = &mut self.lists[list];
+        self.slots.insert(label, (list, list_vectors.labels.len()));
+        list_vectors.labels.push(label);
+        list_vectors.codes.push(code);
+    }
+
+    fn remove(&mut self, label: u64) {
+        let Some((list, pos)) = self.slots.remove(&label) else { return };
+        let list_vectors = &mut self.lists[list];
+        list_vectors.labels.swap_remove(pos);
+        list_vectors.codes.swap_remove(pos);
+        if let Some(&moved) = list_vectors.labels.get(pos) {
+            self.slots.insert(moved, (list, pos));
+        }
+    }
+
+    /// Cluster the held vectors around up to `nlist` new centroids and
+    /// move each into the list of its nearest one.
+    fn train(&mut self, dim: usize, nlist: usize) {
+        let mut labels = Vec::with_capacity(self.slots.len());
+        let mut codes = Vec::with_capacity(self.slots.len());
+        for list in self.lists.drain(..) {
+            labels.extend(list.labels);
+            codes.extend(list.codes);
+        }
+        let vectors: Vec<Vec<f32>> = codes.iter().map(|c| self.quantizer.decode(c.view()).into_owned()).collect();
+        let mut rng = StdRng::seed_from_u64(0);
+        let samples: Vec<&[f32]> = rand::seq::index::sample(&mut rng, vectors.len(), vectors.len().min(nlist * MAX_TRAIN_PER_LIST))
+            .iter()
+            .map(|i| vectors[i].as_slice())
+            .collect();
+        self.centroids = quantize::kmeans(&samples, dim, nlist, 0);
+        self.lists = (0..(self.centroids.len() / dim.max(1)).max(1)).map(|_| InvertedList::default()).collect();
+        self.slots.clear();
+
+        let mut error = 0.0f64;
+        for ((label, code), v) in labels.into_iter().zip(codes).zip(&vectors) {
+            let (list, e) = self.assign(v);
+            error += e as f64;
+            let list_vectors = &mut self.lists[list];
+            self.slots.insert(label, (list, list_vectors.labels.len()));
+            list_vectors.labels.push(label);
+            list_vectors.codes.push(code);
+        }
+        self.trained_error = (error / vectors.len().max(1) as f64) as f32;
+        self.added_error = 0.0;
+        self.added_count = 0;
+    }
+}
+
+impl VectorIndex for IvfIndex {
+    fn insert(&self, label: u64, vector: &[f32]) -> Result<()> {
+        if vector.len() != self.dimension {
+            return Err(VectorDbError::DimensionMismatch {
+                expected: self.dimension,
+                got: vector.len(),
+            });
+        }
+        let mut vec = vector.to_vec();
+        if self.metric == DistanceMetric::Cosine {
+            distance::normalize_vector(&mut vec);
+        }
+        let mut inner = self.inner.write();
+        // An update may belong in a different list.
+        inner.remove(label);
+        inner.add(label, &vec);
+        if !inner.is_trained() && inner.slots.len() >= self.nlist * TRAIN_PER_LIST {
+            inner.train(self.dimension, self.nlist);
+        }
+        let train = inner.quantizer.wants_training(inner.slots.len());
+        drop(inner);
+        if train {
+            self.train_quantizer();
+        }
+        Ok(())
+    }
+
+    fn delete(&self, label: u64) -> Result<()> {
+        self.inner.write().remove(label);
+        Ok(())
+    }
+
+    fn search(&self, query: &[f32], top_k: usize) -> Result<SearchResult> {
+        self.scan(query, top_k, &|_| true)
+    }
+
+    fn search_filtered(&self, query: &[f32], top_k: usize, allow: &dyn Fn(u64) -> bool) -> Result<SearchResult> {
+        self.scan(query, top_k, allow)
+    }
+
+    fn len(&self) -> usize {
+        self.inner.read().slots.len()
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    fn metric(&self) -> DistanceMetric {
+        self.metric
+    }
+
+    fn save(&self, path: &Path) -> Result<()> {
+        let inner = self.inner.read();
+        std::fs::create_dir_all(path)?;
+        let data = IvfSer {
+            dimension: self.dimension,
+            metric: self.metric,
+            nlist: self.nlist,
+            nprobe: self.nprobe,
+            index: &inner,
+        };
+        let json = serde_json::to_vec(&data).map_err(|e| VectorDbError::Serialization(e.to_string()))?;
+        std::fs::write(path.join(INDEX_FILE), json)?;
+        Ok(())
+    }
+
+    fn load(&mut self, path: &Path) -> Result<()> {
+        let data = std::fs::read(path.join(INDEX_FILE))?;
+        let de: IvfDe = serde_json::from_slice(&data)
+            .map_err(|e| VectorDbError::Serialization(e.to_string()))?;
+        let mut index = de.index;
+        let lists = index.centroids.len() / de.dimension.max(1);
+        let shaped = de.dimension > 0
+            && index.centroids.len().is_multiple_of(de.dimension)
+            && index.lists.len() == lists.max(1)
+            && index.lists.iter().all(|l| l.labels.len() == l.codes.len());
+        if !shaped {
+            return Err(VectorDbError::Serialization("ivf index lists do not match its centroids".into()));
+        }
+        index.slots = index.lists.iter().enumerate()
+            .flat_map(|(list, l)| l.labels.iter().enumerate().map(move |(pos, &label)| (label, (list, pos))))
+            .collect();
+        self.dimension = de.dimension;
+        self.metric = de.metric;
+        self.nlist = de.nlist.max(1);
+        self.nprobe = de.nprobe.max(1);
+        *self.inner.write() = index;
+        Ok(())
+    }
+
+    /// Whether enough vectors inserted since training sit far enough from
+    /// the centroids that [`IvfIndex::retrain`] should be run.
+    fn needs_rebuild(&self) -> bool {
+        let added = self.inner.read().added_count;
+        added >= self.nlist && self.drift() >= self.drift_threshold
+    }
+}
+
+#[derive(Serialize)]
+struct IvfSer<'a> {
+    dimension: usize,
+    metric: DistanceMetric,
+    nlist: usize,
+    nprobe: usize,
+    index: &'a IvfInner,
+}
+
+#[derive(Deserialize)]
+struct IvfDe {
+    dimension: usize,
+    metric: DistanceMetric,
+    nlist: usize,
+    nprobe: usize,
+    index: IvfInner,
+}
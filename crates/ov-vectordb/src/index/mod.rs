@@ -1,11 +1,26 @@
-//! Vector index implementations: Flat (brute-force) and HNSW.
+//! Vector index implementations: Flat (brute-force), HNSW and IVF with
+//! optional quantized storage, sparse inverted, a BM25 full-text index,
+//! scalar field indexes for filtering, and fusion of ranked results.
 
 mod flat;
+mod fulltext;
+mod fusion;
 mod hnsw;
+mod ivf;
+mod quantize;
+mod scalar;
+mod sparse;
 mod traits;
 
 pub use flat::FlatIndex;
+pub use fulltext::{tokenize, FullTextIndex};
+pub use fusion::{fuse, Fusion};
 pub use hnsw::HnswIndex;
+pub use ivf::IvfIndex;
+pub use quantize::{Quantization, PQ_TRAIN_SIZE};
+pub use scalar::{plan_filter, ScalarIndex};
+pub(crate) use scalar::{group_values, Key as ScalarKey};
+pub use sparse::{SparseIndex, SparseVector};
 pub use traits::VectorIndex;
 
 /// Search result: (id, score) pairs sorted by descending score.
@@ -27,4 +42,20 @@ impl SearchResult {
     pub fn is_empty(&self) -> bool {
         self.ids.is_empty()
     }
+
+    /// Keep only the hits whose label passes `keep`, preserving order.
+    pub fn retain(&mut self, mut keep: impl FnMut(u64) -> bool) {
+        let (ids, scores) = self.ids.iter().zip(self.scores.iter())
+            .filter(|(&id, _)| keep(id))
+            .map(|(&id, &score)| (id, score))
+            .unzip();
+        self.ids = ids;
+        self.scores = scores;
+    }
+
+    /// Keep at most the first `len` hits.
+    pub fn truncate(&mut self, len: usize) {
+        self.ids.truncate(len);
+        self.scores.truncate(len);
+    }
 }
@@ -0,0 +1,379 @@
+//! Compressed vector encodings for the dense indexes.
+//!
+//! [`Quantization::Int8`] stores each component as one byte, scaled between
+//! the vector's own minimum and maximum, so it needs no training.
+//! [`Quantization::Pq`] splits a vector into `m` subspaces and stores the
+//! nearest of 256 trained centroids for each, one byte per subspace. PQ
+//! indexes keep vectors in full precision until [`PQ_TRAIN_SIZE`] have
+//! arrived, then train the codebooks on them and encode everything.
+//!
+//! Queries are scored against the codes: int8 codes are expanded on the
+//! fly, PQ codes through a per-query table of subspace scores.
+
+use std::borrow::Cow;
+use std::ops::Range;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use crate::distance::{self, DistanceMetric};
+use crate::error::{Result, VectorDbError};
+
+/// How a dense index encodes the vectors it holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Quantization {
+    /// Full-precision `f32` components.
+    #[default]
+    None,
+    /// One byte per component plus 8 bytes per vector.
+    Int8,
+    /// Product quantization into `m` subspaces: `m` bytes per vector.
+    Pq { m: usize },
+}
+
+impl Quantization {
+    /// Check the encoding can be applied to vectors of `dim` components.
+    pub fn validate(&self, dim: usize) -> Result<()> {
+        match *self {
+            Self::Pq { m } if m == 0 || m > dim => Err(VectorDbError::InvalidConfig(format!(
+                "pq needs between 1 and {dim} subspaces, got {m}"
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Vectors a PQ index collects before training its codebooks.
+pub const PQ_TRAIN_SIZE: usize = 1024;
+
+/// Centroids per PQ subspace; codes are one byte.
+const PQ_CENTROIDS: usize = 256;
+
+const KMEANS_ITERATIONS: usize = 8;
+
+/// One stored vector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) enum Code {
+    Full(Vec<f32>),
+    Int8 { min: f32, scale: f32, bytes: Vec<u8> },
+    Pq(Vec<u8>),
+}
+
+impl Code {
+    pub fn view(&self) -> CodeRef<'_> {
+        match self {
+            Self::Full(v) => CodeRef::Full(v),
+            Self::Int8 { min, scale, bytes } => CodeRef::Int8 { min: *min, scale: *scale, bytes },
+            Self::Pq(bytes) => CodeRef::Pq(bytes),
+        }
+    }
+}
+
+/// A stored vector borrowed from an owned [`Code`] or a mapped index file.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum CodeRef<'a> {
+    Full(&'a [f32]),
+    Int8 { min: f32, scale: f32, bytes: &'a [u8] },
+    Pq(&'a [u8]),
+}
+
+impl CodeRef<'_> {
+    pub fn to_code(self) -> Code {
+        match self {
+            Self::Full(v) => Code::Full(v.to_vec()),
+            Self::Int8 { min, scale, bytes } => Code::Int8 { min, scale, bytes: bytes.to_vec() },
+            Self::Pq(bytes) => Code::Pq(bytes.to_vec()),
+        }
+    }
+
+    /// Bytes held by the code.
+    fn size(self) -> usize {
+        match self {
+            Self::Full(v) => std::mem::size_of_val(v),
+            Self::Int8 { bytes, .. } => bytes.len() + 2 * std::mem::size_of::<f32>(),
+            Self::Pq(bytes) => bytes.len(),
+        }
+    }
+}
+
+/// Encoder and decoder for one index, holding the trained PQ codebooks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Quantizer {
+    kind: Quantization,
+    dim: usize,
+    /// Per subspace, its centroids laid out back to back. Empty until trained.
+    #[serde(default)]
+    codebooks: Vec<Vec<f32>>,
+}
+
+impl Quantizer {
+    pub fn new(kind: Quantization, dim: usize) -> Self {
+        Self { kind, dim, codebooks: Vec::new() }
+    }
+
+    /// Rebuild a quantizer from its kind and trained codebooks.
+    pub fn from_parts(kind: Quantization, dim: usize, codebooks: Vec<Vec<f32>>) -> Result<Self> {
+        let quantizer = Self { kind, dim, codebooks };
+        let shaped = match kind {
+            Quantization::Pq { m } => {
+                quantizer.codebooks.is_empty()
+                    || (quantizer.codebooks.len() == m
+                        && (0..m).all(|j| quantizer.codebooks[j].len().is_multiple_of(quantizer.subspace(j).len().max(1))))
+            }
+            _ => quantizer.codebooks.is_empty(),
+        };
+        if !shaped {
+            return Err(VectorDbError::Serialization("codebooks do not match the quantization".into()));
+        }
+        kind.validate(dim)?;
+        Ok(quantizer)
+    }
+
+    pub fn kind(&self) -> Quantization {
+        self.kind
+    }
+
+    pub fn codebooks(&self) -> &[Vec<f32>] {
+        &self.codebooks
+    }
+
+    pub fn encode(&self, v: &[f32]) -> Code {
+        match self.kind {
+            Quantization::None => Code::Full(v.to_vec()),
+            Quantization::Int8 => {
+                let (min, max) = v.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &x| (lo.min(x), hi.max(x)));
+                let (min, scale) = if v.is_empty() { (0.0, 0.0) } else { (min, (max - min) / 255.0) };
+                let bytes = v.iter()
+                    .map(|&x| if scale > 0.0 { ((x - min) / scale).round().clamp(0.0, 255.0) as u8 } else { 0 })
+                    .collect();
+                Code::Int8 { min, scale, bytes }
+            }
+            Quantization::Pq { .. } if self.codebooks.is_empty() => Code::Full(v.to_vec()),
+            Quantization::Pq { m } => Code::Pq(
+                (0..m).map(|j| nearest_centroid(&self.codebooks[j], &v[self.subspace(j)]) as u8).collect(),
+            ),
+        }
+    }
+
+    pub fn decode<'a>(&self, code: CodeRef<'a>) -> Cow<'a, [f32]> {
+        match code {
+            CodeRef::Full(v) => Cow::Borrowed(v),
+            CodeRef::Int8 { min, scale, bytes } => Cow::Owned(bytes.iter().map(|&b| min + scale * b as f32).collect()),
+            CodeRef::Pq(bytes) => {
+                let mut out = Vec::with_capacity(self.dim);
+                for (j, &c) in bytes.iter().enumerate() {
+                    let d = self.subspace(j).len();
+                    out.extend_from_slice(&self.codebooks[j][c as usize * d..(c as usize + 1) * d]);
+                }
+                Cow::Owned(out)
+            }
+        }
+    }
+
+    /// Whether this is an untrained PQ quantizer and `held` vectors are
+    /// enough to train it.
+    pub fn wants_training(&self, held: usize) -> bool {
+        matches!(self.kind, Quantization::Pq { .. }) && self.codebooks.is_empty() && held >= PQ_TRAIN_SIZE
+    }
+
+    /// Train PQ codebooks on `samples`, for [`Quantizer::install`]. Leaves
+    /// the quantizer untouched, so indexes can run the k-means on a copy
+    /// of their vectors without holding their lock.
+    pub fn train(&self, samples: &[Vec<f32>]) -> Vec<Vec<f32>> {
+        let Quantization::Pq { m } = self.kind else { return Vec::new() };
+        (0..m)
+            .map(|j| {
+                let range = self.subspace(j);
+                let sub: Vec<&[f32]> = samples.iter().map(|v| &v[range.clone()]).collect();
+                kmeans(&sub, range.len(), PQ_CENTROIDS, j as u64)
+            })
+            .collect()
+    }
+
+    /// Take the codebooks from [`Quantizer::train`] and encode the
+    /// full-precision `codes`, including any stored since the training
+    /// copy was taken. Returns false, changing nothing, if the quantizer
+    /// was trained or replaced meanwhile.
+    pub fn install<'a>(&mut self, codebooks: Vec<Vec<f32>>, codes: impl IntoIterator<Item = &'a mut Code>) -> bool {
+        let Quantization::Pq { m } = self.kind else { return false };
+        if !self.codebooks.is_empty() || codebooks.len() != m {
+            return false;
+        }
+        self.codebooks = codebooks;
+        for code in codes {
+            if let Code::Full(v) = code {
+                *code = self.encode(v);
+            }
+        }
+        true
+    }
+
+    pub fn scorer(&self, metric: DistanceMetric, query: &[f32]) -> Scorer {
+        let table = match self.kind {
+            Quantization::Pq { m } if !self.codebooks.is_empty() => {
+                let mut table = vec![0.0; m * PQ_CENTROIDS];
+                for j in 0..m {
+                    let q = &query[self.subspace(j)];
+                    for (c, centroid) in self.codebooks[j].chunks_exact(q.len()).enumerate() {
+                        table[j * PQ_CENTROIDS + c] = match metric {
+                            DistanceMetric::L2 => distance::l2_squared(q, centroid),
+                            DistanceMetric::Ip | DistanceMetric::Cosine => distance::inner_product(q, centroid),
+                        };
+                    }
+                }
+                table
+            }
+            _ => Vec::new(),
+        };
+        Scorer {
+            metric,
+            query: query.to_vec(),
+            query_norm: distance::inner_product(query, query).sqrt(),
+            table,
+        }
+    }
+
+    /// Score between two stored vectors.
+    pub fn score_pair(&self, metric: DistanceMetric, a: CodeRef<'_>, b: CodeRef<'_>) -> f32 {
+        match (a, b) {
+            (CodeRef::Full(a), CodeRef::Full(b)) => distance::compute_score_normalized(metric, a, b),
+            _ => distance::compute_score(metric, &self.decode(a), &self.decode(b)),
+        }
+    }
+
+    /// Bytes of `codes` plus the codebooks.
+    pub fn size<'a>(&self, codes: impl IntoIterator<Item = CodeRef<'a>>) -> usize {
+        let books: usize = self.codebooks.iter().map(|b| std::mem::size_of_val(b.as_slice())).sum();
+        books + codes.into_iter().map(CodeRef::size).sum::<usize>()
+    }
+
+    /// Components covered by subspace `j`. Subspaces differ in size by at
+    /// most one when `m` does not divide the dimension.
+    fn subspace(&self, j: usize) -> Range<usize> {
+        let m = match self.kind {
+            Quantization::Pq { m } => m,
+            _ => 1,
+        };
+        j * self.dim / m..(j + 1) * self.dim / m
+    }
+}
+
+/// Scores one query against stored codes, with the same scale as
+/// [`distance::compute_score`]. Cosine queries and full-precision codes
+/// are expected to be unit length.
+pub(crate) struct Scorer {
+    metric: DistanceMetric,
+    query: Vec<f32>,
+    query_norm: f32,
+    /// PQ only: partial score of each centroid, `PQ_CENTROIDS` per subspace.
+    /// Inner products for IP and cosine, squared distances for L2.
+    table: Vec<f32>,
+}
+
+impl Scorer {
+    pub fn score(&self, code: CodeRef<'_>) -> f32 {
+        match code {
+            CodeRef::Full(v) => distance::compute_score_normalized(self.metric, &self.query, v),
+            CodeRef::Int8 { min, scale, bytes } => self.score_int8(min, scale, bytes),
+            CodeRef::Pq(bytes) => {
+                let sum: f32 = bytes.iter().enumerate().map(|(j, &c)| self.table[j * PQ_CENTROIDS + c as usize]).sum();
+                match self.metric {
+                    DistanceMetric::L2 => 1.0 / (1.0 + sum),
+                    // Cosine indexes hold unit vectors, so this is the cosine.
+                    DistanceMetric::Ip | DistanceMetric::Cosine => sum,
+                }
+            }
+        }
+    }
+
+    /// Score each of `codes` into `out`, batching full-precision vectors.
+    pub fn score_many(&self, codes: &[CodeRef<'_>], out: &mut [f32]) {
+        let rows: Option<Vec<&[f32]>> = codes.iter()
+            .map(|c| match *c {
+                CodeRef::Full(v) => Some(v),
+                _ => None,
+            })
+            .collect();
+        match rows {
+            Some(rows) => distance::compute_scores_normalized(self.metric, &self.query, &rows, out),
+            None => {
+                for (o, code) in out.iter_mut().zip(codes) {
+                    *o = self.score(*code);
+                }
+            }
+        }
+    }
+
+    fn score_int8(&self, min: f32, scale: f32, bytes: &[u8]) -> f32 {
+        let (mut dot, mut dist, mut norm) = (0.0f32, 0.0f32, 0.0f32);
+        for (&q, &b) in self.query.iter().zip(bytes) {
+            let x = min + scale * b as f32;
+            dot += q * x;
+            dist += (q - x) * (q - x);
+            norm += x * x;
+        }
+        match self.metric {
+            DistanceMetric::Ip => dot,
+            DistanceMetric::L2 => 1.0 / (1.0 + dist),
+            DistanceMetric::Cosine if norm == 0.0 || self.query_norm == 0.0 => 0.0,
+            DistanceMetric::Cosine => dot / (self.query_norm * norm.sqrt()),
+        }
+    }
+}
+
+/// Copies of the full-precision vectors among `codes`, to train on.
+pub(crate) fn full_vectors<'a>(codes: impl IntoIterator<Item = CodeRef<'a>>) -> Vec<Vec<f32>> {
+    codes.into_iter()
+        .filter_map(|c| match c {
+            CodeRef::Full(v) => Some(v.to_vec()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Index of the centroid in `codebook` closest to `v` by L2.
+pub(crate) fn nearest_centroid(codebook: &[f32], v: &[f32]) -> usize {
+    codebook.chunks_exact(v.len())
+        .map(|c| distance::l2_squared(v, c))
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or(0, |(i, _)| i)
+}
+
+/// Lloyd's k-means over `samples` of `dim` components, seeded from a
+/// deterministic sample. Returns up to `k` centroids back to back.
+pub(crate) fn kmeans(samples: &[&[f32]], dim: usize, k: usize, seed: u64) -> Vec<f32> {
+    let k = k.min(samples.len());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut centroids: Vec<f32> = rand::seq::index::sample(&mut rng, samples.len(), k)
+        .iter()
+        .flat_map(|i| samples[i].iter().copied())
+        .collect();
+    let mut assignment = vec![0usize; samples.len()];
+    for _ in 0..KMEANS_ITERATIONS {
+        let mut changed = false;
+        for (a, s) in assignment.iter_mut().zip(samples) {
+            let nearest = nearest_centroid(&centroids, s);
+            changed |= *a != nearest;
+            *a = nearest;
+        }
+        let mut sums = vec![0.0f32; k * dim];
+        let mut counts = vec![0usize; k];
+        for (&a, s) in assignment.iter().zip(samples) {
+            counts[a] += 1;
+            for (acc, x) in sums[a * dim..(a + 1) * dim].iter_mut().zip(s.iter()) {
+                *acc += x;
+            }
+        }
+        // Empty clusters keep their previous centroid.
+        for (c, &n) in counts.iter().enumerate().filter(|(_, &n)| n > 0) {
+            for (dst, sum) in centroids[c * dim..(c + 1) * dim].iter_mut().zip(&sums[c * dim..(c + 1) * dim]) {
+                *dst = sum / n as f32;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    centroids
+}
@@ -0,0 +1,316 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Bound;
+use ordered_float::OrderedFloat;
+use parking_lot::RwLock;
+use serde_json::Value;
+use crate::filter::{parse_datetime, Filter};
+
+/// Indexed form of a scalar value. Variants sort in declaration order, so
+/// each kind occupies one contiguous section of the key space.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Key {
+    Bool(bool),
+    Num(OrderedFloat<f64>),
+    /// RFC 3339 string, as microseconds since the epoch. Such strings are
+    /// also indexed as `Str` so exact and prefix lookups still see them.
+    Time(i64),
+    Str(String),
+}
+
+const NUM_START: Key = Key::Num(OrderedFloat(f64::NEG_INFINITY));
+const TIME_START: Key = Key::Time(i64::MIN);
+const STR_START: Key = Key::Str(String::new());
+
+/// Tolerance used by the `must` filter for floating point equality.
+const NUM_EPSILON: f64 = 1e-9;
+
+fn keys_of(value: &Value, out: &mut Vec<Key>) {
+    match value {
+        Value::Bool(b) => out.push(Key::Bool(*b)),
+        Value::Number(n) => {
+            if let Some(f) = n.as_f64() {
+                out.push(Key::Num(OrderedFloat(f)));
+            }
+        }
+        Value::String(s) => {
+            if let Some(t) = parse_datetime(s) {
+                out.push(Key::Time(t));
+            }
+            out.push(Key::Str(s.clone()));
+        }
+        // List fields: every element is indexed.
+        Value::Array(items) => items.iter().for_each(|v| keys_of(v, out)),
+        _ => {}
+    }
+}
+
+/// Sorted, deduplicated keys of a field value.
+fn sorted_keys(value: Option<&Value>) -> Vec<Key> {
+    let mut keys = Vec::new();
+    if let Some(v) = value {
+        keys_of(v, &mut keys);
+    }
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Labels per key of the given field values, in key order: the grouping
+/// [`ScalarIndex::groups`] returns, for fields without an index.
+pub(crate) fn group_values<'a>(values: impl IntoIterator<Item = (u64, &'a Value)>) -> Vec<(Key, Vec<u64>)> {
+    let mut groups: BTreeMap<Key, Vec<u64>> = BTreeMap::new();
+    for (label, value) in values {
+        for key in sorted_keys(Some(value)) {
+            groups.entry(key).or_default().push(label);
+        }
+    }
+    groups.into_iter().collect()
+}
+
+/// Scalar index over one field: an inverted index from value to labels,
+/// kept ordered so that equality, prefix and range filters are all seeks.
+///
+/// Strings, bools and list elements are looked up by value; numbers and
+/// RFC 3339 datetimes additionally serve ranges in numeric and time order.
+/// Lookups may return a superset of the matching labels (e.g. list fields
+/// under `prefix`); callers re-check candidates with [`Filter::matches`].
+pub struct ScalarIndex {
+    inner: RwLock<ScalarInner>,
+}
+
+#[derive(Default)]
+struct ScalarInner {
+    postings: BTreeMap<Key, HashSet<u64>>,
+    keys: HashMap<u64, Vec<Key>>,
+}
+
+impl ScalarInner {
+    fn unlink(&mut self, label: u64) {
+        let Some(keys) = self.keys.remove(&label) else { return };
+        for key in keys {
+            if let Some(set) = self.postings.get_mut(&key) {
+                set.remove(&label);
+                if set.is_empty() {
+                    self.postings.remove(&key);
+                }
+            }
+        }
+    }
+
+    fn collect(&self, lo: Bound<Key>, hi: Bound<Key>, out: &mut HashSet<u64>) {
+        if range_is_empty(&lo, &hi) {
+            return;
+        }
+        for set in self.postings.range((lo, hi)).map(|(_, s)| s) {
+            out.extend(set.iter().copied());
+        }
+    }
+}
+
+impl ScalarIndex {
+    pub fn new() -> Self {
+        Self { inner: RwLock::new(ScalarInner::default()) }
+    }
+
+    /// Index the field value of `label`, replacing any previous value.
+    /// `None` (field absent) leaves the label unindexed.
+    pub fn insert(&self, label: u64, value: Option<&Value>) {
+        let keys = sorted_keys(value);
+        let mut inner = self.inner.write();
+        inner.unlink(label);
+        if keys.is_empty() {
+            return;
+        }
+        for key in &keys {
+            inner.postings.entry(key.clone()).or_default().insert(label);
+        }
+        inner.keys.insert(label, keys);
+    }
+
+    pub fn delete(&self, label: u64) {
+        self.inner.write().unlink(label);
+    }
+
+    /// Number of labels with an indexed value.
+    pub fn len(&self) -> usize {
+        self.inner.read().keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Labels whose value equals any of `values`, or `None` if some value
+    /// cannot be looked up.
+    pub fn equal(&self, values: &[Value]) -> Option<HashSet<u64>> {
+        let inner = self.inner.read();
+        let mut out = HashSet::new();
+        for v in values {
+            match v {
+                Value::Bool(b) => inner.collect(Bound::Included(Key::Bool(*b)), Bound::Included(Key::Bool(*b)), &mut out),
+                Value::Number(n) => {
+                    let f = n.as_f64()?;
+                    inner.collect(
+                        Bound::Included(Key::Num(OrderedFloat(f - NUM_EPSILON))),
+                        Bound::Included(Key::Num(OrderedFloat(f + NUM_EPSILON))),
+                        &mut out,
+                    );
+                }
+                Value::String(s) => inner.collect(Bound::Included(Key::Str(s.clone())), Bound::Included(Key::Str(s.clone())), &mut out),
+                _ => return None,
+            }
+        }
+        Some(out)
+    }
+
+    /// Labels per indexed key, in key order, keeping only `allowed` ones.
+    pub(crate) fn groups(&self, allowed: Option<&HashSet<u64>>) -> Vec<(Key, Vec<u64>)> {
+        let inner = self.inner.read();
+        inner.postings.iter()
+            .map(|(key, set)| {
+                let labels = set.iter().copied().filter(|l| allowed.is_none_or(|a| a.contains(l))).collect();
+                (key.clone(), labels)
+            })
+            .filter(|(_, labels): &(Key, Vec<u64>)| !labels.is_empty())
+            .collect()
+    }
+
+    /// Labels with a string value starting with `prefix`.
+    pub fn prefix(&self, prefix: &str) -> HashSet<u64> {
+        let inner = self.inner.read();
+        inner.postings.range(Key::Str(prefix.to_string())..)
+            .take_while(|(k, _)| matches!(k, Key::Str(s) if s.starts_with(prefix)))
+            .flat_map(|(_, set)| set.iter().copied())
+            .collect()
+    }
+
+    /// Labels whose value lies within the given bounds, compared the way
+    /// the `range` filter compares them. `None` if there are no bounds.
+    pub fn range(
+        &self,
+        gt: Option<&Value>,
+        gte: Option<&Value>,
+        lt: Option<&Value>,
+        lte: Option<&Value>,
+    ) -> Option<HashSet<u64>> {
+        let bounds: Vec<&Value> = [gt, gte, lt, lte].into_iter().flatten().collect();
+        if bounds.is_empty() {
+            return None;
+        }
+        let inner = self.inner.read();
+        let mut out = HashSet::new();
+
+        if bounds.iter().all(|b| b.is_number()) {
+            let num = |v: &Value| Key::Num(OrderedFloat(v.as_f64().unwrap_or(f64::NAN)));
+            let (lo, hi) = bounds_for(gt, gte, lt, lte, num);
+            inner.collect(or_start(lo, NUM_START), or_end(hi, TIME_START), &mut out);
+        } else if bounds.iter().all(|b| b.is_string()) {
+            let as_str = |v: &Value| v.as_str().unwrap_or_default().to_string();
+            // Strings compare lexicographically ...
+            let (lo, hi) = bounds_for(gt, gte, lt, lte, |v| Key::Str(as_str(v)));
+            inner.collect(or_start(lo, STR_START), hi, &mut out);
+            // ... unless both sides are datetimes, which compare by instant.
+            let dates = bounds.iter().filter(|b| b.as_str().and_then(parse_datetime).is_some()).count();
+            if dates == bounds.len() {
+                let time = |v: &Value| Key::Time(v.as_str().and_then(parse_datetime).unwrap_or_default());
+                let (lo, hi) = bounds_for(gt, gte, lt, lte, time);
+                inner.collect(or_start(lo, TIME_START), or_end(hi, STR_START), &mut out);
+            } else if dates > 0 {
+                // Datetime values would compare by instant against some
+                // bounds only; keep them all for the re-check.
+                inner.collect(Bound::Included(TIME_START), Bound::Excluded(STR_START), &mut out);
+            }
+        }
+        // Bounds of mixed or other types never compare, so nothing matches.
+        Some(out)
+    }
+
+    /// Labels whose value lies outside `[gte, lte]`, as the `range_out`
+    /// filter sees it.
+    pub fn range_out(&self, gte: Option<&Value>, lte: Option<&Value>) -> HashSet<u64> {
+        let mut out = self.range(None, None, gte, None).unwrap_or_default();
+        out.extend(self.range(lte, None, None, None).unwrap_or_default());
+        out
+    }
+}
+
+impl Default for ScalarIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Candidate labels for `filter` from the scalar indexes, or `None` when
+/// the filter cannot narrow the search (unindexed fields, negations, ...).
+/// The result is a superset of the matches.
+pub fn plan_filter(indexes: &HashMap<String, ScalarIndex>, filter: &Filter) -> Option<HashSet<u64>> {
+    match filter {
+        Filter::Must { field, values } => indexes.get(field)?.equal(values),
+        Filter::Range { field, gt, gte, lt, lte } => {
+            indexes.get(field)?.range(gt.as_ref(), gte.as_ref(), lt.as_ref(), lte.as_ref())
+        }
+        Filter::RangeOut { field, gte, lte } => Some(indexes.get(field)?.range_out(gte.as_ref(), lte.as_ref())),
+        Filter::Prefix { field, prefix } => Some(indexes.get(field)?.prefix(prefix)),
+        Filter::Subtree { field, path } => Some(indexes.get(field)?.prefix(path.trim_end_matches('/'))),
+        Filter::And(filters) => filters.iter()
+            .filter_map(|f| plan_filter(indexes, f))
+            .reduce(|a, b| if a.len() <= b.len() { &a & &b } else { &b & &a }),
+        Filter::Or(filters) => filters.iter().try_fold(HashSet::new(), |mut acc, f| {
+            acc.extend(plan_filter(indexes, f)?);
+            Some(acc)
+        }),
+        Filter::MustNot { .. }
+        | Filter::Contains { .. }
+        | Filter::Regex { .. }
+        | Filter::Exists { .. }
+        | Filter::NotExists { .. }
+        | Filter::GeoRange { .. } => None,
+    }
+}
+
+fn bounds_for(
+    gt: Option<&Value>,
+    gte: Option<&Value>,
+    lt: Option<&Value>,
+    lte: Option<&Value>,
+    key: impl Fn(&Value) -> Key,
+) -> (Bound<Key>, Bound<Key>) {
+    let lo = match (gt.map(&key), gte.map(&key)) {
+        (Some(a), Some(b)) if a >= b => Bound::Excluded(a),
+        (_, Some(b)) => Bound::Included(b),
+        (Some(a), None) => Bound::Excluded(a),
+        (None, None) => Bound::Unbounded,
+    };
+    let hi = match (lt.map(&key), lte.map(&key)) {
+        (Some(a), Some(b)) if a <= b => Bound::Excluded(a),
+        (_, Some(b)) => Bound::Included(b),
+        (Some(a), None) => Bound::Excluded(a),
+        (None, None) => Bound::Unbounded,
+    };
+    (lo, hi)
+}
+
+fn or_start(lo: Bound<Key>, start: Key) -> Bound<Key> {
+    match lo {
+        Bound::Unbounded => Bound::Included(start),
+        b => b,
+    }
+}
+
+fn or_end(hi: Bound<Key>, next_section: Key) -> Bound<Key> {
+    match hi {
+        Bound::Unbounded => Bound::Excluded(next_section),
+        b => b,
+    }
+}
+
+/// `BTreeMap::range` panics on inverted or empty-exclusive ranges.
+fn range_is_empty(lo: &Bound<Key>, hi: &Bound<Key>) -> bool {
+    match (lo, hi) {
+        (Bound::Included(a), Bound::Included(b)) => a > b,
+        (Bound::Included(a), Bound::Excluded(b))
+        | (Bound::Excluded(a), Bound::Included(b))
+        | (Bound::Excluded(a), Bound::Excluded(b)) => a >= b,
+        _ => false,
+    }
+}
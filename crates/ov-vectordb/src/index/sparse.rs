@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::path::Path;
+use parking_lot::RwLock;
+use crate::error::{Result, VectorDbError};
+use super::SearchResult;
+
+/// Sparse vector: term (or token id) -> weight.
+pub type SparseVector = HashMap<String, f32>;
+
+/// Inverted index over sparse vectors, scored by inner product.
+///
+/// Only terms shared with the query are visited, so search cost follows
+/// the posting lists of the query terms rather than the collection size.
+pub struct SparseIndex {
+    inner: RwLock<SparseInner>,
+}
+
+#[derive(Default)]
+struct SparseInner {
+    /// term -> (label, weight) postings.
+    postings: HashMap<String, Vec<(u64, f32)>>,
+    /// label -> stored vector, needed to unlink postings on delete.
+    vectors: HashMap<u64, SparseVector>,
+}
+
+impl SparseInner {
+    fn unlink(&mut self, label: u64) {
+        let Some(old) = self.vectors.remove(&label) else { return };
+        for term in old.keys() {
+            if let Some(list) = self.postings.get_mut(term) {
+                list.retain(|&(l, _)| l != label);
+                if list.is_empty() {
+                    self.postings.remove(term);
+                }
+            }
+        }
+    }
+
+    fn link(&mut self, label: u64, vector: SparseVector) {
+        for (term, &weight) in &vector {
+            self.postings.entry(term.clone()).or_default().push((label, weight));
+        }
+        self.vectors.insert(label, vector);
+    }
+}
+
+impl SparseIndex {
+    pub fn new() -> Self {
+        Self { inner: RwLock::new(SparseInner::default()) }
+    }
+
+    /// Insert or replace the vector for `label`. Zero weights are dropped.
+    pub fn insert(&self, label: u64, vector: &SparseVector) -> Result<()> {
+        if let Some((term, w)) = vector.iter().find(|(_, w)| !w.is_finite()) {
+            return Err(VectorDbError::InvalidConfig(format!(
+                "sparse weight for {term:?} is not finite: {w}"
+            )));
+        }
+        let vector: SparseVector = vector.iter()
+            .filter(|(_, &w)| w != 0.0)
+            .map(|(t, &w)| (t.clone(), w))
+            .collect();
+        let mut inner = self.inner.write();
+        inner.unlink(label);
+        if !vector.is_empty() {
+            inner.link(label, vector);
+        }
+        Ok(())
+    }
+
+    pub fn delete(&self, label: u64) -> Result<()> {
+        self.inner.write().unlink(label);
+        Ok(())
+    }
+
+    /// Top-k labels by inner product with `query`. Labels sharing no term
+    /// with the query are never returned.
+    pub fn search(&self, query: &SparseVector, top_k: usize) -> Result<SearchResult> {
+        if top_k == 0 {
+            return Ok(SearchResult::empty());
+        }
+        let inner = self.inner.read();
+        let mut acc: HashMap<u64, f32> = HashMap::new();
+        for (term, &qw) in query {
+            if let Some(list) = inner.postings.get(term) {
+                for &(label, w) in list {
+                    *acc.entry(label).or_insert(0.0) += qw * w;
+                }
+            }
+        }
+
+        let mut scored: Vec<(u64, f32)> = acc.into_iter().collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal).then(a.0.cmp(&b.0)));
+        scored.truncate(top_k);
+
+        Ok(SearchResult {
+            ids: scored.iter().map(|s| s.0).collect(),
+            scores: scored.iter().map(|s| s.1).collect(),
+        })
+    }
+
+    /// Number of labels with a non-empty vector.
+    pub fn len(&self) -> usize {
+        self.inner.read().vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of distinct terms.
+    pub fn term_count(&self) -> usize {
+        self.inner.read().postings.len()
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        std::fs::create_dir_all(path)?;
+        let inner = self.inner.read();
+        let json = serde_json::to_vec(&inner.vectors).map_err(|e| VectorDbError::Serialization(e.to_string()))?;
+        std::fs::write(path.join("sparse_index.json"), json)?;
+        Ok(())
+    }
+
+    /// Load vectors saved by [`SparseIndex::save`], rebuilding the postings.
+    pub fn load(&mut self, path: &Path) -> Result<()> {
+        let data = std::fs::read(path.join("sparse_index.json"))?;
+        let vectors: HashMap<u64, SparseVector> = serde_json::from_slice(&data)
+            .map_err(|e| VectorDbError::Serialization(e.to_string()))?;
+        let mut inner = SparseInner::default();
+        for (label, vector) in vectors {
+            inner.link(label, vector);
+        }
+        *self.inner.write() = inner;
+        Ok(())
+    }
+}
+
+impl Default for SparseIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -22,6 +22,25 @@ pub trait VectorIndex: Send + Sync {
     /// Search for the top-k nearest vectors.
     fn search(&self, query: &[f32], top_k: usize) -> Result<SearchResult>;
 
+    /// Search for the top-k nearest vectors whose label passes `allow`.
+    /// Fewer than `top_k` hits means fewer labels in the index pass.
+    ///
+    /// The default over-fetches with [`VectorIndex::search`], widening the
+    /// request until enough hits pass or the index has nothing more.
+    fn search_filtered(&self, query: &[f32], top_k: usize, allow: &dyn Fn(u64) -> bool) -> Result<SearchResult> {
+        let mut fetch = top_k;
+        loop {
+            let mut hits = self.search(query, fetch)?;
+            let exhausted = hits.len() < fetch || fetch >= self.len();
+            hits.retain(allow);
+            if hits.len() >= top_k || exhausted {
+                hits.truncate(top_k);
+                return Ok(hits);
+            }
+            fetch = fetch.saturating_mul(4);
+        }
+    }
+
     /// Get the number of vectors in the index.
     fn len(&self) -> usize;
 
@@ -46,4 +65,10 @@ pub trait VectorIndex: Send + Sync {
     fn needs_rebuild(&self) -> bool {
         false
     }
+
+    /// Drop deleted entries still held by the index, returning how many
+    /// were removed. Indexes that remove entries on delete have none.
+    fn vacuum(&self) -> Result<usize> {
+        Ok(0)
+    }
 }
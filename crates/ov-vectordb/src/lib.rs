@@ -2,6 +2,9 @@
 //!
 //! Provides HNSW and Flat (brute-force) vector indexes, collection management,
 //! KV store, metadata management, project management, and filter support.
+//!
+//! [`VectorIndex`] is the label-based index trait; [`ContextIndex`] is the
+//! Context-level API served by any such index through [`ContextIndexAdapter`].
 
 pub mod distance;
 pub mod filter;
@@ -11,8 +14,10 @@ pub mod meta;
 pub mod collection;
 pub mod project;
 pub mod error;
+pub mod context_index;
 
-pub use collection::{Collection, CollectionConfig, FieldDef, FieldType};
-pub use index::{VectorIndex, FlatIndex, HnswIndex};
+pub use collection::{Collection, CollectionConfig, Durability, FieldDef, FieldType, SchemaIssue, SchemaMode};
+pub use index::{VectorIndex, FlatIndex, HnswIndex, SparseIndex, FullTextIndex};
 pub use project::{Project, ProjectGroup};
 pub use error::{VectorDbError, Result};
+pub use context_index::{ContextIndex, ContextIndexAdapter};
@@ -3,6 +3,7 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
+use crate::index::Quantization;
 use crate::store::FileStore;
 
 /// Volatile (in-memory) metadata dictionary.
@@ -120,5 +121,21 @@ pub struct IndexMeta {
     #[serde(default)]
     pub scalar_index_fields: Vec<String>,
     #[serde(default)]
+    pub text_fields: Vec<String>,
+    /// Vector field of a dense index; absent in metadata written before
+    /// collections had several vector fields.
+    #[serde(default)]
+    pub vector_field: Option<String>,
+    #[serde(default)]
+    pub quantization: Quantization,
+    #[serde(default)]
+    pub rescore: Option<usize>,
+    #[serde(default)]
+    pub mmap: bool,
+    #[serde(default)]
+    pub nlist: Option<usize>,
+    #[serde(default)]
+    pub nprobe: Option<usize>,
+    #[serde(default)]
     pub description: String,
 }
@@ -104,7 +104,7 @@ fn test_persistence_after_deletes() {
         coll.upsert_data(&data).unwrap();
         // Delete first 250
         let ids: Vec<_> = (0..250).map(|i| json!(i)).collect();
-        coll.delete_data(&ids);
+        coll.delete_data(&ids).unwrap();
         assert_eq!(coll.count(), 250);
     }
     let coll2 = Collection::with_path(config, path).unwrap();
@@ -407,7 +407,7 @@ fn test_collection_delete_half_then_search() {
     }).collect();
     coll.upsert_data(&data).unwrap();
     let del_ids: Vec<_> = (0..100).map(|i| json!(i)).collect();
-    coll.delete_data(&del_ids);
+    coll.delete_data(&del_ids).unwrap();
     assert_eq!(coll.count(), 100);
     let search = coll.search_by_vector("idx", &[1.0, 0.0, 0.0, 0.0], 200, 0, None).unwrap();
     assert_eq!(search.data.len(), 100);
@@ -738,8 +738,8 @@ fn test_collection_double_delete_same_id() {
         ("tags".into(), json!("")),
     ])];
     coll.upsert_data(&data).unwrap();
-    coll.delete_data(&[json!(1)]);
-    coll.delete_data(&[json!(1)]); // Double delete should not panic
+    coll.delete_data(&[json!(1)]).unwrap();
+    coll.delete_data(&[json!(1)]).unwrap(); // Double delete should not panic
     assert_eq!(coll.count(), 0);
 }
 
@@ -2,14 +2,14 @@
 //! Ported from Python tests + new Rust-specific tests (~100 tests total)
 
 use ov_vectordb::{
-    Collection, CollectionConfig, FieldDef, FieldType,
-    index::{FlatIndex, HnswIndex, VectorIndex},
-    distance::{self, DistanceMetric},
+    Collection, CollectionConfig, Durability, FieldDef, FieldType, SchemaMode,
+    index::{FlatIndex, HnswIndex, IvfIndex, VectorIndex, SparseIndex, SparseVector, SearchResult, Fusion, fuse, FullTextIndex, tokenize, ScalarIndex, plan_filter, Quantization, PQ_TRAIN_SIZE},
+    distance::{self, DistanceMetric, SimdLevel},
     filter::Filter,
     store::{MemoryKvStore, KvStore, MultiTableStore, FileStore, BytesRow, BytesRowSchema, FieldSchema, SchemaFieldType},
-    meta::{VolatileDict, PersistentDict},
+    meta::{IndexMeta, VolatileDict, PersistentDict},
     project::{Project, ProjectGroup},
-    collection::IndexConfig,
+    collection::{Aggregation, AggregationResult, Bucket, FieldStats, GroupBy, IndexConfig, IndexQuery, IndexStatus, HybridQuery, Mmr, ScoreBoost},
     error::VectorDbError,
 };
 use std::collections::HashMap;
@@ -728,7 +728,7 @@ fn test_collection_delete() {
         HashMap::from([("id".into(), json!(2)), ("embedding".into(), json!([0.0, 1.0, 0.0, 0.0])), ("category".into(), json!("B")), ("score".into(), json!(20))]),
     ];
     coll.upsert_data(&data).unwrap();
-    coll.delete_data(&[json!(1)]);
+    coll.delete_data(&[json!(1)]).unwrap();
     assert_eq!(coll.count(), 1);
 }
 
@@ -740,7 +740,7 @@ fn test_collection_delete_all() {
         HashMap::from([("id".into(), json!(1)), ("embedding".into(), json!([1.0, 0.0, 0.0, 0.0])), ("category".into(), json!("A")), ("score".into(), json!(10))]),
     ];
     coll.upsert_data(&data).unwrap();
-    coll.delete_all_data();
+    coll.delete_all_data().unwrap();
     assert_eq!(coll.count(), 0);
 }
 
@@ -813,8 +813,8 @@ fn test_collection_empty_operations() {
     coll.create_index("idx", IndexConfig::default()).unwrap();
     let search = coll.search_by_vector("idx", &[1.0, 0.0, 0.0, 0.0], 10, 0, None).unwrap();
     assert!(search.data.is_empty());
-    coll.delete_data(&[json!(1)]);
-    coll.delete_all_data();
+    coll.delete_data(&[json!(1)]).unwrap();
+    coll.delete_all_data().unwrap();
 }
 
 #[test]
@@ -856,6 +856,7 @@ fn test_collection_hnsw_index() {
         index_type: "hnsw".to_string(),
         distance: DistanceMetric::Cosine,
         scalar_index_fields: vec![],
+        ..Default::default()
     };
     coll.create_index("hnsw_idx", cfg).unwrap();
     let data: Vec<_> = (0..50).map(|i| {
@@ -1161,3 +1162,2900 @@ fn test_multi_table_clear() {
     assert!(store.read(&["k1".into()], "t1")[0].is_none());
     assert!(store.read(&["k2".into()], "t2")[0].is_none());
 }
+
+// ============================================================
+// Context Index Adapter Tests
+// ============================================================
+
+fn ctx_with_vector(uri: &str, vector: Vec<f32>) -> ov_core::context::Context {
+    let mut ctx = ov_core::context::Context::new(uri, "abstract");
+    ctx.vector = Some(vector);
+    ctx
+}
+
+#[tokio::test]
+async fn test_context_adapter_insert_search() {
+    use ov_vectordb::{ContextIndex, ContextIndexAdapter};
+    let adapter = ContextIndexAdapter::new(std::sync::Arc::new(FlatIndex::new(2, DistanceMetric::Cosine)));
+    let a = ctx_with_vector("viking://resources/a", vec![1.0, 0.0]);
+    let b = ctx_with_vector("viking://resources/b", vec![0.0, 1.0]);
+    let embed = ov_core::types::EmbedResult::default();
+    let id_a = adapter.insert(&a, &embed).await.unwrap();
+    adapter.insert(&b, &embed).await.unwrap();
+    assert_eq!(id_a, a.id.to_string());
+    assert_eq!(adapter.len(), 2);
+
+    let hits = adapter.search(&[1.0, 0.1], 1).await.unwrap();
+    assert_eq!(hits[0].0, id_a);
+    assert_eq!(adapter.uri_of(&id_a).as_deref(), Some("viking://resources/a"));
+}
+
+#[tokio::test]
+async fn test_context_adapter_delete_by_uri_and_id() {
+    use ov_vectordb::{ContextIndex, ContextIndexAdapter};
+    let adapter = ContextIndexAdapter::new(std::sync::Arc::new(HnswIndex::new(2, DistanceMetric::Ip)));
+    let a = ctx_with_vector("viking://resources/a", vec![1.0, 0.0]);
+    let b = ctx_with_vector("viking://resources/b", vec![0.0, 1.0]);
+    let embed = ov_core::types::EmbedResult::default();
+    adapter.insert(&a, &embed).await.unwrap();
+    adapter.insert(&b, &embed).await.unwrap();
+
+    adapter.delete("viking://resources/a").await.unwrap();
+    adapter.delete(&b.id.to_string()).await.unwrap();
+    adapter.delete("viking://resources/unknown").await.unwrap();
+    assert!(adapter.is_empty());
+    assert!(adapter.search(&[1.0, 0.0], 5).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_context_adapter_replaces_same_uri() {
+    use ov_vectordb::{ContextIndex, ContextIndexAdapter};
+    let adapter = ContextIndexAdapter::new(std::sync::Arc::new(FlatIndex::new(2, DistanceMetric::Ip)));
+    let old = ctx_with_vector("viking://resources/a", vec![1.0, 0.0]);
+    let new = ctx_with_vector("viking://resources/a", vec![0.0, 1.0]);
+    let embed = ov_core::types::EmbedResult::default();
+    adapter.insert(&old, &embed).await.unwrap();
+    adapter.insert(&new, &embed).await.unwrap();
+    assert_eq!(adapter.len(), 1);
+    assert_eq!(adapter.index().len(), 1);
+    assert!(adapter.label_of(&old.id.to_string()).is_none());
+}
+
+#[tokio::test]
+async fn test_context_adapter_prefers_embedding_and_checks_dimension() {
+    use ov_vectordb::{ContextIndex, ContextIndexAdapter};
+    let adapter = ContextIndexAdapter::new(std::sync::Arc::new(FlatIndex::new(2, DistanceMetric::Ip)));
+    let ctx = ov_core::context::Context::new("viking://resources/a", "abstract");
+    let missing = ov_core::types::EmbedResult::default();
+    assert!(adapter.insert(&ctx, &missing).await.is_err());
+    let wrong = ov_core::types::EmbedResult { dense_vector: Some(vec![1.0]), sparse_vector: None };
+    assert!(adapter.insert(&ctx, &wrong).await.is_err());
+    let ok = ov_core::types::EmbedResult { dense_vector: Some(vec![1.0, 0.0]), sparse_vector: None };
+    adapter.insert(&ctx, &ok).await.unwrap();
+    assert!(adapter.ensure_collection("ctx", 2).await.is_ok());
+    assert!(adapter.ensure_collection("ctx", 3).await.is_err());
+}
+
+/// Flat index whose inserts fail while `fail` is set.
+struct FlakyIndex {
+    inner: FlatIndex,
+    fail: std::sync::atomic::AtomicBool,
+}
+
+impl VectorIndex for FlakyIndex {
+    fn insert(&self, label: u64, vector: &[f32]) -> ov_vectordb::error::Result<()> {
+        if self.fail.load(std::sync::atomic::Ordering::SeqCst) {
+            return Err(VectorDbError::Storage("insert failed".into()));
+        }
+        self.inner.insert(label, vector)
+    }
+    fn delete(&self, label: u64) -> ov_vectordb::error::Result<()> {
+        self.inner.delete(label)
+    }
+    fn search(&self, query: &[f32], top_k: usize) -> ov_vectordb::error::Result<SearchResult> {
+        self.inner.search(query, top_k)
+    }
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+    fn dimension(&self) -> usize {
+        self.inner.dimension()
+    }
+    fn metric(&self) -> DistanceMetric {
+        self.inner.metric()
+    }
+    fn save(&self, path: &std::path::Path) -> ov_vectordb::error::Result<()> {
+        self.inner.save(path)
+    }
+    fn load(&mut self, path: &std::path::Path) -> ov_vectordb::error::Result<()> {
+        self.inner.load(path)
+    }
+}
+
+#[tokio::test]
+async fn test_context_adapter_failed_insert_keeps_labels() {
+    use ov_vectordb::{ContextIndex, ContextIndexAdapter};
+    use std::sync::atomic::Ordering;
+    let index = std::sync::Arc::new(FlakyIndex { inner: FlatIndex::new(2, DistanceMetric::Ip), fail: false.into() });
+    let adapter = ContextIndexAdapter::new(index.clone());
+    let old = ctx_with_vector("viking://resources/a", vec![1.0, 0.0]);
+    let new = ctx_with_vector("viking://resources/a", vec![0.0, 1.0]);
+    let embed = ov_core::types::EmbedResult::default();
+    adapter.insert(&old, &embed).await.unwrap();
+
+    index.fail.store(true, Ordering::SeqCst);
+    assert!(adapter.insert(&new, &embed).await.is_err());
+    assert_eq!(adapter.len(), 1);
+    assert!(adapter.label_of(&new.id.to_string()).is_none());
+    assert_eq!(adapter.label_of("viking://resources/a"), adapter.label_of(&old.id.to_string()));
+    assert_eq!(adapter.search(&[1.0, 0.0], 5).await.unwrap()[0].0, old.id.to_string());
+
+    index.fail.store(false, Ordering::SeqCst);
+    adapter.insert(&new, &embed).await.unwrap();
+    assert_eq!(adapter.len(), 1);
+    assert_eq!(index.len(), 1);
+    assert_eq!(adapter.search(&[1.0, 0.0], 5).await.unwrap()[0].0, new.id.to_string());
+}
+
+// ============================================================
+// String Primary Key Tests
+// ============================================================
+
+fn make_string_pk_config() -> CollectionConfig {
+    CollectionConfig {
+        name: "spk".into(),
+        fields: vec![
+            FieldDef { name: "id".into(), field_type: FieldType::String, is_primary_key: true, dim: None },
+            FieldDef { name: "vec".into(), field_type: FieldType::Vector, is_primary_key: false, dim: Some(2) },
+            FieldDef { name: "n".into(), field_type: FieldType::Int64, is_primary_key: false, dim: None },
+        ],
+        description: String::new(),
+    }
+}
+
+#[test]
+fn test_string_pk_distinct_labels() {
+    let coll = Collection::new(make_string_pk_config());
+    coll.create_index("idx", IndexConfig::default()).unwrap();
+    let data: Vec<_> = (0..500).map(|i| HashMap::from([
+        ("id".into(), json!(format!("doc-{i}"))),
+        ("vec".into(), json!([1.0, i as f64])),
+        ("n".into(), json!(i)),
+    ])).collect();
+    coll.upsert_data(&data).unwrap();
+    assert_eq!(coll.count(), 500);
+    let fetched = coll.fetch_data(&[json!("doc-7"), json!("doc-499"), json!("missing")]);
+    assert_eq!(fetched[0].as_ref().unwrap()["n"], json!(7));
+    assert_eq!(fetched[1].as_ref().unwrap()["n"], json!(499));
+    assert!(fetched[2].is_none());
+
+    let result = coll.search_by_vector("idx", &[1.0, 0.0], 1, 0, None).unwrap();
+    assert_eq!(result.data[0].id, json!("doc-0"));
+}
+
+#[test]
+fn test_string_pk_upsert_delete_reinsert() {
+    let coll = Collection::new(make_string_pk_config());
+    let row = |v: i64| vec![HashMap::from([
+        ("id".into(), json!("a")),
+        ("vec".into(), json!([1.0, 0.0])),
+        ("n".into(), json!(v)),
+    ])];
+    coll.upsert_data(&row(1)).unwrap();
+    coll.upsert_data(&row(2)).unwrap();
+    assert_eq!(coll.count(), 1);
+    coll.delete_data(&[json!("a")]).unwrap();
+    assert_eq!(coll.count(), 0);
+    assert!(coll.fetch_data(&[json!("a")])[0].is_none());
+    coll.upsert_data(&row(3)).unwrap();
+    assert_eq!(coll.fetch_data(&[json!("a")])[0].as_ref().unwrap()["n"], json!(3));
+}
+
+#[test]
+fn test_string_pk_concurrent_upserts_share_label() {
+    let coll = std::sync::Arc::new(Collection::new(make_string_pk_config()));
+    let writers: Vec<_> = (0..8)
+        .map(|t| {
+            let coll = coll.clone();
+            std::thread::spawn(move || {
+                for i in 0..50 {
+                    let record = HashMap::from([
+                        ("id".into(), json!(format!("k{}", i % 10))),
+                        ("vec".into(), json!([1.0, 0.0])),
+                        ("n".into(), json!(t)),
+                    ]);
+                    coll.upsert_data(&[record]).unwrap();
+                }
+            })
+        })
+        .collect();
+    for w in writers {
+        w.join().unwrap();
+    }
+    assert_eq!(coll.count(), 10);
+    assert_eq!(coll.count_by_filter(None).unwrap(), 10);
+}
+
+#[test]
+fn test_string_pk_labels_survive_recover() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("spk");
+    {
+        let coll = Collection::with_path(make_string_pk_config(), path.clone()).unwrap();
+        let data: Vec<_> = ["x", "y", "z"].iter().enumerate().map(|(i, id)| HashMap::from([
+            ("id".into(), json!(id)),
+            ("vec".into(), json!([1.0, 0.0])),
+            ("n".into(), json!(i)),
+        ])).collect();
+        coll.upsert_data(&data).unwrap();
+        coll.delete_data(&[json!("y")]).unwrap();
+    }
+    let coll = Collection::with_path(make_string_pk_config(), path).unwrap();
+    assert_eq!(coll.count(), 2);
+    assert_eq!(coll.fetch_data(&[json!("z")])[0].as_ref().unwrap()["n"], json!(2));
+    assert!(coll.fetch_data(&[json!("y")])[0].is_none());
+
+    // New keys after reopen must not reuse an existing label.
+    coll.upsert_data(&[HashMap::from([
+        ("id".into(), json!("w")),
+        ("vec".into(), json!([0.0, 1.0])),
+        ("n".into(), json!(9)),
+    ])]).unwrap();
+    assert_eq!(coll.count(), 3);
+    assert_eq!(coll.fetch_data(&[json!("x")])[0].as_ref().unwrap()["n"], json!(0));
+}
+
+#[test]
+fn test_string_pk_labels_saved_with_records() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("spk");
+    let data: Vec<_> = ["x", "y"].iter().enumerate().map(|(i, id)| HashMap::from([
+        ("id".into(), json!(id)),
+        ("vec".into(), json!([1.0, 0.0])),
+        ("n".into(), json!(i)),
+    ])).collect();
+    {
+        let coll = Collection::with_path(make_string_pk_config(), path.clone()).unwrap();
+        coll.upsert_data(&data).unwrap();
+        coll.checkpoint().unwrap();
+    }
+    assert!(!path.join("pk_labels.json").exists());
+    let snapshot: serde_json::Value = serde_json::from_slice(&std::fs::read(path.join("records.json")).unwrap()).unwrap();
+    assert_eq!(snapshot["records"].as_array().unwrap().len(), 2);
+    assert_eq!(snapshot["pk_labels"].as_object().unwrap().len(), 2);
+
+    // Snapshots written as two files still load.
+    std::fs::write(path.join("records.json"), serde_json::to_vec(&snapshot["records"]).unwrap()).unwrap();
+    std::fs::write(path.join("pk_labels.json"), serde_json::to_vec(&snapshot["pk_labels"]).unwrap()).unwrap();
+    let coll = Collection::with_path(make_string_pk_config(), path).unwrap();
+    assert_eq!(coll.count(), 2);
+    assert_eq!(coll.fetch_data(&[json!("y")])[0].as_ref().unwrap()["n"], json!(1));
+}
+
+#[test]
+fn test_string_pk_legacy_hashed_labels_are_relabeled() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("legacy");
+    std::fs::create_dir_all(&path).unwrap();
+    // records.json as written before the key map existed: hashed labels, no pk_labels.json
+    let legacy = json!([
+        {"label": 18446744073709551000u64, "vector": [1.0, 0.0], "fields": {"id": "a", "n": 1}},
+        {"label": 9223372036854775809u64, "vector": [0.0, 1.0], "fields": {"id": "b", "n": 2}},
+    ]);
+    std::fs::write(path.join("records.json"), serde_json::to_vec(&legacy).unwrap()).unwrap();
+
+    let coll = Collection::with_path(make_string_pk_config(), path).unwrap();
+    assert_eq!(coll.count(), 2);
+    assert_eq!(coll.fetch_data(&[json!("a")])[0].as_ref().unwrap()["n"], json!(1));
+    assert_eq!(coll.fetch_data(&[json!("b")])[0].as_ref().unwrap()["n"], json!(2));
+}
+
+// ============================================================
+// Index Recovery Tests
+// ============================================================
+
+fn make_recover_config() -> CollectionConfig {
+    CollectionConfig {
+        name: "recover".into(),
+        fields: vec![
+            FieldDef { name: "id".into(), field_type: FieldType::Int64, is_primary_key: true, dim: None },
+            FieldDef { name: "vec".into(), field_type: FieldType::Vector, is_primary_key: false, dim: Some(3) },
+        ],
+        description: String::new(),
+    }
+}
+
+fn fill_recover_collection(coll: &Collection) {
+    let data: Vec<_> = (0..30).map(|i| HashMap::from([
+        ("id".into(), json!(i)),
+        ("vec".into(), json!([1.0, i as f64 / 30.0, 0.5])),
+    ])).collect();
+    coll.upsert_data(&data).unwrap();
+}
+
+#[test]
+fn test_collection_indexes_restored_on_reopen() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("coll");
+    {
+        let coll = Collection::with_path(make_recover_config(), path.clone()).unwrap();
+        coll.create_index("flat", IndexConfig::default()).unwrap();
+        coll.create_index("hnsw", IndexConfig { index_type: "hnsw".into(), distance: DistanceMetric::L2, ..Default::default() }).unwrap();
+        fill_recover_collection(&coll);
+    }
+    assert!(path.join("indexes/hnsw/index_meta.json").exists());
+    let coll = Collection::with_path(make_recover_config(), path).unwrap();
+    let mut names = coll.list_indexes();
+    names.sort();
+    assert_eq!(names, vec!["flat", "hnsw"]);
+    for name in ["flat", "hnsw"] {
+        let result = coll.search_by_vector(name, &[1.0, 0.0, 0.5], 3, 0, None).unwrap();
+        assert_eq!(result.data.len(), 3);
+        assert_eq!(result.data[0].id, json!(0));
+    }
+}
+
+#[test]
+fn test_collection_index_rebuilt_when_file_missing_or_corrupt() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("coll");
+    {
+        let coll = Collection::with_path(make_recover_config(), path.clone()).unwrap();
+        coll.create_index("flat", IndexConfig::default()).unwrap();
+        coll.create_index("hnsw", IndexConfig { index_type: "hnsw".into(), ..Default::default() }).unwrap();
+        fill_recover_collection(&coll);
+    }
+    std::fs::remove_file(path.join("indexes/flat/flat_index.bin")).unwrap();
+    std::fs::write(path.join("indexes/hnsw/hnsw_index.bin"), b"OVHNSW\0\0 truncated").unwrap();
+
+    let coll = Collection::with_path(make_recover_config(), path).unwrap();
+    for name in ["flat", "hnsw"] {
+        let result = coll.search_by_vector(name, &[1.0, 1.0, 0.5], 1, 0, None).unwrap();
+        assert_eq!(result.data[0].id, json!(29));
+    }
+}
+
+#[test]
+fn test_collection_dropped_index_stays_dropped() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("coll");
+    {
+        let coll = Collection::with_path(make_recover_config(), path.clone()).unwrap();
+        coll.create_index("a", IndexConfig::default()).unwrap();
+        coll.create_index("b", IndexConfig::default()).unwrap();
+        fill_recover_collection(&coll);
+        coll.close();
+        coll.drop_index("a");
+    }
+    let coll = Collection::with_path(make_recover_config(), path).unwrap();
+    assert_eq!(coll.list_indexes(), vec!["b".to_string()]);
+}
+
+#[test]
+fn test_index_config_meta_roundtrip() {
+    let cfg = IndexConfig {
+        index_type: "hnsw".into(),
+        distance: DistanceMetric::Ip,
+        scalar_index_fields: vec!["uri".into()],
+        text_fields: vec!["abstract".into()],
+        vector_field: Some("content_vec".into()),
+        quantization: Quantization::Pq { m: 16 },
+        rescore: Some(3),
+        mmap: true,
+        nlist: Some(64),
+        nprobe: Some(4),
+    };
+    let meta = cfg.to_meta("main");
+    assert_eq!(meta.index_name, "main");
+    assert_eq!(meta.distance, "ip");
+    let back = IndexConfig::from_meta(&meta);
+    assert_eq!(back.index_type, "hnsw");
+    assert_eq!(back.distance, DistanceMetric::Ip);
+    assert_eq!(back.scalar_index_fields, vec!["uri".to_string()]);
+    assert_eq!(back.text_fields, vec!["abstract".to_string()]);
+    assert_eq!(back.vector_field.as_deref(), Some("content_vec"));
+    assert_eq!(back.quantization, Quantization::Pq { m: 16 });
+    assert_eq!(back.rescore, Some(3));
+    assert!(back.mmap);
+    assert_eq!((back.nlist, back.nprobe), (Some(64), Some(4)));
+}
+
+// ============================================================
+// Write-Ahead Log Tests
+// ============================================================
+
+/// Simulate a crash: skip `Drop`, so nothing past the log reaches disk.
+fn crash(coll: Collection) {
+    std::mem::forget(coll);
+}
+
+#[test]
+fn test_wal_replays_after_crash() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("coll");
+    let coll = Collection::with_path(make_string_pk_config(), path.clone()).unwrap();
+    coll.create_index("idx", IndexConfig::default()).unwrap();
+    coll.checkpoint().unwrap();
+    let data: Vec<_> = (0..10).map(|i| HashMap::from([
+        ("id".into(), json!(format!("doc-{i}"))),
+        ("vec".into(), json!([1.0, i as f64])),
+        ("n".into(), json!(i)),
+    ])).collect();
+    coll.upsert_data(&data).unwrap();
+    coll.delete_data(&[json!("doc-3")]).unwrap();
+    crash(coll);
+
+    let coll = Collection::with_path(make_string_pk_config(), path).unwrap();
+    assert_eq!(coll.count(), 9);
+    assert!(coll.fetch_data(&[json!("doc-3")])[0].is_none());
+    assert_eq!(coll.fetch_data(&[json!("doc-9")])[0].as_ref().unwrap()["n"], json!(9));
+    let result = coll.search_by_vector("idx", &[1.0, 0.0], 1, 0, None).unwrap();
+    assert_eq!(result.data[0].id, json!("doc-0"));
+
+    // New keys must not reuse labels of replayed records.
+    coll.upsert_data(&[HashMap::from([
+        ("id".into(), json!("new")),
+        ("vec".into(), json!([0.0, 1.0])),
+        ("n".into(), json!(100)),
+    ])]).unwrap();
+    assert_eq!(coll.count(), 10);
+    assert_eq!(coll.fetch_data(&[json!("doc-9")])[0].as_ref().unwrap()["n"], json!(9));
+}
+
+#[test]
+fn test_wal_replays_delete_all() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("coll");
+    {
+        let coll = Collection::with_path(make_recover_config(), path.clone()).unwrap();
+        fill_recover_collection(&coll);
+    }
+    let coll = Collection::with_path(make_recover_config(), path.clone()).unwrap();
+    coll.delete_all_data().unwrap();
+    crash(coll);
+
+    let coll = Collection::with_path(make_recover_config(), path).unwrap();
+    assert_eq!(coll.count(), 0);
+}
+
+#[test]
+fn test_wal_torn_tail_is_discarded() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("coll");
+    let coll = Collection::with_path(make_recover_config(), path.clone()).unwrap();
+    fill_recover_collection(&coll);
+    crash(coll);
+
+    // A partially written frame after the last good entry.
+    let wal = path.join("wal.log");
+    let good_len = std::fs::metadata(&wal).unwrap().len();
+    let mut bytes = std::fs::read(&wal).unwrap();
+    bytes.extend_from_slice(&[200, 0, 0, 0, 1, 2, 3, 4, b'{']);
+    std::fs::write(&wal, &bytes).unwrap();
+
+    let coll = Collection::with_path(make_recover_config(), path).unwrap();
+    assert_eq!(coll.count(), 30);
+    assert_eq!(std::fs::metadata(&wal).unwrap().len(), good_len);
+}
+
+#[test]
+fn test_wal_truncated_by_checkpoint() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("coll");
+    let coll = Collection::with_path(make_recover_config(), path.clone()).unwrap();
+    fill_recover_collection(&coll);
+    let wal = path.join("wal.log");
+    assert!(std::fs::metadata(&wal).unwrap().len() > 0);
+    coll.checkpoint().unwrap();
+    assert_eq!(std::fs::metadata(&wal).unwrap().len(), 0);
+    crash(coll);
+
+    let coll = Collection::with_path(make_recover_config(), path).unwrap();
+    assert_eq!(coll.count(), 30);
+}
+
+#[test]
+fn test_corrupt_snapshot_fails_reopen() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("coll");
+    let coll = Collection::with_path(make_recover_config(), path.clone()).unwrap();
+    fill_recover_collection(&coll);
+    coll.checkpoint().unwrap();
+    crash(coll);
+
+    std::fs::write(path.join("records.json"), b"{\"records\": [").unwrap();
+    assert!(matches!(
+        Collection::with_path(make_recover_config(), path),
+        Err(VectorDbError::Serialization(_))
+    ));
+}
+
+#[test]
+fn test_wal_durability_policies() {
+    for durability in [Durability::Always, Durability::EveryN(4), Durability::Os] {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("coll");
+        let coll = Collection::with_durability(make_recover_config(), path.clone(), durability).unwrap();
+        for i in 0..5 {
+            coll.upsert_data(&[HashMap::from([
+                ("id".into(), json!(i)),
+                ("vec".into(), json!([1.0, 0.0, 0.0])),
+            ])]).unwrap();
+        }
+        crash(coll);
+        let coll = Collection::with_path(make_recover_config(), path).unwrap();
+        assert_eq!(coll.count(), 5, "{durability:?}");
+    }
+}
+
+#[test]
+fn test_wal_rejected_batch_not_logged() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("coll");
+    let coll = Collection::with_path(make_recover_config(), path.clone()).unwrap();
+    let bad = vec![
+        HashMap::from([("id".into(), json!(1)), ("vec".into(), json!([1.0, 0.0, 0.0]))]),
+        HashMap::from([("id".into(), json!(2)), ("vec".into(), json!([1.0]))]),
+    ];
+    assert!(coll.upsert_data(&bad).is_err());
+    assert_eq!(coll.count(), 0);
+    assert_eq!(std::fs::metadata(path.join("wal.log")).unwrap().len(), 0);
+}
+
+// ============================================================
+// Sparse Index & Hybrid Search Tests
+// ============================================================
+
+fn sv(terms: &[(&str, f32)]) -> SparseVector {
+    terms.iter().map(|&(t, w)| (t.to_string(), w)).collect()
+}
+
+#[test]
+fn test_sparse_index_search() {
+    let idx = SparseIndex::new();
+    idx.insert(1, &sv(&[("rust", 1.0), ("error", 0.5)])).unwrap();
+    idx.insert(2, &sv(&[("error", 2.0), ("e0382", 3.0)])).unwrap();
+    idx.insert(3, &sv(&[("python", 1.0)])).unwrap();
+    assert_eq!(idx.len(), 3);
+
+    let result = idx.search(&sv(&[("e0382", 1.0), ("error", 1.0)]), 10).unwrap();
+    assert_eq!(result.ids, vec![2, 1]);
+    assert!((result.scores[0] - 5.0).abs() < 1e-6);
+    assert!(idx.search(&sv(&[("missing", 1.0)]), 10).unwrap().is_empty());
+}
+
+#[test]
+fn test_sparse_index_update_and_delete() {
+    let idx = SparseIndex::new();
+    idx.insert(1, &sv(&[("a", 1.0)])).unwrap();
+    idx.insert(1, &sv(&[("b", 1.0), ("zero", 0.0)])).unwrap();
+    assert!(idx.search(&sv(&[("a", 1.0)]), 5).unwrap().is_empty());
+    assert_eq!(idx.search(&sv(&[("b", 1.0)]), 5).unwrap().ids, vec![1]);
+    assert_eq!(idx.term_count(), 1);
+    idx.delete(1).unwrap();
+    assert!(idx.is_empty());
+    assert_eq!(idx.term_count(), 0);
+    assert!(idx.insert(2, &sv(&[("nan", f32::NAN)])).is_err());
+}
+
+#[test]
+fn test_sparse_index_save_load() {
+    let dir = TempDir::new().unwrap();
+    let idx = SparseIndex::new();
+    idx.insert(7, &sv(&[("x", 1.0), ("y", 2.0)])).unwrap();
+    idx.save(dir.path()).unwrap();
+    let mut loaded = SparseIndex::new();
+    loaded.load(dir.path()).unwrap();
+    assert_eq!(loaded.len(), 1);
+    assert_eq!(loaded.search(&sv(&[("y", 1.0)]), 1).unwrap().ids, vec![7]);
+}
+
+#[test]
+fn test_fuse_rrf_and_weighted_sum() {
+    let a = SearchResult { ids: vec![1, 2, 3], scores: vec![0.9, 0.8, 0.1] };
+    let b = SearchResult { ids: vec![3, 4], scores: vec![40.0, 10.0] };
+
+    let rrf = fuse(&[(&a, 1.0), (&b, 1.0)], Fusion::Rrf { k: 60.0 }, 10);
+    assert_eq!(rrf.ids[0], 3); // ranked by both lists
+    assert_eq!(rrf.len(), 4);
+
+    let ws = fuse(&[(&a, 1.0), (&b, 0.5)], Fusion::WeightedSum, 2);
+    // 1: 1.0, 3: 0.0 + 0.5, 2: 0.875, 4: 0.0
+    assert_eq!(ws.ids, vec![1, 2]);
+    assert!((ws.scores[0] - 1.0).abs() < 1e-6);
+}
+
+fn make_hybrid_collection() -> Collection {
+    let config = CollectionConfig {
+        name: "hybrid".into(),
+        fields: vec![
+            FieldDef { name: "id".into(), field_type: FieldType::String, is_primary_key: true, dim: None },
+            FieldDef { name: "vec".into(), field_type: FieldType::Vector, is_primary_key: false, dim: Some(2) },
+            FieldDef { name: "sparse".into(), field_type: FieldType::SparseVector, is_primary_key: false, dim: None },
+            FieldDef { name: "lang".into(), field_type: FieldType::String, is_primary_key: false, dim: None },
+        ],
+        description: String::new(),
+    };
+    let coll = Collection::new(config);
+    coll.create_index("dense", IndexConfig::default()).unwrap();
+    coll.create_index("sparse", IndexConfig { index_type: "sparse".into(), ..Default::default() }).unwrap();
+    let docs = [
+        ("borrow", [1.0, 0.0], json!({"borrow": 1.0, "move": 0.5}), "rust"),
+        ("lifetime", [0.95, 0.1], json!({"lifetime": 1.0}), "rust"),
+        ("e0382", [0.2, 1.0], json!({"e0382": 2.0, "move": 1.0}), "rust"),
+        ("gil", [0.0, 1.0], json!({"gil": 1.0}), "python"),
+    ];
+    let data: Vec<_> = docs.iter().map(|(id, v, sp, lang)| HashMap::from([
+        ("id".into(), json!(id)),
+        ("vec".into(), json!(v)),
+        ("sparse".into(), sp.clone()),
+        ("lang".into(), json!(lang)),
+    ])).collect();
+    coll.upsert_data(&data).unwrap();
+    coll
+}
+
+#[test]
+fn test_collection_sparse_search() {
+    let coll = make_hybrid_collection();
+    let result = coll.search_by_sparse_vector("sparse", &sv(&[("move", 1.0)]), 10, 0, None).unwrap();
+    let ids: Vec<_> = result.data.iter().map(|d| d.id.clone()).collect();
+    assert_eq!(ids, vec![json!("e0382"), json!("borrow")]);
+    assert!(!result.data[0].fields.contains_key("sparse"));
+
+    let fetched = coll.fetch_data(&[json!("e0382")]);
+    assert_eq!(fetched[0].as_ref().unwrap()["sparse"]["e0382"], json!(2.0));
+
+    // Queries must match the index kind.
+    assert!(coll.search_by_vector("sparse", &[1.0, 0.0], 1, 0, None).is_err());
+    assert!(coll.search_by_sparse_vector("dense", &sv(&[("move", 1.0)]), 1, 0, None).is_err());
+}
+
+#[test]
+fn test_collection_sparse_index_follows_updates() {
+    let coll = make_hybrid_collection();
+    coll.upsert_data(&[HashMap::from([
+        ("id".into(), json!("gil")),
+        ("vec".into(), json!([0.0, 1.0])),
+        ("lang".into(), json!("python")),
+    ])]).unwrap();
+    assert!(coll.search_by_sparse_vector("sparse", &sv(&[("gil", 1.0)]), 10, 0, None).unwrap().data.is_empty());
+    coll.delete_data(&[json!("e0382")]).unwrap();
+    let result = coll.search_by_sparse_vector("sparse", &sv(&[("e0382", 1.0)]), 10, 0, None).unwrap();
+    assert!(result.data.is_empty());
+}
+
+#[test]
+fn test_collection_hybrid_search_finds_keyword_match() {
+    let coll = make_hybrid_collection();
+    let dense_query = [1.0, 0.0];
+    let sparse_query = sv(&[("e0382", 1.0)]);
+
+    // Dense alone ranks the error-code document last among rust docs.
+    let dense = coll.search_by_vector("dense", &dense_query, 2, 0, None).unwrap();
+    assert!(dense.data.iter().all(|d| d.id != json!("e0382")));
+
+    for fusion in [Fusion::Rrf { k: 60.0 }, Fusion::WeightedSum] {
+        let query = HybridQuery::new()
+            .dense("dense", &dense_query, 1.0)
+            .sparse("sparse", &sparse_query, 2.0)
+            .with_fusion(fusion);
+        let result = coll.search_hybrid(&query, 2, 0, None).unwrap();
+        assert_eq!(result.data[0].id, json!("e0382"), "{fusion:?}");
+        assert_eq!(result.data.len(), 2);
+    }
+}
+
+#[test]
+fn test_collection_hybrid_search_with_filter() {
+    let coll = make_hybrid_collection();
+    let sparse_query = sv(&[("gil", 1.0), ("move", 1.0)]);
+    let query = HybridQuery::new().dense("dense", &[0.0, 1.0], 1.0).sparse("sparse", &sparse_query, 1.0);
+    let filter = json!({"op": "must", "field": "lang", "conds": ["rust"]});
+    let result = coll.search_hybrid(&query, 10, 0, Some(&filter)).unwrap();
+    assert!(!result.data.is_empty());
+    assert!(result.data.iter().all(|d| d.fields["lang"] == json!("rust")));
+    let missing = HybridQuery::new().dense("dense", &[0.0, 1.0], 1.0).sparse("nope", &sparse_query, 1.0);
+    assert!(coll.search_hybrid(&missing, 1, 0, None).is_err());
+}
+
+#[test]
+fn test_collection_sparse_index_restored_on_reopen() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("hybrid");
+    let config = make_hybrid_collection().config().clone();
+    {
+        let coll = Collection::with_path(config.clone(), path.clone()).unwrap();
+        coll.create_index("sparse", IndexConfig { index_type: "sparse".into(), ..Default::default() }).unwrap();
+        coll.upsert_data(&[HashMap::from([
+            ("id".into(), json!("a")),
+            ("vec".into(), json!([1.0, 0.0])),
+            ("sparse".into(), json!({"needle": 1.0})),
+        ])]).unwrap();
+    }
+    assert!(path.join("indexes/sparse/sparse_index.json").exists());
+    let coll = Collection::with_path(config, path).unwrap();
+    let result = coll.search_by_sparse_vector("sparse", &sv(&[("needle", 1.0)]), 1, 0, None).unwrap();
+    assert_eq!(result.data[0].id, json!("a"));
+}
+
+// ============================================================
+// Full-Text (BM25) Index Tests
+// ============================================================
+
+#[test]
+fn test_tokenize_latin_and_cjk() {
+    assert_eq!(tokenize("Hello, World! snake_case E0382"), vec!["hello", "world", "snake_case", "e0382"]);
+    assert_eq!(tokenize("向量数据库"), vec!["向", "量", "向量", "数", "量数", "据", "数据", "库", "据库"]);
+    assert_eq!(tokenize("rust向量db"), vec!["rust", "向", "量", "向量", "db"]);
+    assert!(tokenize("  ... ").is_empty());
+}
+
+#[test]
+fn test_fulltext_index_bm25_ranking() {
+    let idx = FullTextIndex::new();
+    idx.insert(1, "the borrow checker rejects use after move").unwrap();
+    idx.insert(2, "error E0382: borrow of moved value").unwrap();
+    idx.insert(3, "the the the the the").unwrap();
+    idx.insert(4, "").unwrap();
+    assert_eq!(idx.len(), 3);
+
+    let result = idx.search("E0382", 10).unwrap();
+    assert_eq!(result.ids, vec![2]);
+    // Rarer terms weigh more: "moved" only appears in doc 2.
+    let result = idx.search("borrow moved", 10).unwrap();
+    assert_eq!(result.ids, vec![2, 1]);
+    assert!(idx.search("python", 10).unwrap().is_empty());
+}
+
+#[test]
+fn test_fulltext_index_update_delete_save_load() {
+    let dir = TempDir::new().unwrap();
+    let idx = FullTextIndex::new();
+    idx.insert(1, "alpha beta").unwrap();
+    idx.insert(1, "gamma").unwrap();
+    assert!(idx.search("alpha", 5).unwrap().is_empty());
+    idx.insert(2, "向量检索").unwrap();
+    idx.save(dir.path()).unwrap();
+    idx.delete(1).unwrap();
+    assert_eq!(idx.len(), 1);
+
+    let mut loaded = FullTextIndex::new();
+    loaded.load(dir.path()).unwrap();
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded.search("gamma", 5).unwrap().ids, vec![1]);
+    assert_eq!(loaded.search("检索", 5).unwrap().ids, vec![2]);
+}
+
+fn make_text_collection() -> Collection {
+    let config = CollectionConfig {
+        name: "docs".into(),
+        fields: vec![
+            FieldDef { name: "id".into(), field_type: FieldType::Int64, is_primary_key: true, dim: None },
+            FieldDef { name: "vec".into(), field_type: FieldType::Vector, is_primary_key: false, dim: Some(2) },
+            FieldDef { name: "name".into(), field_type: FieldType::String, is_primary_key: false, dim: None },
+            FieldDef { name: "abstract".into(), field_type: FieldType::String, is_primary_key: false, dim: None },
+            FieldDef { name: "tags".into(), field_type: FieldType::ListString, is_primary_key: false, dim: None },
+            FieldDef { name: "rank".into(), field_type: FieldType::Int64, is_primary_key: false, dim: None },
+        ],
+        description: String::new(),
+    };
+    let coll = Collection::new(config);
+    coll.create_index("dense", IndexConfig::default()).unwrap();
+    let text_cfg = IndexConfig {
+        index_type: "fulltext".into(),
+        text_fields: vec!["name".into(), "abstract".into(), "tags".into()],
+        ..Default::default()
+    };
+    coll.create_index("text", text_cfg).unwrap();
+    let docs = [
+        (1, [1.0, 0.0], "ownership.md", "Ownership and borrowing rules", json!(["rust"])),
+        (2, [0.9, 0.2], "errors.md", "Fixing error E0382 in loops", json!(["rust", "compiler"])),
+        (3, [0.0, 1.0], "gil.md", "全局解释器锁 and threads", json!(["python"])),
+    ];
+    let data: Vec<_> = docs.iter().map(|(id, v, name, abs, tags)| HashMap::from([
+        ("id".into(), json!(id)),
+        ("vec".into(), json!(v)),
+        ("name".into(), json!(name)),
+        ("abstract".into(), json!(abs)),
+        ("tags".into(), tags.clone()),
+        ("rank".into(), json!(id)),
+    ])).collect();
+    coll.upsert_data(&data).unwrap();
+    coll
+}
+
+#[test]
+fn test_collection_text_search() {
+    let coll = make_text_collection();
+    let result = coll.search_by_text("text", "e0382", 10, 0, None).unwrap();
+    assert_eq!(result.data.len(), 1);
+    assert_eq!(result.data[0].id, json!(2));
+
+    let result = coll.search_by_text("text", "解释器", 10, 0, None).unwrap();
+    assert_eq!(result.data[0].id, json!(3));
+
+    // List fields and filters.
+    let filter = json!({"op": "range", "field": "rank", "gte": 2});
+    let result = coll.search_by_text("text", "rust", 10, 0, Some(&filter)).unwrap();
+    assert_eq!(result.data.iter().map(|d| d.id.clone()).collect::<Vec<_>>(), vec![json!(2)]);
+
+    assert!(coll.search_by_text("dense", "rust", 10, 0, None).is_err());
+    assert!(coll.search_by_vector("text", &[1.0, 0.0], 10, 0, None).is_err());
+}
+
+#[test]
+fn test_collection_text_index_maintained_incrementally() {
+    let coll = make_text_collection();
+    coll.upsert_data(&[HashMap::from([
+        ("id".into(), json!(4)),
+        ("vec".into(), json!([0.5, 0.5])),
+        ("abstract".into(), json!("Lifetimes explained")),
+    ])]).unwrap();
+    assert_eq!(coll.search_by_text("text", "lifetimes", 10, 0, None).unwrap().data[0].id, json!(4));
+
+    coll.upsert_data(&[HashMap::from([
+        ("id".into(), json!(4)),
+        ("vec".into(), json!([0.5, 0.5])),
+        ("abstract".into(), json!("Traits explained")),
+    ])]).unwrap();
+    assert!(coll.search_by_text("text", "lifetimes", 10, 0, None).unwrap().data.is_empty());
+
+    coll.delete_data(&[json!(4)]).unwrap();
+    assert!(coll.search_by_text("text", "traits", 10, 0, None).unwrap().data.is_empty());
+}
+
+#[test]
+fn test_collection_text_fused_with_vector() {
+    let coll = make_text_collection();
+    let dense_query = [1.0, 0.0];
+    let query = HybridQuery::new()
+        .dense("dense", &dense_query, 1.0)
+        .text("text", "E0382", 1.0);
+    let result = coll.search_hybrid(&query, 3, 0, None).unwrap();
+    assert_eq!(result.data[0].id, json!(2));
+    assert_eq!(result.data.len(), 3);
+}
+
+#[test]
+fn test_collection_text_index_config_validated() {
+    let coll = make_text_collection();
+    let cfg = |fields: &[&str]| IndexConfig {
+        index_type: "fulltext".into(),
+        text_fields: fields.iter().map(|f| f.to_string()).collect(),
+        ..Default::default()
+    };
+    assert!(coll.create_index("none", cfg(&[])).is_err());
+    assert!(coll.create_index("missing", cfg(&["nope"])).is_err());
+    assert!(coll.create_index("numeric", cfg(&["rank"])).is_err());
+    assert!(!coll.has_index("numeric"));
+}
+
+#[test]
+fn test_collection_text_index_restored_on_reopen() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("docs");
+    let config = make_text_collection().config().clone();
+    {
+        let coll = Collection::with_path(config.clone(), path.clone()).unwrap();
+        let cfg = IndexConfig { index_type: "fulltext".into(), text_fields: vec!["name".into()], ..Default::default() };
+        coll.create_index("text", cfg).unwrap();
+        coll.upsert_data(&[HashMap::from([("id".into(), json!(1)), ("name".into(), json!("needle"))])]).unwrap();
+    }
+    let coll = Collection::with_path(config, path).unwrap();
+    assert_eq!(coll.search_by_text("text", "needle", 1, 0, None).unwrap().data[0].id, json!(1));
+    coll.upsert_data(&[HashMap::from([("id".into(), json!(2)), ("name".into(), json!("haystack"))])]).unwrap();
+    assert_eq!(coll.search_by_text("text", "haystack", 1, 0, None).unwrap().data[0].id, json!(2));
+}
+
+// ============================================================
+// Scalar Index & Filter Planning Tests
+// ============================================================
+
+fn sorted(set: std::collections::HashSet<u64>) -> Vec<u64> {
+    let mut v: Vec<u64> = set.into_iter().collect();
+    v.sort();
+    v
+}
+
+#[test]
+fn test_scalar_index_equal_and_prefix() {
+    let idx = ScalarIndex::new();
+    idx.insert(1, Some(&json!("viking://docs/a")));
+    idx.insert(2, Some(&json!("viking://docs/b")));
+    idx.insert(3, Some(&json!(["rust", "db"])));
+    idx.insert(4, Some(&json!(true)));
+    idx.insert(5, Some(&json!(2.5)));
+    idx.insert(6, None);
+    assert_eq!(idx.len(), 5);
+
+    assert_eq!(sorted(idx.equal(&[json!("viking://docs/a"), json!("db")]).unwrap()), vec![1, 3]);
+    assert_eq!(sorted(idx.equal(&[json!(true)]).unwrap()), vec![4]);
+    assert_eq!(sorted(idx.equal(&[json!(2.5000000001)]).unwrap()), vec![5]);
+    assert!(idx.equal(&[json!(null)]).is_none());
+    assert_eq!(sorted(idx.prefix("viking://docs/")), vec![1, 2]);
+
+    idx.insert(1, Some(&json!("viking://other")));
+    assert!(idx.equal(&[json!("viking://docs/a")]).unwrap().is_empty());
+    idx.delete(2);
+    assert!(idx.prefix("viking://docs/").is_empty());
+}
+
+#[test]
+fn test_scalar_index_ranges() {
+    let idx = ScalarIndex::new();
+    for i in 0..10u64 {
+        idx.insert(i, Some(&json!(i)));
+    }
+    idx.insert(100, Some(&json!("5")));
+    assert_eq!(sorted(idx.range(Some(&json!(6)), None, None, Some(&json!(8))).unwrap()), vec![7, 8]);
+    assert_eq!(sorted(idx.range(None, Some(&json!(8.5)), None, None).unwrap()), vec![9]);
+    assert_eq!(sorted(idx.range(None, None, Some(&json!(2)), None).unwrap()), vec![0, 1]);
+    assert!(idx.range(Some(&json!(5)), None, Some(&json!(5)), None).unwrap().is_empty());
+    assert!(idx.range(Some(&json!(1)), None, Some(&json!("z")), None).unwrap().is_empty());
+    assert!(idx.range(None, None, None, None).is_none());
+    assert_eq!(sorted(idx.range_out(Some(&json!(1)), Some(&json!(8)))), vec![0, 9]);
+}
+
+#[test]
+fn test_scalar_index_datetime_ranges_compare_instants() {
+    let idx = ScalarIndex::new();
+    idx.insert(1, Some(&json!("2024-01-01T00:00:00Z")));
+    idx.insert(2, Some(&json!("2024-01-01T09:00:00+08:00"))); // == 01:00Z
+    idx.insert(3, Some(&json!("2024-01-02T00:00:00Z")));
+    let from = json!("2024-01-01T00:30:00Z");
+    let to = json!("2024-01-01T12:00:00Z");
+    assert_eq!(sorted(idx.range(None, Some(&from), None, Some(&to)).unwrap()), vec![2]);
+
+    let filter = Filter::from_json(&json!({"op": "range", "field": "t", "gte": from, "lte": to})).unwrap();
+    assert!(filter.matches(&HashMap::from([("t".to_string(), json!("2024-01-01T09:00:00+08:00"))])));
+    assert!(!filter.matches(&HashMap::from([("t".to_string(), json!("2024-01-01T00:00:00Z"))])));
+}
+
+#[test]
+fn test_plan_filter_combinators() {
+    let a = ScalarIndex::new();
+    let b = ScalarIndex::new();
+    for i in 0..6u64 {
+        a.insert(i, Some(&json!(if i % 2 == 0 { "even" } else { "odd" })));
+        b.insert(i, Some(&json!(i)));
+    }
+    let indexes = HashMap::from([("parity".to_string(), a), ("n".to_string(), b)]);
+    let plan = |v: serde_json::Value| plan_filter(&indexes, &Filter::from_json(&v).unwrap()).map(sorted);
+
+    assert_eq!(plan(json!({"op": "and", "conds": [
+        {"op": "must", "field": "parity", "conds": ["even"]},
+        {"op": "range", "field": "n", "gte": 2},
+        {"op": "contains", "field": "other", "substring": "x"},
+    ]})), Some(vec![2, 4]));
+    assert_eq!(plan(json!({"op": "or", "conds": [
+        {"op": "must", "field": "n", "conds": [1]},
+        {"op": "must", "field": "n", "conds": [5]},
+    ]})), Some(vec![1, 5]));
+    assert_eq!(plan(json!({"op": "or", "conds": [
+        {"op": "must", "field": "n", "conds": [1]},
+        {"op": "must", "field": "unindexed", "conds": [5]},
+    ]})), None);
+    assert_eq!(plan(json!({"op": "must_not", "field": "n", "conds": [1]})), None);
+}
+
+fn make_scalar_collection(index_type: &str, n: usize) -> Collection {
+    let config = CollectionConfig {
+        name: "scalar".into(),
+        fields: vec![
+            FieldDef { name: "id".into(), field_type: FieldType::Int64, is_primary_key: true, dim: None },
+            FieldDef { name: "vec".into(), field_type: FieldType::Vector, is_primary_key: false, dim: Some(2) },
+            FieldDef { name: "parent_uri".into(), field_type: FieldType::Path, is_primary_key: false, dim: None },
+            FieldDef { name: "n".into(), field_type: FieldType::Int64, is_primary_key: false, dim: None },
+        ],
+        description: String::new(),
+    };
+    let coll = Collection::new(config);
+    coll.create_index("idx", IndexConfig {
+        index_type: index_type.into(),
+        scalar_index_fields: vec!["parent_uri".into(), "n".into()],
+        ..Default::default()
+    }).unwrap();
+    let data: Vec<_> = (0..n).map(|i| {
+        let angle = i as f64 / n as f64 * std::f64::consts::FRAC_PI_2;
+        HashMap::from([
+            ("id".into(), json!(i)),
+            ("vec".into(), json!([angle.cos(), angle.sin()])),
+            ("parent_uri".into(), json!(format!("viking://p/{}", i % 100))),
+            ("n".into(), json!(i)),
+        ])
+    }).collect();
+    coll.upsert_data(&data).unwrap();
+    coll
+}
+
+#[test]
+fn test_selective_filter_fills_page() {
+    let coll = make_scalar_collection("hnsw", 1000);
+    // parent 99 holds ids 99, 199, ..., 999, mostly far from the query vector.
+    let filter = json!({"op": "must", "field": "parent_uri", "conds": ["viking://p/99"]});
+    let result = coll.search_by_vector("idx", &[1.0, 0.0], 5, 0, Some(&filter)).unwrap();
+    let ids: Vec<_> = result.data.iter().map(|d| d.id.clone()).collect();
+    assert_eq!(ids, vec![json!(99), json!(199), json!(299), json!(399), json!(499)]);
+
+    let page2 = coll.search_by_vector("idx", &[1.0, 0.0], 5, 5, Some(&filter)).unwrap();
+    assert_eq!(page2.data.len(), 5);
+    assert_eq!(page2.data[0].id, json!(599));
+}
+
+#[test]
+fn test_large_allowed_set_uses_index_search() {
+    let coll = make_scalar_collection("flat", 5000);
+    let filter = json!({"op": "range", "field": "n", "gte": 500});
+    let result = coll.search_by_vector("idx", &[1.0, 0.0], 3, 0, Some(&filter)).unwrap();
+    let ids: Vec<_> = result.data.iter().map(|d| d.id.clone()).collect();
+    assert_eq!(ids, vec![json!(500), json!(501), json!(502)]);
+}
+
+#[test]
+fn test_scalar_index_follows_updates() {
+    let coll = make_scalar_collection("flat", 100);
+    let filter = json!({"op": "prefix", "field": "parent_uri", "conds": [], "prefix": "viking://moved"});
+    assert!(coll.search_by_vector("idx", &[1.0, 0.0], 5, 0, Some(&filter)).unwrap().data.is_empty());
+    coll.upsert_data(&[HashMap::from([
+        ("id".into(), json!(7)),
+        ("vec".into(), json!([1.0, 0.0])),
+        ("parent_uri".into(), json!("viking://moved/x")),
+        ("n".into(), json!(7)),
+    ])]).unwrap();
+    let result = coll.search_by_vector("idx", &[1.0, 0.0], 5, 0, Some(&filter)).unwrap();
+    assert_eq!(result.data.len(), 1);
+    coll.delete_data(&[json!(7)]).unwrap();
+    assert!(coll.search_by_vector("idx", &[1.0, 0.0], 5, 0, Some(&filter)).unwrap().data.is_empty());
+}
+
+#[test]
+fn test_scalar_index_fields_validated() {
+    let coll = make_scalar_collection("flat", 0);
+    let cfg = |field: &str| IndexConfig { scalar_index_fields: vec![field.into()], ..Default::default() };
+    assert!(coll.create_index("bad1", cfg("missing")).is_err());
+    assert!(coll.create_index("bad2", cfg("vec")).is_err());
+    assert!(coll.create_index("ok", cfg("n")).is_ok());
+}
+
+#[test]
+fn test_scalar_indexes_rebuilt_on_reopen() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("scalar");
+    let config = make_scalar_collection("flat", 0).config().clone();
+    {
+        let coll = Collection::with_path(config.clone(), path.clone()).unwrap();
+        coll.create_index("idx", IndexConfig { scalar_index_fields: vec!["n".into()], ..Default::default() }).unwrap();
+        let data: Vec<_> = (0..50).map(|i| HashMap::from([
+            ("id".into(), json!(i)),
+            ("vec".into(), json!([1.0, i as f64])),
+            ("n".into(), json!(i)),
+        ])).collect();
+        coll.upsert_data(&data).unwrap();
+    }
+    let coll = Collection::with_path(config, path).unwrap();
+    let filter = json!({"op": "range", "field": "n", "gt": 47});
+    let result = coll.search_by_vector("idx", &[1.0, 0.0], 10, 0, Some(&filter)).unwrap();
+    assert_eq!(result.data.len(), 2);
+}
+
+// ============================================================
+// Filtered ANN Search
+// ============================================================
+
+fn make_filter_indexes(n: u64, dim: usize) -> (HnswIndex, FlatIndex, Vec<f32>) {
+    use rand::Rng;
+    // A narrow beam, so selective filters have to widen it.
+    let hnsw = HnswIndex::with_params(dim, DistanceMetric::L2, 16, 64, 10);
+    let flat = FlatIndex::new(dim, DistanceMetric::L2);
+    let mut rng = rand::thread_rng();
+    for i in 0..n {
+        let vec: Vec<f32> = (0..dim).map(|_| rng.gen::<f32>()).collect();
+        hnsw.insert(i, &vec).unwrap();
+        flat.insert(i, &vec).unwrap();
+    }
+    let query = (0..dim).map(|_| rng.gen::<f32>()).collect();
+    (hnsw, flat, query)
+}
+
+#[test]
+fn test_hnsw_filtered_search_fills_limit() {
+    let (hnsw, flat, query) = make_filter_indexes(1000, 16);
+    let allow = |label: u64| label % 50 == 3;
+    let result = hnsw.search_filtered(&query, 10, &allow).unwrap();
+    assert_eq!(result.len(), 10);
+    assert!(result.ids.iter().all(|&l| allow(l)));
+    assert!(result.scores.windows(2).all(|w| w[0] >= w[1]));
+
+    let exact = flat.search_filtered(&query, 10, &allow).unwrap();
+    let exact_set: std::collections::HashSet<u64> = exact.ids.iter().copied().collect();
+    let overlap = result.ids.iter().filter(|l| exact_set.contains(l)).count();
+    assert!(overlap >= 8, "filtered recall too low: {overlap}/10");
+}
+
+#[test]
+fn test_hnsw_filtered_search_exhausts_graph() {
+    let (hnsw, _, query) = make_filter_indexes(500, 8);
+    let result = hnsw.search_filtered(&query, 10, &|label| label < 3).unwrap();
+    assert_eq!(sorted(result.ids.into_iter().collect()), vec![0, 1, 2]);
+    assert!(hnsw.search_filtered(&query, 10, &|_| false).unwrap().is_empty());
+}
+
+#[test]
+fn test_hnsw_filtered_search_skips_deleted() {
+    let (hnsw, _, query) = make_filter_indexes(300, 8);
+    for label in [5, 15, 25] {
+        hnsw.delete(label).unwrap();
+    }
+    let result = hnsw.search_filtered(&query, 10, &|label| label % 10 == 5).unwrap();
+    assert_eq!(result.len(), 10);
+    assert!(result.ids.iter().all(|&l| l % 10 == 5 && ![5, 15, 25].contains(&l)));
+}
+
+#[test]
+fn test_flat_filtered_search_is_exact() {
+    let (_, flat, query) = make_filter_indexes(200, 4);
+    let allow = |label: u64| label.is_multiple_of(7);
+    let result = flat.search_filtered(&query, 5, &allow).unwrap();
+    let mut expected = flat.search(&query, 200).unwrap();
+    expected.retain(allow);
+    expected.truncate(5);
+    assert_eq!(result.ids, expected.ids);
+}
+
+#[test]
+fn test_unplanned_filter_fills_page() {
+    // `contains` cannot be answered by a scalar index, so the filter is
+    // checked inside the HNSW traversal.
+    let coll = make_scalar_collection("hnsw", 1000);
+    let filter = json!({"op": "contains", "field": "parent_uri", "substring": "/p/99"});
+    let result = coll.search_by_vector("idx", &[1.0, 0.0], 5, 0, Some(&filter)).unwrap();
+    let ids: Vec<_> = result.data.iter().map(|d| d.id.clone()).collect();
+    assert_eq!(ids, vec![json!(99), json!(199), json!(299), json!(399), json!(499)]);
+}
+
+// ============================================================
+// Filter Operator Tests
+// ============================================================
+
+fn one_field(name: &str, value: serde_json::Value) -> HashMap<String, serde_json::Value> {
+    HashMap::from([(name.to_string(), value)])
+}
+
+#[test]
+fn test_filter_regex_character_class() {
+    let filter = Filter::from_json(&json!({"op": "regex", "field": "uri", "pattern": "^viking://user/[^/]+/memories"})).unwrap();
+    assert!(filter.matches(&one_field("uri", json!("viking://user/alice/memories/m1"))));
+    assert!(!filter.matches(&one_field("uri", json!("viking://user/alice/x/memories"))));
+    assert!(!filter.matches(&one_field("uri", json!("viking://user//memories"))));
+}
+
+#[test]
+fn test_filter_regex_compiled_at_parse() {
+    assert!(Filter::from_json(&json!({"op": "regex", "field": "name", "pattern": "(unclosed"})).is_err());
+    let filter = Filter::from_json(&json!({"op": "regex", "field": "name", "pattern": "^a+$"})).unwrap();
+    assert!(matches!(&filter, Filter::Regex { regex, .. } if regex.as_str() == "^a+$"));
+}
+
+#[test]
+fn test_filter_exists() {
+    let exists = Filter::from_json(&json!({"op": "exists", "field": "tag"})).unwrap();
+    let not_exists = Filter::from_json(&json!({"op": "not_exists", "field": "tag"})).unwrap();
+    for (fields, present) in [
+        (one_field("tag", json!("a")), true),
+        (one_field("tag", json!([])), true),
+        (one_field("tag", json!(null)), false),
+        (one_field("other", json!("a")), false),
+    ] {
+        assert_eq!(exists.matches(&fields), present);
+        assert_eq!(not_exists.matches(&fields), !present);
+    }
+}
+
+#[test]
+fn test_filter_subtree() {
+    let filter = Filter::from_json(&json!({"op": "subtree", "field": "uri", "path": "viking://res/docs/"})).unwrap();
+    assert!(filter.matches(&one_field("uri", json!("viking://res/docs"))));
+    assert!(filter.matches(&one_field("uri", json!("viking://res/docs/a/b.md"))));
+    assert!(!filter.matches(&one_field("uri", json!("viking://res/docs2/a"))));
+    assert!(!filter.matches(&one_field("uri", json!("viking://res"))));
+}
+
+#[test]
+fn test_filter_datetime_range_across_offsets() {
+    let filter = Filter::from_json(&json!({
+        "op": "range", "field": "ts", "gte": "2024-01-01T00:00:00Z", "lt": "2024-01-02T00:00:00Z"
+    })).unwrap();
+    // 2024-01-01T20:00:00-05:00 is 2024-01-02T01:00:00Z.
+    assert!(!filter.matches(&one_field("ts", json!("2024-01-01T20:00:00-05:00"))));
+    assert!(filter.matches(&one_field("ts", json!("2024-01-02T07:00:00+08:00"))));
+    assert!(filter.matches(&one_field("ts", json!("2024-01-01T12:30:00.250Z"))));
+    let text = Filter::from_json(&json!({"op": "range", "field": "s", "gte": "b"})).unwrap();
+    assert!(text.matches(&one_field("s", json!("c"))) && !text.matches(&one_field("s", json!("a"))));
+}
+
+#[test]
+fn test_filter_geo_range() {
+    // 1 km around the Forbidden City.
+    let filter = Filter::from_json(&json!({
+        "op": "geo_range", "field": "loc", "center": [116.3972, 39.9163], "radius": 1000
+    })).unwrap();
+    assert!(filter.matches(&one_field("loc", json!("116.3974,39.9087"))));
+    assert!(filter.matches(&one_field("loc", json!([116.3974, 39.9087]))));
+    assert!(filter.matches(&one_field("loc", json!({"lon": 116.3974, "lat": 39.9087}))));
+    // Temple of Heaven, about 4 km south.
+    assert!(!filter.matches(&one_field("loc", json!("116.4108,39.8822"))));
+    assert!(!filter.matches(&one_field("loc", json!("not a point"))));
+    assert!(Filter::from_json(&json!({"op": "geo_range", "field": "loc", "center": [1.0], "radius": 1})).is_err());
+}
+
+#[test]
+fn test_subtree_filter_uses_scalar_index() {
+    let coll = make_scalar_collection("flat", 1000);
+    let filter = json!({"op": "subtree", "field": "parent_uri", "path": "viking://p/9"});
+    let result = coll.search_by_vector("idx", &[1.0, 0.0], 3, 0, Some(&filter)).unwrap();
+    let ids: Vec<_> = result.data.iter().map(|d| d.id.clone()).collect();
+    assert_eq!(ids, vec![json!(9), json!(109), json!(209)]);
+}
+
+// ============================================================
+// Filter Parsing & Validation Tests
+// ============================================================
+
+fn filter_error(result: ov_vectordb::error::Result<Filter>) -> (String, String) {
+    match result {
+        Err(VectorDbError::InvalidFilter { path, message }) => (path, message),
+        other => panic!("expected InvalidFilter, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_filter_parse_reports_json_path() {
+    let (path, message) = filter_error(Filter::from_json(&json!({
+        "op": "and",
+        "conds": [
+            {"op": "must", "field": "a", "conds": [1]},
+            {"op": "or", "conds": [{"op": "rnage", "field": "b", "gt": 1}]}
+        ]
+    })));
+    assert_eq!(path, "$.conds[1].conds[0].op");
+    assert!(message.contains("rnage"));
+
+    let (path, message) = filter_error(Filter::from_json(&json!({"op": "must", "field": "a"})));
+    assert_eq!(path, "$");
+    assert!(message.contains("conds"));
+
+    let (path, _) = filter_error(Filter::from_json(&json!({"op": "range", "field": "a", "gt": [1]})));
+    assert_eq!(path, "$.gt");
+    let (path, _) = filter_error(Filter::from_json(&json!({"op": "range", "field": "a"})));
+    assert_eq!(path, "$");
+    let (path, _) = filter_error(Filter::from_json(&json!(["must"])));
+    assert_eq!(path, "$");
+}
+
+#[test]
+fn test_filter_parse_against_schema() {
+    let config = make_scalar_collection("flat", 0).config().clone();
+    let parse = |v: serde_json::Value| Filter::from_json_with_schema(&v, &config);
+
+    assert!(parse(json!({"op": "and", "conds": [
+        {"op": "must", "field": "n", "conds": [1, 2]},
+        {"op": "subtree", "field": "parent_uri", "path": "viking://p"}
+    ]})).is_ok());
+
+    let (path, message) = filter_error(parse(json!({"op": "must", "field": "nn", "conds": [1]})));
+    assert_eq!((path.as_str(), message.as_str()), ("$.field", "unknown field \"nn\""));
+
+    let (path, _) = filter_error(parse(json!({"op": "must", "field": "n", "conds": [1, "2"]})));
+    assert_eq!(path, "$.conds[1]");
+
+    let (path, message) = filter_error(parse(json!({"op": "range", "field": "vec", "gt": 1})));
+    assert_eq!(path, "$.field");
+    assert!(message.contains("cannot be filtered"));
+
+    let (path, _) = filter_error(parse(json!({"op": "prefix", "field": "n", "prefix": "1"})));
+    assert_eq!(path, "$.field");
+}
+
+#[test]
+fn test_filter_parse_datetime_bounds() {
+    let config = CollectionConfig {
+        name: "dt".into(),
+        fields: vec![
+            FieldDef { name: "id".into(), field_type: FieldType::Int64, is_primary_key: true, dim: None },
+            FieldDef { name: "ts".into(), field_type: FieldType::DateTime, is_primary_key: false, dim: None },
+        ],
+        description: String::new(),
+    };
+    assert!(Filter::from_json_with_schema(&json!({"op": "range", "field": "ts", "gte": "2024-01-01T00:00:00Z"}), &config).is_ok());
+    let (path, _) = filter_error(Filter::from_json_with_schema(&json!({"op": "range", "field": "ts", "gte": "yesterday"}), &config));
+    assert_eq!(path, "$.gte");
+}
+
+#[test]
+fn test_search_rejects_invalid_filter() {
+    let coll = make_scalar_collection("flat", 10);
+    let bad = json!({"op": "and", "conds": [{"op": "rnage", "field": "n", "gt": 1}]});
+    match coll.search_by_vector("idx", &[1.0, 0.0], 5, 0, Some(&bad)) {
+        Err(VectorDbError::InvalidFilter { path, .. }) => assert_eq!(path, "$.conds[0].op"),
+        other => panic!("expected InvalidFilter, got {:?}", other.map(|r| r.data.len())),
+    }
+    // `null` is no filter.
+    let result = coll.search_by_vector("idx", &[1.0, 0.0], 5, 0, Some(&json!(null))).unwrap();
+    assert_eq!(result.data.len(), 5);
+}
+
+// ============================================================
+// Schema Enforcement Tests
+// ============================================================
+
+fn make_typed_config() -> CollectionConfig {
+    let field = |name: &str, field_type: FieldType| FieldDef { name: name.into(), field_type, is_primary_key: false, dim: None };
+    CollectionConfig {
+        name: "typed".into(),
+        fields: vec![
+            FieldDef { name: "id".into(), field_type: FieldType::Int64, is_primary_key: true, dim: None },
+            FieldDef { name: "vec".into(), field_type: FieldType::Vector, is_primary_key: false, dim: Some(2) },
+            field("count", FieldType::Int64),
+            field("score", FieldType::Float32),
+            field("flag", FieldType::Bool),
+            field("tags", FieldType::ListString),
+            field("ids", FieldType::ListInt64),
+            field("ts", FieldType::DateTime),
+            field("loc", FieldType::GeoPoint),
+            field("uri", FieldType::Path),
+        ],
+        description: String::new(),
+    }
+}
+
+fn typed_record(id: i64, extra: serde_json::Value) -> HashMap<String, serde_json::Value> {
+    let mut record = HashMap::from([("id".into(), json!(id)), ("vec".into(), json!([1.0, 0.0]))]);
+    for (k, v) in extra.as_object().unwrap() {
+        record.insert(k.clone(), v.clone());
+    }
+    record
+}
+
+#[test]
+fn test_range_filters_on_list_fields_match_any_element() {
+    let coll = Collection::new(make_typed_config());
+    coll.create_index("idx", IndexConfig {
+        scalar_index_fields: vec!["ids".into()],
+        ..Default::default()
+    }).unwrap();
+    coll.upsert_data(&[
+        typed_record(1, json!({"ids": [1, 50], "tags": ["apple", "pear"]})),
+        typed_record(2, json!({"ids": [20, 30], "tags": ["kiwi"]})),
+        typed_record(3, json!({"ids": [], "tags": []})),
+    ]).unwrap();
+
+    let hits = |filter: serde_json::Value| {
+        let mut ids: Vec<u64> = coll.search_by_vector("idx", &[1.0, 0.0], 10, 0, Some(&filter)).unwrap()
+            .data.iter().map(|item| item.id.as_u64().unwrap()).collect();
+        ids.sort();
+        ids
+    };
+    assert_eq!(hits(json!({"op": "range", "field": "ids", "gte": 40})), vec![1]);
+    assert_eq!(hits(json!({"op": "range", "field": "ids", "gt": 10, "lt": 40})), vec![2]);
+    assert_eq!(hits(json!({"op": "range_out", "field": "ids", "gte": 10, "lte": 40})), vec![1]);
+    assert_eq!(hits(json!({"op": "range", "field": "tags", "gte": "k", "lt": "o"})), vec![2]);
+}
+
+#[test]
+fn test_upsert_coerces_values() {
+    let coll = Collection::new(make_typed_config());
+    let result = coll.upsert_data(&[typed_record(1, json!({
+        "count": "42",
+        "score": "1.5",
+        "flag": "TRUE",
+        "tags": "solo",
+        "ids": [1, "2", 3.0],
+        "ts": "2024-01-02",
+        "loc": [116.4, 39.9],
+        "uri": "viking://a/b",
+    }))]).unwrap();
+    assert!(result.warnings.is_empty());
+
+    let fields = coll.fetch_data(&[json!(1)]).remove(0).unwrap();
+    assert_eq!(fields["count"], json!(42));
+    assert_eq!(fields["score"], json!(1.5));
+    assert_eq!(fields["flag"], json!(true));
+    assert_eq!(fields["tags"], json!(["solo"]));
+    assert_eq!(fields["ids"], json!([1, 2, 3]));
+    assert_eq!(fields["ts"], json!("2024-01-02T00:00:00Z"));
+    assert_eq!(fields["loc"], json!("116.4,39.9"));
+
+    coll.upsert_data(&[typed_record(2, json!({"ts": 0}))]).unwrap();
+    assert_eq!(coll.fetch_data(&[json!(2)])[0].as_ref().unwrap()["ts"], json!("1970-01-01T00:00:00Z"));
+}
+
+#[test]
+fn test_lenient_upsert_drops_bad_values_with_warnings() {
+    let coll = Collection::new(make_typed_config());
+    let result = coll.upsert_data(&[
+        typed_record(1, json!({"count": 7})),
+        typed_record(2, json!({"count": "many", "ts": "garbage", "note": "kept"})),
+    ]).unwrap();
+    assert_eq!(result.ids.len(), 2);
+    let mut warned: Vec<_> = result.warnings.iter().map(|w| (w.record, w.field.as_str())).collect();
+    warned.sort();
+    assert_eq!(warned, vec![(1, "count"), (1, "note"), (1, "ts")]);
+
+    let fields = coll.fetch_data(&[json!(2)]).remove(0).unwrap();
+    assert!(!fields.contains_key("count"));
+    assert!(!fields.contains_key("ts"));
+    assert_eq!(fields["note"], json!("kept"));
+}
+
+#[test]
+fn test_strict_upsert_rejects_batch() {
+    let coll = Collection::new(make_typed_config()).with_schema_mode(SchemaMode::Strict);
+    let err = coll.upsert_data(&[
+        typed_record(1, json!({"ids": [1, "x"]})),
+        typed_record(2, json!({"count": 3})),
+        typed_record(3, json!({"loc": "north"})),
+    ]).unwrap_err();
+    match err {
+        VectorDbError::InvalidRecords(issues) => {
+            let mut bad: Vec<_> = issues.iter().map(|i| (i.record, i.field.clone())).collect();
+            bad.sort();
+            assert_eq!(bad, vec![(0, "ids".to_string()), (2, "loc".to_string())]);
+            assert!(issues.iter().any(|i| i.message.contains("element 1")));
+        }
+        other => panic!("expected InvalidRecords, got {other:?}"),
+    }
+    assert_eq!(coll.count(), 0);
+
+    let unknown = coll.upsert_data(&[typed_record(4, json!({"extra": 1}))]);
+    assert!(matches!(unknown, Err(VectorDbError::InvalidRecords(_))));
+    coll.upsert_data(&[typed_record(5, json!({"count": "5"}))]).unwrap();
+    assert_eq!(coll.count(), 1);
+}
+
+#[test]
+fn test_coerced_values_are_filterable() {
+    let coll = Collection::new(make_typed_config());
+    coll.create_index("idx", IndexConfig { scalar_index_fields: vec!["count".into()], ..Default::default() }).unwrap();
+    let data: Vec<_> = (0..10).map(|i| typed_record(i, json!({"count": i.to_string()}))).collect();
+    coll.upsert_data(&data).unwrap();
+    let filter = json!({"op": "range", "field": "count", "gte": 7});
+    let result = coll.search_by_vector("idx", &[1.0, 0.0], 10, 0, Some(&filter)).unwrap();
+    assert_eq!(result.data.len(), 3);
+}
+
+#[test]
+fn test_unknown_fields_follow_schema_mode() {
+    let lenient = Collection::new(make_typed_config());
+    lenient.create_index("idx", IndexConfig::default()).unwrap();
+    let data: Vec<_> = (0..4).map(|i| typed_record(i, json!({"note": format!("n{}", i % 2)}))).collect();
+    lenient.upsert_data(&data).unwrap();
+    let note = json!({"op": "must", "field": "note", "conds": ["n1"]});
+    assert_eq!(lenient.count_by_filter(Some(&note)).unwrap(), 2);
+    assert_eq!(lenient.search_by_vector("idx", &[1.0, 0.0], 10, 0, Some(&note)).unwrap().data.len(), 2);
+    let AggregationResult::Terms(buckets) = lenient.aggregate(&Aggregation::terms("note", 10), None).unwrap() else {
+        panic!("expected terms buckets");
+    };
+    assert_eq!(buckets.len(), 2);
+
+    let strict = Collection::new(make_typed_config()).with_schema_mode(SchemaMode::Strict);
+    strict.create_index("idx", IndexConfig::default()).unwrap();
+    match strict.count_by_filter(Some(&note)) {
+        Err(VectorDbError::InvalidFilter { path, .. }) => assert_eq!(path, "$.field"),
+        other => panic!("expected InvalidFilter, got {other:?}"),
+    }
+    assert!(strict.search_by_vector("idx", &[1.0, 0.0], 10, 0, Some(&note)).is_err());
+    assert!(strict.aggregate(&Aggregation::terms("note", 10), None).is_err());
+    let group_by = GroupBy::new("note", 2, 1);
+    assert!(strict.search_grouped("idx", IndexQuery::Dense(&[1.0, 0.0]), &group_by, None).is_err());
+    assert!(lenient.search_grouped("idx", IndexQuery::Dense(&[1.0, 0.0]), &group_by, None).is_ok());
+}
+
+// ============================================================
+// Multi-Vector Field Tests
+// ============================================================
+
+fn make_multi_vector_config() -> CollectionConfig {
+    CollectionConfig {
+        name: "multi".into(),
+        fields: vec![
+            FieldDef { name: "id".into(), field_type: FieldType::Int64, is_primary_key: true, dim: None },
+            FieldDef { name: "abstract_vec".into(), field_type: FieldType::Vector, is_primary_key: false, dim: Some(2) },
+            FieldDef { name: "content_vec".into(), field_type: FieldType::Vector, is_primary_key: false, dim: Some(3) },
+            FieldDef { name: "title".into(), field_type: FieldType::String, is_primary_key: false, dim: None },
+        ],
+        description: String::new(),
+    }
+}
+
+fn dense_on(field: &str, index_type: &str) -> IndexConfig {
+    IndexConfig {
+        index_type: index_type.into(),
+        distance: DistanceMetric::L2,
+        vector_field: Some(field.into()),
+        ..Default::default()
+    }
+}
+
+/// Record 1 is nearest to the x axis in both fields, record 2 to the y axis.
+fn multi_vector_records() -> Vec<HashMap<String, serde_json::Value>> {
+    vec![
+        HashMap::from([
+            ("id".into(), json!(1)),
+            ("abstract_vec".into(), json!([1.0, 0.0])),
+            ("content_vec".into(), json!([0.0, 0.0, 1.0])),
+        ]),
+        HashMap::from([
+            ("id".into(), json!(2)),
+            ("abstract_vec".into(), json!([0.0, 1.0])),
+            ("content_vec".into(), json!([1.0, 0.0, 0.0])),
+        ]),
+        // No content embedding yet.
+        HashMap::from([("id".into(), json!(3)), ("abstract_vec".into(), json!([0.5, 0.5]))]),
+    ]
+}
+
+#[test]
+fn test_indexes_bind_to_vector_fields() {
+    let coll = Collection::new(make_multi_vector_config());
+    coll.create_index("abstract", dense_on("abstract_vec", "flat")).unwrap();
+    coll.create_index("content", dense_on("content_vec", "hnsw")).unwrap();
+    coll.upsert_data(&multi_vector_records()).unwrap();
+
+    let hits = coll.search_by_vector("abstract", &[1.0, 0.0], 3, 0, None).unwrap();
+    assert_eq!(hits.data.len(), 3);
+    assert_eq!(hits.data[0].id, json!(1));
+    let hits = coll.search_by_vector("content", &[1.0, 0.0, 0.0], 3, 0, None).unwrap();
+    assert_eq!(hits.data.iter().map(|d| d.id.clone()).collect::<Vec<_>>(), vec![json!(2), json!(1)]);
+
+    let hits = coll.search_by_vector_field("content_vec", &[0.0, 0.0, 1.0], 1, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(1));
+    let filter = json!({"op": "must", "field": "id", "conds": [2]});
+    let hits = coll.search_by_vector_field("abstract_vec", &[1.0, 0.0], 5, 0, Some(&filter)).unwrap();
+    assert_eq!(hits.data.len(), 1);
+    assert!(matches!(
+        coll.search_by_vector("content", &[1.0, 0.0], 1, 0, None),
+        Err(VectorDbError::DimensionMismatch { expected: 3, got: 2 })
+    ));
+}
+
+#[test]
+fn test_default_index_uses_first_vector_field() {
+    let coll = Collection::new(make_multi_vector_config());
+    coll.create_index("default", IndexConfig { distance: DistanceMetric::L2, ..Default::default() }).unwrap();
+    coll.upsert_data(&multi_vector_records()).unwrap();
+    let hits = coll.search_by_vector_field("abstract_vec", &[0.0, 1.0], 1, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(2));
+    assert!(matches!(
+        coll.search_by_vector_field("content_vec", &[1.0, 0.0, 0.0], 1, 0, None),
+        Err(VectorDbError::IndexNotFound(_))
+    ));
+}
+
+#[test]
+fn test_vector_fields_checked_per_field() {
+    let coll = Collection::new(make_multi_vector_config());
+    for field in ["title", "missing"] {
+        assert!(matches!(coll.create_index("bad", dense_on(field, "flat")), Err(VectorDbError::InvalidConfig(_))));
+    }
+    let mut record = multi_vector_records().remove(0);
+    record.insert("content_vec".into(), json!([1.0, 0.0]));
+    assert!(matches!(coll.upsert_data(&[record]), Err(VectorDbError::DimensionMismatch { expected: 3, got: 2 })));
+}
+
+#[test]
+fn test_fetch_returns_every_vector_field() {
+    let coll = Collection::new(make_multi_vector_config());
+    coll.upsert_data(&multi_vector_records()).unwrap();
+    let fields = coll.fetch_data(&[json!(1), json!(3)]);
+    let first = fields[0].as_ref().unwrap();
+    assert_eq!(first["abstract_vec"], json!([1.0, 0.0]));
+    assert_eq!(first["content_vec"], json!([0.0, 0.0, 1.0]));
+    assert!(!fields[1].as_ref().unwrap().contains_key("content_vec"));
+}
+
+#[test]
+fn test_multi_vector_indexes_survive_reopen() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("multi");
+    {
+        let coll = Collection::with_path(make_multi_vector_config(), path.clone()).unwrap();
+        coll.create_index("content", dense_on("content_vec", "hnsw")).unwrap();
+        coll.upsert_data(&multi_vector_records()).unwrap();
+    }
+    let coll = Collection::with_path(make_multi_vector_config(), path).unwrap();
+    let hits = coll.search_by_vector_field("content_vec", &[1.0, 0.0, 0.0], 5, 0, None).unwrap();
+    assert_eq!(hits.data.iter().map(|d| d.id.clone()).collect::<Vec<_>>(), vec![json!(2), json!(1)]);
+}
+
+#[test]
+fn test_legacy_single_vector_records_upgrade() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("legacy");
+    std::fs::create_dir_all(&path).unwrap();
+    // records.json and a log entry from before named vector fields
+    let legacy = json!([{"label": 1, "vector": [1.0, 0.0], "fields": {"id": 1}}]);
+    std::fs::write(path.join("records.json"), serde_json::to_vec(&legacy).unwrap()).unwrap();
+    let entry = json!({"op": "upsert", "records": [{"label": 2, "vector": [0.0, 1.0], "fields": {"id": 2}}]});
+    let payload = serde_json::to_vec(&entry).unwrap();
+    let mut frame = (payload.len() as u32).to_le_bytes().to_vec();
+    frame.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    frame.extend_from_slice(&payload);
+    std::fs::write(path.join("wal.log"), frame).unwrap();
+
+    let coll = Collection::with_path(make_multi_vector_config(), path).unwrap();
+    assert_eq!(coll.count(), 2);
+    assert_eq!(coll.fetch_data(&[json!(2)])[0].as_ref().unwrap()["abstract_vec"], json!([0.0, 1.0]));
+    coll.create_index("abstract", dense_on("abstract_vec", "flat")).unwrap();
+    let hits = coll.search_by_vector("abstract", &[1.0, 0.0], 1, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(1));
+}
+
+// ============================================================
+// Quantization Tests
+// ============================================================
+
+fn random_vectors(n: usize, dim: usize) -> Vec<Vec<f32>> {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    (0..n).map(|_| (0..dim).map(|_| rng.gen::<f32>() * 2.0 - 1.0).collect()).collect()
+}
+
+fn overlap(a: &SearchResult, b: &SearchResult) -> usize {
+    let set: std::collections::HashSet<u64> = a.ids.iter().copied().collect();
+    b.ids.iter().filter(|l| set.contains(l)).count()
+}
+
+#[test]
+fn test_int8_flat_close_to_exact() {
+    let dim = 32;
+    let exact = FlatIndex::new(dim, DistanceMetric::Cosine);
+    let int8 = FlatIndex::new(dim, DistanceMetric::Cosine).with_quantization(Quantization::Int8);
+    for (i, v) in random_vectors(500, dim).iter().enumerate() {
+        exact.insert(i as u64, v).unwrap();
+        int8.insert(i as u64, v).unwrap();
+    }
+    assert_eq!(int8.quantization(), Quantization::Int8);
+    assert!(int8.vector_bytes() * 3 < exact.vector_bytes());
+    let query = &random_vectors(1, dim)[0];
+    let expected = exact.search(query, 10).unwrap();
+    let result = int8.search(query, 10).unwrap();
+    assert!(overlap(&expected, &result) >= 8, "int8 recall too low");
+    assert!((expected.scores[0] - result.scores[0]).abs() < 0.05);
+}
+
+#[test]
+fn test_pq_trains_after_enough_vectors() {
+    let dim = 16;
+    let vectors = random_vectors(PQ_TRAIN_SIZE + 200, dim);
+    let exact = FlatIndex::new(dim, DistanceMetric::L2);
+    let pq = HnswIndex::new(dim, DistanceMetric::L2).with_quantization(Quantization::Pq { m: 8 });
+    for (i, v) in vectors.iter().enumerate() {
+        exact.insert(i as u64, v).unwrap();
+        pq.insert(i as u64, v).unwrap();
+        if i + 1 == PQ_TRAIN_SIZE - 1 {
+            // Untrained vectors are held in full precision.
+            assert_eq!(pq.vector_bytes(), exact.vector_bytes());
+        }
+    }
+    // 8 code bytes per vector, plus the codebooks.
+    let codebooks = 256 * dim * 4;
+    assert_eq!(pq.vector_bytes(), vectors.len() * 8 + codebooks);
+    let query = &vectors[7];
+    let result = pq.search(query, 10).unwrap();
+    assert_eq!(result.ids[0], 7);
+    assert!(overlap(&exact.search(query, 10).unwrap(), &result) >= 5, "pq recall too low");
+}
+
+#[test]
+fn test_pq_trains_once_under_concurrent_inserts() {
+    use std::sync::Arc;
+    let dim = 16;
+    let vectors = random_vectors(PQ_TRAIN_SIZE + 400, dim);
+    let codebooks = 256 * dim * 4;
+    let flat = Arc::new(FlatIndex::new(dim, DistanceMetric::L2).with_quantization(Quantization::Pq { m: 8 }));
+    let handles: Vec<_> = vectors.chunks(vectors.len() / 4 + 1).enumerate()
+        .map(|(t, chunk)| {
+            let (flat, chunk) = (flat.clone(), chunk.to_vec());
+            std::thread::spawn(move || {
+                for (i, v) in chunk.iter().enumerate() {
+                    flat.insert((t * 10_000 + i) as u64, v).unwrap();
+                    flat.search(v, 1).unwrap();
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    // Vectors stored while the codebooks trained were encoded on install.
+    assert_eq!(flat.vector_bytes(), vectors.len() * 8 + codebooks);
+
+    let labels: Vec<u64> = (0..vectors.len() as u64).collect();
+    let hnsw = HnswIndex::new(dim, DistanceMetric::L2).with_quantization(Quantization::Pq { m: 8 });
+    hnsw.insert_batch(&labels, &vectors).unwrap();
+    assert_eq!(hnsw.vector_bytes(), vectors.len() * 8 + codebooks);
+}
+
+#[test]
+fn test_quantized_indexes_save_and_load() {
+    let dir = TempDir::new().unwrap();
+    let dim = 8;
+    let vectors = random_vectors(PQ_TRAIN_SIZE, dim);
+    let flat = FlatIndex::new(dim, DistanceMetric::Ip).with_quantization(Quantization::Int8);
+    let hnsw = HnswIndex::new(dim, DistanceMetric::L2).with_quantization(Quantization::Pq { m: 4 });
+    for (i, v) in vectors.iter().enumerate() {
+        flat.insert(i as u64, v).unwrap();
+        hnsw.insert(i as u64, v).unwrap();
+    }
+    flat.save(&dir.path().join("flat")).unwrap();
+    hnsw.save(&dir.path().join("hnsw")).unwrap();
+
+    let mut flat2 = FlatIndex::new(dim, DistanceMetric::Ip).with_quantization(Quantization::Int8);
+    flat2.load(&dir.path().join("flat")).unwrap();
+    let mut hnsw2 = HnswIndex::new(dim, DistanceMetric::L2);
+    hnsw2.load(&dir.path().join("hnsw")).unwrap();
+    assert_eq!(hnsw2.quantization(), Quantization::Pq { m: 4 });
+    assert_eq!(hnsw2.vector_bytes(), hnsw.vector_bytes());
+    let query = &vectors[3];
+    assert_eq!(flat2.search(query, 5).unwrap().ids, flat.search(query, 5).unwrap().ids);
+    assert_eq!(hnsw2.search(query, 5).unwrap().ids, hnsw.search(query, 5).unwrap().ids);
+}
+
+fn make_quantized_config(dim: usize) -> CollectionConfig {
+    CollectionConfig {
+        name: "quantized".into(),
+        fields: vec![
+            FieldDef { name: "id".into(), field_type: FieldType::Int64, is_primary_key: true, dim: None },
+            FieldDef { name: "vector".into(), field_type: FieldType::Vector, is_primary_key: false, dim: Some(dim) },
+        ],
+        description: String::new(),
+    }
+}
+
+#[test]
+fn test_collection_rescores_quantized_hits() {
+    let dim = 16;
+    let vectors = random_vectors(300, dim);
+    let records: Vec<_> = vectors.iter().enumerate()
+        .map(|(i, v)| HashMap::from([("id".into(), json!(i)), ("vector".into(), json!(v))]))
+        .collect();
+    let coll = Collection::new(make_quantized_config(dim));
+    let quantized = |rescore| IndexConfig {
+        index_type: "hnsw".into(),
+        distance: DistanceMetric::L2,
+        quantization: Quantization::Int8,
+        rescore,
+        ..Default::default()
+    };
+    coll.create_index("exact", IndexConfig { index_type: "flat".into(), distance: DistanceMetric::L2, ..Default::default() })
+        .unwrap();
+    coll.create_index("int8", quantized(None)).unwrap();
+    coll.create_index("rescored", quantized(Some(4))).unwrap();
+    coll.upsert_data(&records).unwrap();
+
+    let query = &random_vectors(1, dim)[0];
+    let exact = coll.search_by_vector("exact", query, 10, 0, None).unwrap();
+    let approx = coll.search_by_vector("int8", query, 10, 0, None).unwrap();
+    let rescored = coll.search_by_vector("rescored", query, 10, 0, None).unwrap();
+    assert_eq!(rescored.data.len(), 10);
+    // Rescored hits carry full-precision scores.
+    for hit in &rescored.data {
+        let expected = exact.data.iter().find(|d| d.id == hit.id).map(|d| d.score);
+        assert!(expected.is_none_or(|s| (s - hit.score).abs() < 1e-6));
+    }
+    assert!(rescored.data.windows(2).all(|w| w[0].score >= w[1].score));
+    assert_eq!(approx.data.len(), 10);
+}
+
+#[test]
+fn test_collection_rescore_applies_superset_filter() {
+    let dim = 8;
+    let vectors = random_vectors(200, dim);
+    let mut config = make_quantized_config(dim);
+    for name in ["a", "b"] {
+        config.fields.push(FieldDef { name: name.into(), field_type: FieldType::Int64, is_primary_key: false, dim: None });
+    }
+    let coll = Collection::new(config);
+    let records: Vec<_> = vectors.iter().enumerate()
+        .map(|(i, v)| HashMap::from([
+            ("id".into(), json!(i)),
+            ("vector".into(), json!(v)),
+            ("a".into(), json!(i % 2)),
+            ("b".into(), json!(i % 10)),
+        ]))
+        .collect();
+    coll.create_index("exact", IndexConfig { distance: DistanceMetric::L2, ..Default::default() }).unwrap();
+    coll.create_index("rescored", IndexConfig {
+        index_type: "hnsw".into(),
+        distance: DistanceMetric::L2,
+        quantization: Quantization::Int8,
+        rescore: Some(2),
+        scalar_index_fields: vec!["a".into()],
+        ..Default::default()
+    }).unwrap();
+    coll.upsert_data(&records).unwrap();
+
+    // Only `a` is indexed, so the plan is a superset of the matches.
+    let filter = json!({"op": "and", "conds": [
+        {"op": "must", "field": "a", "conds": [0]},
+        {"op": "range", "field": "b", "lt": 3}
+    ]});
+    let query = &random_vectors(1, dim)[0];
+    let exact = coll.search_by_vector("exact", query, 10, 0, Some(&filter)).unwrap();
+    let rescored = coll.search_by_vector("rescored", query, 10, 0, Some(&filter)).unwrap();
+    assert_eq!(rescored.data.len(), 10);
+    let ids = |r: &ov_vectordb::collection::CollectionSearchResult| r.data.iter().map(|d| d.id.clone()).collect::<Vec<_>>();
+    assert_eq!(ids(&rescored), ids(&exact));
+}
+
+#[test]
+fn test_collection_rejects_bad_quantization() {
+    let coll = Collection::new(make_quantized_config(4));
+    let pq = |m| IndexConfig { quantization: Quantization::Pq { m }, ..Default::default() };
+    for m in [0, 5] {
+        assert!(matches!(coll.create_index("bad", pq(m)), Err(VectorDbError::InvalidConfig(_))));
+    }
+    coll.create_index("ok", pq(2)).unwrap();
+    let sparse = IndexConfig { index_type: "sparse".into(), quantization: Quantization::Int8, ..Default::default() };
+    assert!(matches!(coll.create_index("sparse", sparse), Err(VectorDbError::InvalidConfig(_))));
+}
+
+#[test]
+fn test_quantized_index_survives_reopen() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("quantized");
+    let records: Vec<_> = (0..20)
+        .map(|i| HashMap::from([("id".into(), json!(i)), ("vector".into(), json!([i as f32, 1.0]))]))
+        .collect();
+    let cfg = IndexConfig {
+        index_type: "hnsw".into(),
+        distance: DistanceMetric::L2,
+        quantization: Quantization::Int8,
+        rescore: Some(2),
+        ..Default::default()
+    };
+    {
+        let coll = Collection::with_path(make_quantized_config(2), path.clone()).unwrap();
+        coll.create_index("idx", cfg).unwrap();
+        coll.upsert_data(&records).unwrap();
+    }
+    let coll = Collection::with_path(make_quantized_config(2), path).unwrap();
+    let hits = coll.search_by_vector("idx", &[7.0, 1.0], 3, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(7));
+    assert_eq!(hits.data[0].score, 1.0);
+}
+
+// ============================================================
+// SIMD Distance Kernel Tests
+// ============================================================
+
+fn assert_close(actual: f32, expected: f32, what: &str) {
+    assert!((actual - expected).abs() <= 1e-4 * expected.abs().max(1.0), "{what}: {actual} vs {expected}");
+}
+
+#[test]
+fn test_simd_levels_match_scalar() {
+    let levels = SimdLevel::available();
+    assert_eq!(levels[0], SimdLevel::Scalar);
+    assert!(levels.contains(&SimdLevel::detect()));
+    // Lengths around every register width, so the tails are exercised.
+    for len in 0..70 {
+        let v = random_vectors(2, len);
+        let (a, b) = (&v[0], &v[1]);
+        let dot = distance::scalar::inner_product(a, b);
+        let l2 = distance::scalar::l2_squared(a, b);
+        for level in &levels {
+            assert_close(level.inner_product(a, b), dot, &format!("{level:?} dot, len {len}"));
+            assert_close(level.l2_squared(a, b), l2, &format!("{level:?} l2, len {len}"));
+        }
+        assert_close(distance::inner_product(a, b), dot, "dispatched dot");
+        assert_close(distance::l2_squared(a, b), l2, "dispatched l2");
+    }
+}
+
+#[test]
+fn test_simd_batch_matches_pairs() {
+    for dim in [3, 16, 37, 128] {
+        let query = &random_vectors(1, dim)[0];
+        let rows = random_vectors(11, dim);
+        let rows: Vec<&[f32]> = rows.iter().map(|r| r.as_slice()).collect();
+        for level in SimdLevel::available() {
+            let mut dots = vec![0.0; rows.len()];
+            let mut l2s = vec![0.0; rows.len()];
+            level.inner_product_batch(query, &rows, &mut dots);
+            level.l2_squared_batch(query, &rows, &mut l2s);
+            for (i, row) in rows.iter().enumerate() {
+                assert_close(dots[i], distance::scalar::inner_product(query, row), &format!("{level:?} batch dot"));
+                assert_close(l2s[i], distance::scalar::l2_squared(query, row), &format!("{level:?} batch l2"));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_compute_score_normalized() {
+    let mut vectors = random_vectors(5, 24);
+    for v in vectors.iter_mut() {
+        distance::normalize_vector(v);
+    }
+    let rows: Vec<&[f32]> = vectors[1..].iter().map(|r| r.as_slice()).collect();
+    for metric in [DistanceMetric::Cosine, DistanceMetric::Ip, DistanceMetric::L2] {
+        let mut batch = vec![0.0; rows.len()];
+        distance::compute_scores_normalized(metric, &vectors[0], &rows, &mut batch);
+        for (row, &score) in rows.iter().zip(&batch) {
+            let expected = distance::compute_score(metric, &vectors[0], row);
+            assert_close(distance::compute_score_normalized(metric, &vectors[0], row), expected, "pair");
+            assert_close(score, expected, "batch");
+        }
+    }
+}
+
+// ============================================================
+// HNSW Binary Format Tests
+// ============================================================
+
+fn make_saved_hnsw(dir: &std::path::Path, quantization: Quantization) -> (HnswIndex, Vec<Vec<f32>>) {
+    let vectors = random_vectors(300, 24);
+    let idx = HnswIndex::new(24, DistanceMetric::Cosine).with_quantization(quantization);
+    for (i, v) in vectors.iter().enumerate() {
+        idx.insert(i as u64, v).unwrap();
+    }
+    for label in [3, 4, 5] {
+        idx.delete(label).unwrap();
+    }
+    idx.insert(4, &vectors[4]).unwrap();
+    idx.save(dir).unwrap();
+    (idx, vectors)
+}
+
+#[test]
+fn test_hnsw_binary_roundtrip_eager_and_mapped() {
+    for quantization in [Quantization::None, Quantization::Int8] {
+        let dir = TempDir::new().unwrap();
+        let (idx, vectors) = make_saved_hnsw(dir.path(), quantization);
+        assert!(dir.path().join("hnsw_index.bin").exists());
+        for mmap in [false, true] {
+            let mut loaded = HnswIndex::new(24, DistanceMetric::Cosine).with_mmap(mmap);
+            loaded.load(dir.path()).unwrap();
+            assert_eq!(loaded.is_mapped(), mmap);
+            assert_eq!(loaded.quantization(), quantization);
+            assert_eq!(loaded.len(), idx.len());
+            assert_eq!(loaded.vector_bytes(), idx.vector_bytes());
+            for q in [&vectors[0], &vectors[3], &vectors[4]] {
+                assert_eq!(loaded.search(q, 10).unwrap().ids, idx.search(q, 10).unwrap().ids);
+            }
+            // Deletions survive the round trip.
+            let hits = loaded.search(&vectors[3], 300).unwrap();
+            assert!(!hits.ids.contains(&3) && !hits.ids.contains(&5));
+            assert!(hits.ids.contains(&4));
+        }
+    }
+}
+
+#[test]
+fn test_mapped_hnsw_copies_out_on_insert() {
+    let dir = TempDir::new().unwrap();
+    let (_, vectors) = make_saved_hnsw(dir.path(), Quantization::None);
+    let mut idx = HnswIndex::new(24, DistanceMetric::Cosine).with_mmap(true);
+    idx.load(dir.path()).unwrap();
+    idx.insert(1000, &vectors[7]).unwrap();
+    assert!(!idx.is_mapped());
+    let hits = idx.search(&vectors[7], 2).unwrap();
+    assert!(hits.ids.contains(&1000) && hits.ids.contains(&7));
+    // Saving over the file the index was mapped from is safe.
+    idx.save(dir.path()).unwrap();
+    let mut reloaded = HnswIndex::new(24, DistanceMetric::Cosine).with_mmap(true);
+    reloaded.load(dir.path()).unwrap();
+    assert_eq!(reloaded.len(), idx.len());
+}
+
+#[test]
+fn test_hnsw_rejects_corrupt_file() {
+    let dir = TempDir::new().unwrap();
+    make_saved_hnsw(dir.path(), Quantization::None);
+    let path = dir.path().join("hnsw_index.bin");
+    let mut bytes = std::fs::read(&path).unwrap();
+    // Flip a byte of the first label, inside the checksummed graph.
+    let last = bytes.len() - 1;
+    bytes[200] ^= 0xff;
+    std::fs::write(&path, &bytes).unwrap();
+    let mut idx = HnswIndex::new(24, DistanceMetric::Cosine);
+    assert!(matches!(idx.load(dir.path()), Err(VectorDbError::Serialization(_))));
+
+    bytes[200] ^= 0xff;
+    bytes[last] ^= 0xff;
+    std::fs::write(&path, &bytes).unwrap();
+    assert!(matches!(idx.load(dir.path()), Err(VectorDbError::Serialization(_))));
+    std::fs::write(&path, &bytes[..bytes.len() / 2]).unwrap();
+    let mut mapped = HnswIndex::new(24, DistanceMetric::Cosine).with_mmap(true);
+    assert!(mapped.load(dir.path()).is_err());
+}
+
+#[test]
+fn test_hnsw_json_file_migrates_on_load() {
+    let dir = TempDir::new().unwrap();
+    // Label 6 was deleted and re-inserted as node 2.
+    let legacy = json!({
+        "dimension": 2, "m": 16, "ef_construction": 200, "ef_search": 50, "metric": "l2",
+        "vectors": [[0.0, 0.0], [1.0, 0.0], [1.0, 0.5]],
+        "id_to_label": [5, 6, 6],
+        "node_levels": [0, 0, 0],
+        "layers": [[[1, 2], [0, 2], [0, 1]]],
+        "entry_point": 0,
+        "max_level": 0,
+    });
+    std::fs::write(dir.path().join("hnsw_index.json"), serde_json::to_vec(&legacy).unwrap()).unwrap();
+    let mut idx = HnswIndex::new(2, DistanceMetric::L2);
+    idx.load(dir.path()).unwrap();
+    assert!(!dir.path().join("hnsw_index.json").exists());
+    assert!(dir.path().join("hnsw_index.bin").exists());
+    assert_eq!(idx.len(), 2);
+    let hits = idx.search(&[1.0, 0.0], 3).unwrap();
+    assert_eq!(hits.ids, vec![6, 5]);
+    assert_eq!(hits.scores[0], 0.8);
+
+    let mut reloaded = HnswIndex::new(2, DistanceMetric::L2).with_mmap(true);
+    reloaded.load(dir.path()).unwrap();
+    assert_eq!(reloaded.search(&[1.0, 0.0], 3).unwrap().ids, vec![6, 5]);
+}
+
+#[test]
+fn test_collection_mmap_index_reopens() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("mapped");
+    let records: Vec<_> = (0..20)
+        .map(|i| HashMap::from([("id".into(), json!(i)), ("vector".into(), json!([i as f32, 1.0]))]))
+        .collect();
+    let cfg = IndexConfig { index_type: "hnsw".into(), distance: DistanceMetric::L2, mmap: true, ..Default::default() };
+    {
+        let coll = Collection::with_path(make_quantized_config(2), path.clone()).unwrap();
+        coll.create_index("idx", cfg).unwrap();
+        coll.upsert_data(&records).unwrap();
+    }
+    let coll = Collection::with_path(make_quantized_config(2), path).unwrap();
+    let hits = coll.search_by_vector("idx", &[7.0, 1.0], 2, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(7));
+    coll.upsert_data(&[HashMap::from([("id".into(), json!(100)), ("vector".into(), json!([7.0, 1.1]))])]).unwrap();
+    let hits = coll.search_by_vector("idx", &[7.0, 1.1], 1, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(100));
+
+    let flat = IndexConfig { mmap: true, ..Default::default() };
+    assert!(matches!(coll.create_index("flat", flat), Err(VectorDbError::InvalidConfig(_))));
+}
+
+// ============================================================
+// HNSW Vacuum Tests
+// ============================================================
+
+fn make_churned_hnsw() -> (HnswIndex, Vec<Vec<f32>>) {
+    let vectors = random_vectors(600, 16);
+    let idx = HnswIndex::with_params(16, DistanceMetric::L2, 8, 64, 32);
+    for (i, v) in vectors.iter().enumerate() {
+        idx.insert(i as u64, v).unwrap();
+    }
+    for label in (0..600u64).filter(|l| l % 3 != 0) {
+        idx.delete(label).unwrap();
+    }
+    (idx, vectors)
+}
+
+#[test]
+fn test_hnsw_tombstone_ratio() {
+    let idx = HnswIndex::new(2, DistanceMetric::L2).with_vacuum_threshold(0.5);
+    for i in 0u64..10 {
+        idx.insert(i, &[i as f32, 0.0]).unwrap();
+    }
+    for i in 0u64..4 {
+        idx.delete(i).unwrap();
+    }
+    assert!((idx.tombstone_ratio() - 0.4).abs() < 1e-6);
+    assert!(!idx.needs_rebuild());
+    idx.delete(4).unwrap();
+    assert!(idx.needs_rebuild());
+    assert_eq!(idx.vacuum().unwrap(), 5);
+    assert_eq!(idx.tombstone_ratio(), 0.0);
+    assert!(!idx.needs_rebuild());
+}
+
+#[test]
+fn test_hnsw_vacuum_keeps_live_nodes_reachable() {
+    let (idx, vectors) = make_churned_hnsw();
+    let bytes_before = idx.vector_bytes();
+    assert_eq!(idx.vacuum().unwrap(), 400);
+    assert_eq!(idx.len(), 200);
+    assert!(idx.vector_bytes() * 2 < bytes_before);
+
+    let flat = FlatIndex::new(16, DistanceMetric::L2);
+    for label in (0..600u64).filter(|l| l % 3 == 0) {
+        flat.insert(label, &vectors[label as usize]).unwrap();
+    }
+    let all = idx.search(&vectors[0], 200).unwrap();
+    assert!(all.len() >= 198, "only {} live nodes reachable", all.len());
+    assert!(all.ids.iter().all(|l| l % 3 == 0));
+    let mut found = 0;
+    for q in random_vectors(20, 16) {
+        found += overlap(&flat.search(&q, 10).unwrap(), &idx.search(&q, 10).unwrap());
+    }
+    assert!(found >= 180, "recall after vacuum too low: {found}/200");
+}
+
+#[test]
+fn test_hnsw_vacuum_handles_reinserts_and_emptying() {
+    let idx = HnswIndex::new(2, DistanceMetric::L2);
+    for i in 0u64..50 {
+        idx.insert(i, &[i as f32, 0.0]).unwrap();
+    }
+    idx.delete(7).unwrap();
+    idx.insert(7, &[7.0, 0.5]).unwrap();
+    assert_eq!(idx.vacuum().unwrap(), 1);
+    assert_eq!(idx.search(&[7.0, 0.5], 1).unwrap().ids, vec![7]);
+    assert_eq!(idx.len(), 50);
+
+    for i in 0u64..50 {
+        idx.delete(i).unwrap();
+    }
+    assert_eq!(idx.vacuum().unwrap(), 50);
+    assert!(idx.is_empty());
+    assert!(idx.search(&[1.0, 0.0], 5).unwrap().is_empty());
+    idx.insert(1, &[1.0, 0.0]).unwrap();
+    assert_eq!(idx.search(&[1.0, 0.0], 5).unwrap().ids, vec![1]);
+}
+
+#[test]
+fn test_hnsw_vacuum_in_background_while_searching() {
+    let (idx, vectors) = make_churned_hnsw();
+    let idx = std::sync::Arc::new(idx);
+    let handle = idx.spawn_vacuum();
+    for _ in 0..50 {
+        let hits = idx.search(&vectors[3], 5).unwrap();
+        assert!(hits.ids.iter().all(|l| l % 3 == 0));
+    }
+    assert_eq!(handle.join().unwrap().unwrap(), 400);
+
+    let dir = TempDir::new().unwrap();
+    idx.save(dir.path()).unwrap();
+    let mut loaded = HnswIndex::new(16, DistanceMetric::L2);
+    loaded.load(dir.path()).unwrap();
+    assert_eq!(loaded.len(), 200);
+    assert_eq!(loaded.tombstone_ratio(), 0.0);
+    assert_eq!(loaded.search(&vectors[3], 5).unwrap().ids, idx.search(&vectors[3], 5).unwrap().ids);
+}
+
+#[test]
+fn test_collection_vacuums_dense_indexes() {
+    let records: Vec<_> = (0..100)
+        .map(|i| HashMap::from([("id".into(), json!(i)), ("vector".into(), json!([i as f32, 1.0]))]))
+        .collect();
+    let coll = std::sync::Arc::new(Collection::new(make_quantized_config(2)));
+    coll.create_index("idx", IndexConfig { index_type: "hnsw".into(), distance: DistanceMetric::L2, ..Default::default() })
+        .unwrap();
+    coll.upsert_data(&records).unwrap();
+    coll.delete_data(&(0..10).map(|i| json!(i)).collect::<Vec<_>>()).unwrap();
+    // Below the threshold nothing is vacuumed.
+    assert_eq!(coll.vacuum().unwrap(), 0);
+    coll.delete_data(&(10..60).map(|i| json!(i)).collect::<Vec<_>>()).unwrap();
+    assert_eq!(coll.spawn_vacuum().join().unwrap().unwrap(), 60);
+    let hits = coll.search_by_vector("idx", &[0.0, 1.0], 3, 0, None).unwrap();
+    assert_eq!(hits.data.iter().map(|d| d.id.clone()).collect::<Vec<_>>(), vec![json!(60), json!(61), json!(62)]);
+}
+
+// ============================================================
+// IVF Index Tests
+// ============================================================
+
+/// `n` points scattered around `clusters` random centres, shifted by `offset`.
+fn clustered_vectors(n: usize, dim: usize, clusters: usize, offset: f32) -> Vec<Vec<f32>> {
+    let centres = random_vectors(clusters, dim);
+    random_vectors(n, dim).into_iter().enumerate()
+        .map(|(i, noise)| centres[i % clusters].iter().zip(noise).map(|(c, e)| c * 10.0 + e + offset).collect())
+        .collect()
+}
+
+#[test]
+fn test_ivf_untrained_search_is_exact() {
+    let vectors = random_vectors(100, 8);
+    let ivf = IvfIndex::with_params(8, DistanceMetric::Cosine, 16, 2);
+    let flat = FlatIndex::new(8, DistanceMetric::Cosine);
+    for (i, v) in vectors.iter().enumerate() {
+        ivf.insert(i as u64, v).unwrap();
+        flat.insert(i as u64, v).unwrap();
+    }
+    assert!(!ivf.is_trained());
+    let q = &vectors[5];
+    assert_eq!(ivf.search(q, 10).unwrap().ids, flat.search(q, 10).unwrap().ids);
+    assert!(matches!(ivf.search(&[1.0], 1), Err(VectorDbError::DimensionMismatch { .. })));
+}
+
+#[test]
+fn test_ivf_trains_and_probes_nearest_lists() {
+    let vectors = clustered_vectors(800, 16, 8, 0.0);
+    let ivf = IvfIndex::with_params(16, DistanceMetric::L2, 16, 4);
+    let flat = FlatIndex::new(16, DistanceMetric::L2);
+    for (i, v) in vectors.iter().enumerate() {
+        ivf.insert(i as u64, v).unwrap();
+        flat.insert(i as u64, v).unwrap();
+    }
+    assert!(ivf.is_trained());
+    assert_eq!(ivf.len(), 800);
+    let mut found = 0;
+    for q in vectors.iter().step_by(40) {
+        found += overlap(&flat.search(q, 10).unwrap(), &ivf.search(q, 10).unwrap());
+    }
+    assert!(found >= 190, "ivf recall too low: {found}/200");
+
+    // Updates move a vector to its new list; deletes drop it.
+    ivf.insert(3, &vectors[500]).unwrap();
+    assert_eq!(ivf.len(), 800);
+    let hits = ivf.search(&vectors[500], 2).unwrap();
+    assert!(hits.ids.contains(&3) && hits.ids.contains(&500));
+    ivf.delete(500).unwrap();
+    ivf.delete(500).unwrap();
+    assert_eq!(ivf.len(), 799);
+    assert!(!ivf.search(&vectors[500], 5).unwrap().ids.contains(&500));
+}
+
+#[test]
+fn test_ivf_filtered_search_probes_beyond_nprobe() {
+    let vectors = clustered_vectors(600, 8, 6, 0.0);
+    let ivf = IvfIndex::with_params(8, DistanceMetric::L2, 12, 1);
+    for (i, v) in vectors.iter().enumerate() {
+        ivf.insert(i as u64, v).unwrap();
+    }
+    // Only labels from one cluster pass; query from another.
+    let hits = ivf.search_filtered(&vectors[0], 5, &|l| l % 6 == 1).unwrap();
+    assert_eq!(hits.len(), 5);
+    assert!(hits.ids.iter().all(|l| l % 6 == 1));
+}
+
+#[test]
+fn test_ivf_pq_compresses_vectors() {
+    let n = PQ_TRAIN_SIZE + 200;
+    let vectors = clustered_vectors(n, 32, 16, 0.0);
+    let plain = IvfIndex::with_params(32, DistanceMetric::L2, 16, 4);
+    let pq = IvfIndex::with_params(32, DistanceMetric::L2, 16, 4).with_quantization(Quantization::Pq { m: 8 });
+    for (i, v) in vectors.iter().enumerate() {
+        plain.insert(i as u64, v).unwrap();
+        pq.insert(i as u64, v).unwrap();
+    }
+    assert_eq!(pq.quantization(), Quantization::Pq { m: 8 });
+    assert!(pq.vector_bytes() * 3 < plain.vector_bytes());
+    let mut found = 0;
+    for q in vectors.iter().step_by(60) {
+        found += overlap(&plain.search(q, 10).unwrap(), &pq.search(q, 10).unwrap());
+    }
+    assert!(found >= 80, "ivf-pq recall too low: {found}/210");
+}
+
+#[test]
+fn test_ivf_save_load_roundtrip() {
+    let dir = TempDir::new().unwrap();
+    let vectors = clustered_vectors(300, 8, 4, 0.0);
+    let ivf = IvfIndex::with_params(8, DistanceMetric::Ip, 8, 2).with_quantization(Quantization::Int8);
+    for (i, v) in vectors.iter().enumerate() {
+        ivf.insert(i as u64, v).unwrap();
+    }
+    ivf.delete(7).unwrap();
+    ivf.save(dir.path()).unwrap();
+
+    let mut loaded = IvfIndex::new(8, DistanceMetric::L2);
+    loaded.load(dir.path()).unwrap();
+    assert!(loaded.is_trained());
+    assert_eq!((loaded.nlist(), loaded.nprobe()), (8, 2));
+    assert_eq!(loaded.metric(), DistanceMetric::Ip);
+    assert_eq!(loaded.quantization(), Quantization::Int8);
+    assert_eq!(loaded.len(), 299);
+    assert_eq!(loaded.search(&vectors[1], 10).unwrap().ids, ivf.search(&vectors[1], 10).unwrap().ids);
+    loaded.delete(8).unwrap();
+    assert_eq!(loaded.len(), 298);
+
+    std::fs::write(dir.path().join("ivf_index.json"), b"{\"dimension\": 8}").unwrap();
+    assert!(loaded.load(dir.path()).is_err());
+}
+
+#[test]
+fn test_ivf_needs_rebuild_after_drift() {
+    let ivf = IvfIndex::with_params(8, DistanceMetric::L2, 8, 2);
+    for (i, v) in clustered_vectors(320, 8, 8, 0.0).iter().enumerate() {
+        ivf.insert(i as u64, v).unwrap();
+    }
+    assert!(ivf.is_trained());
+    assert!(!ivf.needs_rebuild());
+    for (i, v) in clustered_vectors(200, 8, 8, 50.0).iter().enumerate() {
+        ivf.insert(1000 + i as u64, v).unwrap();
+    }
+    assert!(ivf.drift() > 2.0);
+    assert!(ivf.needs_rebuild());
+    ivf.retrain();
+    assert_eq!(ivf.drift(), 0.0);
+    assert!(!ivf.needs_rebuild());
+    assert_eq!(ivf.len(), 520);
+}
+
+#[test]
+fn test_collection_ivf_index() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("ivf");
+    let vectors = clustered_vectors(400, 4, 4, 0.0);
+    let records: Vec<_> = vectors.iter().enumerate()
+        .map(|(i, v)| HashMap::from([("id".into(), json!(i)), ("vector".into(), json!(v))]))
+        .collect();
+    {
+        let coll = Collection::with_path(make_quantized_config(4), path.clone()).unwrap();
+        let bad = IndexConfig { index_type: "hnsw".into(), nlist: Some(8), ..Default::default() };
+        assert!(matches!(coll.create_index("bad", bad), Err(VectorDbError::InvalidConfig(_))));
+        let zero = IndexConfig { index_type: "ivf".into(), nprobe: Some(0), ..Default::default() };
+        assert!(matches!(coll.create_index("zero", zero), Err(VectorDbError::InvalidConfig(_))));
+        let cfg = IndexConfig {
+            index_type: "ivf".into(),
+            distance: DistanceMetric::L2,
+            nlist: Some(4),
+            nprobe: Some(2),
+            ..Default::default()
+        };
+        coll.create_index("idx", cfg).unwrap();
+        coll.upsert_data(&records).unwrap();
+        let hits = coll.search_by_vector("idx", &vectors[9], 1, 0, None).unwrap();
+        assert_eq!(hits.data[0].id, json!(9));
+    }
+    let meta: IndexMeta = serde_json::from_slice(&std::fs::read(path.join("indexes/idx/index_meta.json")).unwrap()).unwrap();
+    assert_eq!((meta.index_type.as_str(), meta.nlist, meta.nprobe), ("ivf", Some(4), Some(2)));
+    assert!(path.join("indexes/idx/ivf_index.json").exists());
+    let coll = Collection::with_path(make_quantized_config(4), path).unwrap();
+    let hits = coll.search_by_vector("idx", &vectors[9], 1, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(9));
+}
+
+// ============================================================
+// Concurrent HNSW Construction Tests
+// ============================================================
+
+#[test]
+fn test_hnsw_insert_batch_recall() {
+    let vectors = random_vectors(3000, 32);
+    let labels: Vec<u64> = (0..3000).collect();
+    let idx = HnswIndex::with_params(32, DistanceMetric::L2, 16, 100, 64);
+    idx.insert_batch(&labels, &vectors).unwrap();
+    assert_eq!(idx.len(), 3000);
+
+    let flat = FlatIndex::new(32, DistanceMetric::L2);
+    flat.insert_batch(&labels, &vectors).unwrap();
+    let mut found = 0;
+    for q in random_vectors(20, 32) {
+        found += overlap(&flat.search(&q, 10).unwrap(), &idx.search(&q, 10).unwrap());
+    }
+    assert!(found >= 170, "batch-built recall too low: {found}/200");
+}
+
+#[test]
+fn test_hnsw_insert_batch_updates_and_repeats() {
+    let idx = HnswIndex::new(2, DistanceMetric::L2);
+    idx.insert(1, &[1.0, 0.0]).unwrap();
+    let labels = [1, 2, 3, 2];
+    let vectors = vec![vec![5.0, 5.0], vec![0.0, 9.0], vec![3.0, 3.0], vec![-4.0, 0.0]];
+    idx.insert_batch(&labels, &vectors).unwrap();
+    assert_eq!(idx.len(), 3);
+    assert_eq!(idx.search(&[5.0, 5.0], 1).unwrap().ids, vec![1]);
+    assert_eq!(idx.search(&[-4.0, 0.0], 1).unwrap().ids, vec![2]);
+
+    let bad = idx.insert_batch(&[7, 8], &[vec![1.0, 1.0], vec![1.0]]);
+    assert!(matches!(bad, Err(VectorDbError::DimensionMismatch { .. })));
+    assert_eq!(idx.len(), 3);
+}
+
+#[test]
+fn test_hnsw_parallel_build_keeps_back_links() {
+    let vectors = random_vectors(2000, 8);
+    let m = 4;
+    let idx = HnswIndex::with_params(8, DistanceMetric::L2, m, 32, 32);
+    idx.insert_batch(&(0..2000).collect::<Vec<_>>(), &vectors).unwrap();
+
+    let links: Vec<Vec<Vec<u64>>> = (0..2000).map(|label| idx.neighbors(label).unwrap()).collect();
+    let score = |a: u64, b: u64| distance::compute_score_normalized(DistanceMetric::L2, &vectors[a as usize], &vectors[b as usize]);
+    for (node, levels) in links.iter().enumerate() {
+        let node = node as u64;
+        for (lev, neighbors) in levels.iter().enumerate() {
+            let max_neighbors = if lev == 0 { m * 2 } else { m };
+            assert!(neighbors.len() <= max_neighbors);
+            for &n in neighbors {
+                let back = &links[n as usize][lev];
+                // A missing back-link must have lost out to closer nodes.
+                assert!(
+                    back.contains(&node)
+                        || (back.len() == max_neighbors && back.iter().all(|&b| score(n, b) >= score(n, node))),
+                    "{node} -> {n} on level {lev} has no back-link",
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_hnsw_searches_run_during_batch_insert() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    let vectors = random_vectors(1500, 16);
+    let idx = std::sync::Arc::new(HnswIndex::with_params(16, DistanceMetric::L2, 16, 64, 50));
+    idx.insert_batch(&(0..100).collect::<Vec<_>>(), &vectors[..100]).unwrap();
+
+    let done = std::sync::Arc::new(AtomicBool::new(false));
+    let loader = {
+        let (idx, done, vectors) = (idx.clone(), done.clone(), vectors.clone());
+        std::thread::spawn(move || {
+            idx.insert_batch(&(100..1500).collect::<Vec<_>>(), &vectors[100..]).unwrap();
+            done.store(true, Ordering::SeqCst);
+        })
+    };
+    let mut searched_while_loading = 0;
+    while !done.load(Ordering::SeqCst) {
+        assert_eq!(idx.search(&vectors[0], 5).unwrap().len(), 5);
+        searched_while_loading += 1;
+    }
+    loader.join().unwrap();
+    assert!(searched_while_loading > 0);
+    assert_eq!(idx.len(), 1500);
+    let found = (0..1500).step_by(5).filter(|&i| idx.search(&vectors[i], 1).unwrap().ids == vec![i as u64]).count();
+    assert!(found >= 294, "only {found}/300 vectors find themselves");
+}
+
+#[test]
+fn test_hnsw_concurrent_inserts_and_vacuum() {
+    let vectors = random_vectors(2000, 16);
+    let idx = std::sync::Arc::new(HnswIndex::with_params(16, DistanceMetric::L2, 8, 64, 64));
+    idx.insert_batch(&(0..1000).collect::<Vec<_>>(), &vectors[..1000]).unwrap();
+    for label in (0..1000).step_by(2) {
+        idx.delete(label).unwrap();
+    }
+
+    let writers: Vec<_> = (0..4u64)
+        .map(|t| {
+            let (idx, vectors) = (idx.clone(), vectors.clone());
+            std::thread::spawn(move || {
+                for i in (1000 + t * 250)..(1000 + (t + 1) * 250) {
+                    idx.insert(i, &vectors[i as usize]).unwrap();
+                }
+            })
+        })
+        .collect();
+    let vacuum = idx.spawn_vacuum();
+    for w in writers {
+        w.join().unwrap();
+    }
+    assert_eq!(vacuum.join().unwrap().unwrap(), 500);
+    assert_eq!(idx.len(), 1500);
+    assert_eq!(idx.tombstone_ratio(), 0.0);
+    let live: Vec<usize> = (1..1000).step_by(2).chain(1000..2000).collect();
+    let found = live.iter().filter(|&&i| idx.search(&vectors[i], 1).unwrap().ids == vec![i as u64]).count();
+    assert!(found * 100 >= live.len() * 98, "only {found}/{} vectors find themselves", live.len());
+}
+
+// ============================================================
+// Background Index Build Tests
+// ============================================================
+
+fn make_vector_records(ids: std::ops::Range<usize>) -> Vec<HashMap<String, serde_json::Value>> {
+    ids.map(|i| HashMap::from([("id".into(), json!(i)), ("vector".into(), json!([i as f32, 1.0]))]))
+        .collect()
+}
+
+fn hnsw_l2() -> IndexConfig {
+    IndexConfig { index_type: "hnsw".into(), distance: DistanceMetric::L2, ..Default::default() }
+}
+
+#[test]
+fn test_background_build_turns_ready() {
+    let coll = std::sync::Arc::new(Collection::new(make_quantized_config(2)));
+    coll.upsert_data(&make_vector_records(0..3000)).unwrap();
+    let handle = coll.spawn_create_index("idx", hnsw_l2()).unwrap();
+    // Until it is ready, searches report the index as not ready.
+    while coll.index_status("idx") == Some(IndexStatus::Building) {
+        match coll.search_by_vector("idx", &[5.0, 1.0], 1, 0, None) {
+            Ok(_) | Err(VectorDbError::IndexNotReady(_)) => {}
+            Err(e) => panic!("unexpected error {e}"),
+        }
+        std::thread::yield_now();
+    }
+    handle.join().unwrap().unwrap();
+    assert_eq!(coll.index_status("idx"), Some(IndexStatus::Ready));
+    assert_eq!(coll.index_status("missing"), None);
+    let hits = coll.search_by_vector("idx", &[1234.0, 1.0], 1, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(1234));
+}
+
+#[test]
+fn test_background_build_captures_concurrent_writes() {
+    let coll = std::sync::Arc::new(Collection::new(make_quantized_config(2)));
+    coll.upsert_data(&make_vector_records(0..3000)).unwrap();
+    let flat = IndexConfig { index_type: "flat".into(), distance: DistanceMetric::L2, ..Default::default() };
+    let handle = coll.spawn_create_index("idx", flat).unwrap();
+    coll.delete_data(&(0..1000).map(|i| json!(i)).collect::<Vec<_>>()).unwrap();
+    coll.upsert_data(&make_vector_records(5000..5100)).unwrap();
+    // Move record 2000 far away from where it was snapshotted.
+    coll.upsert_data(&[HashMap::from([("id".into(), json!(2000)), ("vector".into(), json!([-5000.0, 1.0]))])]).unwrap();
+    handle.join().unwrap().unwrap();
+
+    let hits = coll.search_by_vector("idx", &[0.0, 1.0], 1, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(1000));
+    let hits = coll.search_by_vector("idx", &[5050.0, 1.0], 1, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(5050));
+    let hits = coll.search_by_vector("idx", &[-5000.0, 1.0], 1, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(2000));
+}
+
+#[test]
+fn test_background_build_after_delete_all() {
+    let coll = std::sync::Arc::new(Collection::new(make_quantized_config(2)));
+    coll.upsert_data(&make_vector_records(0..3000)).unwrap();
+    let handle = coll.spawn_create_index("idx", hnsw_l2()).unwrap();
+    coll.delete_all_data().unwrap();
+    coll.upsert_data(&make_vector_records(10..20)).unwrap();
+    handle.join().unwrap().unwrap();
+    let hits = coll.search_by_vector("idx", &[0.0, 1.0], 100, 0, None).unwrap();
+    assert_eq!(hits.data.len(), 10);
+    assert_eq!(hits.data[0].id, json!(10));
+}
+
+#[test]
+fn test_background_build_rejects_duplicates_and_can_be_dropped() {
+    let coll = std::sync::Arc::new(Collection::new(make_quantized_config(2)));
+    coll.upsert_data(&make_vector_records(0..3000)).unwrap();
+    let bad = IndexConfig { index_type: "flat".into(), mmap: true, ..Default::default() };
+    assert!(matches!(coll.spawn_create_index("bad", bad), Err(VectorDbError::InvalidConfig(_))));
+    assert_eq!(coll.index_status("bad"), None);
+
+    let handle = coll.spawn_create_index("idx", hnsw_l2()).unwrap();
+    assert!(matches!(coll.spawn_create_index("idx", hnsw_l2()), Err(VectorDbError::IndexAlreadyExists(_))));
+    assert!(matches!(coll.create_index("idx", hnsw_l2()), Err(VectorDbError::IndexAlreadyExists(_))));
+    coll.drop_index("idx");
+    // A build stopped by the drop reports the index as gone.
+    match handle.join().unwrap() {
+        Ok(()) | Err(VectorDbError::IndexNotFound(_)) => {}
+        Err(e) => panic!("unexpected error {e}"),
+    }
+    assert_eq!(coll.index_status("idx"), None);
+    assert!(!coll.has_index("idx"));
+
+    coll.spawn_create_index("idx", hnsw_l2()).unwrap().join().unwrap().unwrap();
+    assert!(coll.has_index("idx"));
+}
+
+// ============================================================
+// Scroll / Filter Maintenance Tests
+// ============================================================
+
+#[test]
+fn test_scroll_pages_through_matches() {
+    let coll = make_scalar_collection("flat", 1000);
+    let filter = json!({"op": "must", "field": "parent_uri", "conds": ["viking://p/7"]});
+    let mut ids = Vec::new();
+    let mut cursor = None;
+    loop {
+        let page = coll.scroll(Some(&filter), 3, cursor).unwrap();
+        assert!(page.data.len() <= 3);
+        ids.extend(page.data.iter().map(|item| item.id.clone()));
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(ids, (0..10).map(|i| json!(i * 100 + 7)).collect::<Vec<_>>());
+
+    let page = coll.scroll(None, 2, None).unwrap();
+    assert_eq!(page.data[0].fields["vec"], json!([1.0, 0.0]));
+    assert_eq!(page.data[1].id, json!(1));
+    assert_eq!(coll.scroll(None, 5, Some(997)).unwrap().data.len(), 2);
+    assert!(coll.scroll(Some(&json!({"op": "bogus"})), 5, None).is_err());
+    assert!(coll.scroll(None, 0, None).is_err());
+}
+
+#[test]
+fn test_count_and_delete_by_filter() {
+    let coll = make_scalar_collection("hnsw", 1000);
+    let unindexed = json!({"op": "must", "field": "id", "conds": [3, 4, 5]});
+    assert_eq!(coll.count_by_filter(Some(&unindexed)).unwrap(), 3);
+    assert_eq!(coll.count_by_filter(None).unwrap(), 1000);
+
+    let filter = json!({"op": "range", "field": "n", "gte": 900});
+    assert_eq!(coll.count_by_filter(Some(&filter)).unwrap(), 100);
+    assert_eq!(coll.delete_by_filter(&filter).unwrap(), 100);
+    assert_eq!(coll.delete_by_filter(&filter).unwrap(), 0);
+    assert_eq!(coll.count(), 900);
+    let hits = coll.search_by_vector("idx", &[0.0, 1.0], 1, 0, None).unwrap();
+    assert_eq!(hits.data[0].id, json!(899));
+}
+
+#[test]
+fn test_delete_by_filter_string_keys_survives_reopen() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("spk");
+    {
+        let coll = Collection::with_path(make_string_pk_config(), path.clone()).unwrap();
+        let data: Vec<_> = (0..20)
+            .map(|i| HashMap::from([("id".into(), json!(format!("k{i}"))), ("vec".into(), json!([1.0, 0.0])), ("n".into(), json!(i % 4))]))
+            .collect();
+        coll.upsert_data(&data).unwrap();
+        assert_eq!(coll.delete_by_filter(&json!({"op": "must", "field": "n", "conds": [0]})).unwrap(), 5);
+        assert_eq!(coll.fetch_data(&[json!("k4"), json!("k5")]).iter().filter(|r| r.is_some()).count(), 1);
+    }
+    let coll = Collection::with_path(make_string_pk_config(), path).unwrap();
+    assert_eq!(coll.count(), 15);
+    let page = coll.scroll(Some(&json!({"op": "must", "field": "n", "conds": [0]})), 10, None).unwrap();
+    assert!(page.data.is_empty() && page.next_cursor.is_none());
+}
+
+// ============================================================
+// Aggregation Tests
+// ============================================================
+
+fn make_aggregation_collection(indexed: bool) -> Collection {
+    let coll = Collection::new(make_typed_config());
+    if indexed {
+        coll.create_index("idx", IndexConfig {
+            scalar_index_fields: vec!["count".into(), "tags".into(), "ts".into(), "flag".into()],
+            ..Default::default()
+        }).unwrap();
+    }
+    let data: Vec<_> = (0..12)
+        .map(|i| typed_record(i, json!({
+            "count": i % 4,
+            "flag": i % 2 == 0,
+            "tags": if i < 8 { json!(["a", "b"]) } else { json!(["c"]) },
+            "ts": format!("2024-03-{:02}T{:02}:00:00Z", 1 + i / 5, i),
+        })))
+        .collect();
+    coll.upsert_data(&data).unwrap();
+    coll
+}
+
+fn counts(result: &AggregationResult) -> Vec<(serde_json::Value, usize)> {
+    match result {
+        AggregationResult::Terms(buckets) | AggregationResult::Histogram(buckets) | AggregationResult::DateHistogram(buckets) => {
+            buckets.iter().map(|b| (b.key.clone(), b.count)).collect()
+        }
+        AggregationResult::Stats(_) => panic!("not a bucket aggregation"),
+    }
+}
+
+#[test]
+fn test_aggregate_terms_and_stats() {
+    for indexed in [false, true] {
+        let coll = make_aggregation_collection(indexed);
+        let tags = coll.aggregate(&Aggregation::terms("tags", 10), None).unwrap();
+        assert_eq!(counts(&tags), vec![(json!("a"), 8), (json!("b"), 8), (json!("c"), 4)]);
+        let top = coll.aggregate(&Aggregation::terms("count", 1), None).unwrap();
+        assert_eq!(counts(&top), vec![(json!(0), 3)]);
+
+        let stats = coll.aggregate(&Aggregation::stats("count"), None).unwrap();
+        assert_eq!(stats, AggregationResult::Stats(FieldStats {
+            count: 12,
+            min: Some(0.0),
+            max: Some(3.0),
+            sum: 18.0,
+            avg: Some(1.5),
+        }));
+        let filter = json!({"op": "must", "field": "flag", "conds": [true]});
+        let AggregationResult::Stats(even) = coll.aggregate(&Aggregation::stats("count"), Some(&filter)).unwrap() else {
+            panic!("expected stats");
+        };
+        assert_eq!((even.count, even.max), (6, Some(2.0)));
+        let AggregationResult::Stats(none) = coll.aggregate(&Aggregation::stats("tags"), None).unwrap() else {
+            panic!("expected stats");
+        };
+        assert_eq!((none.count, none.avg), (0, None));
+    }
+}
+
+#[test]
+fn test_aggregate_nested_terms() {
+    for indexed in [false, true] {
+        let coll = make_aggregation_collection(indexed);
+        let agg = Aggregation::terms("flag", 10).with_sub(Aggregation::terms("count", 10));
+        let AggregationResult::Terms(buckets) = coll.aggregate(&agg, None).unwrap() else { panic!("expected terms") };
+        assert_eq!(buckets.len(), 2);
+        let Bucket { key, count, sub } = &buckets[0];
+        assert_eq!((key, *count), (&json!(false), 6));
+        assert_eq!(counts(sub.as_ref().unwrap()), vec![(json!(1), 3), (json!(3), 3)]);
+    }
+}
+
+#[test]
+fn test_aggregate_histograms() {
+    for indexed in [false, true] {
+        let coll = make_aggregation_collection(indexed);
+        let hist = coll.aggregate(&Aggregation::histogram("count", 2.0), None).unwrap();
+        assert_eq!(counts(&hist), vec![(json!(0), 6), (json!(2), 6)]);
+
+        let day = std::time::Duration::from_secs(86_400);
+        let dates = coll.aggregate(&Aggregation::date_histogram("ts", day), None).unwrap();
+        assert_eq!(counts(&dates), vec![
+            (json!("2024-03-01T00:00:00Z"), 5),
+            (json!("2024-03-02T00:00:00Z"), 5),
+            (json!("2024-03-03T00:00:00Z"), 2),
+        ]);
+        let filter = json!({"op": "range", "field": "count", "gte": 3});
+        let dates = coll.aggregate(&Aggregation::date_histogram("ts", day), Some(&filter)).unwrap();
+        assert_eq!(counts(&dates), vec![
+            (json!("2024-03-01T00:00:00Z"), 1),
+            (json!("2024-03-02T00:00:00Z"), 1),
+            (json!("2024-03-03T00:00:00Z"), 1),
+        ]);
+    }
+}
+
+#[test]
+fn test_aggregate_rejects_bad_requests() {
+    let coll = make_aggregation_collection(false);
+    assert!(matches!(coll.aggregate(&Aggregation::terms("vec", 5), None), Err(VectorDbError::InvalidConfig(_))));
+    assert!(matches!(coll.aggregate(&Aggregation::histogram("count", 0.0), None), Err(VectorDbError::InvalidConfig(_))));
+    let zero = Aggregation::date_histogram("ts", std::time::Duration::ZERO);
+    assert!(matches!(coll.aggregate(&zero, None), Err(VectorDbError::InvalidConfig(_))));
+}
+
+// ============================================================
+// Grouped Search Tests
+// ============================================================
+
+#[test]
+fn test_search_grouped_fills_groups() {
+    let coll = make_scalar_collection("flat", 1000);
+    let query = IndexQuery::Dense(&[1.0, 0.0]);
+    let result = coll.search_grouped("idx", query, &GroupBy::new("parent_uri", 3, 2), None).unwrap();
+    let groups: Vec<_> = result.groups.iter()
+        .map(|g| (g.key.clone(), g.hits.iter().map(|h| h.id.clone()).collect::<Vec<_>>()))
+        .collect();
+    assert_eq!(groups, vec![
+        (json!("viking://p/0"), vec![json!(0), json!(100)]),
+        (json!("viking://p/1"), vec![json!(1), json!(101)]),
+        (json!("viking://p/2"), vec![json!(2), json!(102)]),
+    ]);
+    for group in &result.groups {
+        assert_eq!(group.score, group.hits[0].score);
+        assert!(group.hits[0].score >= group.hits[1].score);
+    }
+    assert!(result.groups[0].score >= result.groups[1].score);
+}
+
+#[test]
+fn test_search_grouped_with_filter_and_few_groups() {
+    let coll = make_scalar_collection("hnsw", 300);
+    let filter = json!({"op": "range", "field": "n", "gte": 250});
+    let query = IndexQuery::Dense(&[1.0, 0.0]);
+    let result = coll.search_grouped("idx", query, &GroupBy::new("parent_uri", 5, 3), Some(&filter)).unwrap();
+    assert_eq!(result.groups.len(), 5);
+    assert_eq!(result.groups[0].key, json!("viking://p/50"));
+    // Only one record per parent passes the filter.
+    assert!(result.groups.iter().all(|g| g.hits.len() == 1));
+
+    // Fewer groups exist than requested: every group comes back.
+    let result = coll.search_grouped("idx", query, &GroupBy::new("n", 1000, 1), Some(&filter)).unwrap();
+    assert_eq!(result.groups.len(), 50);
+    // No record carries the field.
+    let result = coll.search_grouped("idx", query, &GroupBy::new("missing", 3, 1), None).unwrap();
+    assert!(result.groups.is_empty());
+    assert!(matches!(
+        coll.search_grouped("idx", query, &GroupBy::new("vec", 3, 1), None),
+        Err(VectorDbError::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        coll.search_grouped("nope", query, &GroupBy::new("n", 3, 1), None),
+        Err(VectorDbError::IndexNotFound(_))
+    ));
+}
+
+// ============================================================
+// Score Boost Tests
+// ============================================================
+
+fn make_boost_collection() -> Collection {
+    let coll = Collection::new(make_typed_config());
+    coll.create_index("idx", IndexConfig { distance: DistanceMetric::Ip, ..Default::default() }).unwrap();
+    // Similarity falls with the id; recency and activity rise with it.
+    let data: Vec<_> = (0..5)
+        .map(|i| {
+            let mut record = typed_record(i, json!({"count": i * 10, "ts": format!("2024-01-{:02}T00:00:00Z", 1 + i)}));
+            record.insert("vec".into(), json!([1.0 - i as f32 * 0.01, 0.0]));
+            record
+        })
+        .collect();
+    coll.upsert_data(&data).unwrap();
+    coll.upsert_data(&[typed_record(9, json!({"vec": [0.5, 0.0]}))]).unwrap();
+    coll
+}
+
+fn ids(result: &ov_vectordb::collection::CollectionSearchResult) -> Vec<serde_json::Value> {
+    result.data.iter().map(|d| d.id.clone()).collect()
+}
+
+#[test]
+fn test_search_boosted_by_recency_and_activity() {
+    let coll = make_boost_collection();
+    let now = chrono::DateTime::parse_from_rfc3339("2024-01-05T00:00:00Z").unwrap().to_utc();
+    let query = IndexQuery::Dense(&[1.0, 0.0]);
+
+    let plain = coll.search_boosted("idx", query, &ScoreBoost::new(), 3, 0, None).unwrap();
+    assert_eq!(ids(&plain), vec![json!(0), json!(1), json!(2)]);
+
+    let day = std::time::Duration::from_secs(86_400);
+    let recent = ScoreBoost::new().decay("ts", day, 1.0).with_now(now);
+    let result = coll.search_boosted("idx", query, &recent, 3, 0, None).unwrap();
+    assert_eq!(ids(&result), vec![json!(4), json!(3), json!(2)]);
+    let explanation = result.data[1].explanation.as_ref().unwrap();
+    assert!((explanation.terms[0].value - 0.5).abs() < 1e-6);
+    assert!((explanation.similarity - 0.97).abs() < 1e-6);
+    assert!((result.data[1].score - (explanation.similarity_score + explanation.terms[0].score)).abs() < 1e-6);
+
+    let active = ScoreBoost::new().with_similarity_weight(0.0).log("count", 2.0);
+    let result = coll.search_boosted("idx", query, &active, 2, 1, None).unwrap();
+    assert_eq!(ids(&result), vec![json!(3), json!(2)]);
+    let term = &result.data[0].explanation.as_ref().unwrap().terms[0];
+    assert_eq!(term.field, "count");
+    assert!((term.score - 2.0 * 31f32.ln()).abs() < 1e-5);
+}
+
+#[test]
+fn test_search_boosted_filters_and_validates() {
+    let coll = make_boost_collection();
+    let query = IndexQuery::Dense(&[1.0, 0.0]);
+    let active = ScoreBoost::new().log("count", 1.0);
+    let filter = json!({"op": "range", "field": "count", "lt": 25});
+    let result = coll.search_boosted("idx", query, &active, 10, 0, Some(&filter)).unwrap();
+    assert_eq!(ids(&result), vec![json!(2), json!(1), json!(0)]);
+    // Plain searches carry no breakdown.
+    assert!(coll.search_by_vector("idx", &[1.0, 0.0], 1, 0, None).unwrap().data[0].explanation.is_none());
+
+    let zero = ScoreBoost::new().decay("ts", std::time::Duration::ZERO, 1.0);
+    assert!(matches!(coll.search_boosted("idx", query, &zero, 3, 0, None), Err(VectorDbError::InvalidConfig(_))));
+    let vector = ScoreBoost::new().log("vec", 1.0);
+    assert!(matches!(coll.search_boosted("idx", query, &vector, 3, 0, None), Err(VectorDbError::InvalidConfig(_))));
+}
+
+// ============================================================
+// MMR Diversification Tests
+// ============================================================
+
+fn make_mmr_collection() -> Collection {
+    let coll = Collection::new(make_quantized_config(2));
+    coll.create_index("idx", IndexConfig::default()).unwrap();
+    // Five near-duplicates around 0°, then one record at 30° and one at 60°.
+    let degrees = [0.0f32, 0.1, 0.2, 0.3, 0.4, 30.0, 60.0];
+    let data: Vec<_> = degrees.iter().enumerate()
+        .map(|(i, d)| {
+            let r = d.to_radians();
+            HashMap::from([("id".into(), json!(i)), ("vector".into(), json!([r.cos(), r.sin()]))])
+        })
+        .collect();
+    coll.upsert_data(&data).unwrap();
+    coll
+}
+
+#[test]
+fn test_mmr_diversifies_near_duplicates() {
+    let coll = make_mmr_collection();
+    let plain = coll.search_by_vector("idx", &[1.0, 0.0], 3, 0, None).unwrap();
+    assert_eq!(ids(&plain), vec![json!(0), json!(1), json!(2)]);
+
+    let diverse = coll.search_by_vector_mmr("idx", &[1.0, 0.0], &Mmr::new(0.3), 3, 0, None).unwrap();
+    assert_eq!(ids(&diverse), vec![json!(0), json!(6), json!(5)]);
+    // Scores are similarities to the query.
+    assert!((diverse.data[1].score - 0.5).abs() < 1e-5);
+
+    let relevant = coll.search_by_vector_mmr("idx", &[1.0, 0.0], &Mmr::new(1.0), 3, 0, None).unwrap();
+    assert_eq!(ids(&relevant), ids(&plain));
+    let paged = coll.search_by_vector_mmr("idx", &[1.0, 0.0], &Mmr::new(0.3), 2, 1, None).unwrap();
+    assert_eq!(ids(&paged), vec![json!(6), json!(5)]);
+}
+
+#[test]
+fn test_mmr_respects_filters_and_validates() {
+    let coll = make_mmr_collection();
+    let filter = json!({"op": "must_not", "field": "id", "conds": [6]});
+    let result = coll.search_by_vector_mmr("idx", &[1.0, 0.0], &Mmr::new(0.3).with_candidates(10), 3, 0, Some(&filter)).unwrap();
+    assert_eq!(ids(&result)[..2], [json!(0), json!(5)]);
+    assert!(matches!(
+        coll.search_by_vector_mmr("idx", &[1.0, 0.0], &Mmr::new(1.5), 3, 0, None),
+        Err(VectorDbError::InvalidConfig(_))
+    ));
+    assert!(matches!(
+        coll.search_by_vector_mmr("missing", &[1.0, 0.0], &Mmr::default(), 3, 0, None),
+        Err(VectorDbError::IndexNotFound(_))
+    ));
+}